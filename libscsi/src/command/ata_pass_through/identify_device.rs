@@ -0,0 +1,275 @@
+use crate::{Scsi, Transport};
+
+use super::{AtaCommand, AtaProtocol, AtaResponse, AtaSector};
+
+const IDENTIFY_DEVICE: u8 = 0xec;
+
+const SERIAL_NUMBER_WORDS: (usize, usize) = (10, 20);
+const FIRMWARE_REVISION_WORDS: (usize, usize) = (23, 27);
+const MODEL_NUMBER_WORDS: (usize, usize) = (27, 47);
+const TOTAL_SECTORS_28_WORDS: usize = 60;
+const COMMAND_SET_SUPPORTED_WORD: usize = 82;
+const COMMAND_SET_SUPPORTED_EXT_WORD: usize = 83;
+const COMMAND_SET_ENABLED_EXT_WORD: usize = 86;
+const APM_LEVEL_WORD: usize = 91;
+const TOTAL_SECTORS_48_WORDS: usize = 100;
+const SECTOR_SIZE_WORD: usize = 106;
+const LOGICAL_SECTOR_SIZE_WORDS: usize = 117;
+const SECURITY_STATUS_WORD: usize = 128;
+const DATA_SET_MANAGEMENT_WORD: usize = 169;
+const SCT_COMMAND_TRANSPORT_WORD: usize = 206;
+const ROTATION_RATE_WORD: usize = 217;
+const INTEGRITY_WORD: usize = 255;
+
+const INTEGRITY_SIGNATURE: u8 = 0xa5;
+const DEFAULT_LOGICAL_SECTOR_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationRate {
+    NotReported,
+    /// Solid state
+    NonRotating,
+    Rpm(u16),
+}
+
+/// The parts of IDENTIFY DEVICE data that tell which disk sits behind a bridge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentifyDevice {
+    /// Trailing spaces are trimmed
+    pub model_number: String,
+    /// Trailing spaces are trimmed
+    pub serial_number: String,
+    /// Trailing spaces are trimmed
+    pub firmware_revision: String,
+    /// In bytes
+    pub logical_sector_size: u32,
+    /// In bytes
+    pub physical_sector_size: u32,
+    pub rotation_rate: RotationRate,
+    /// 48 bit if the device supports it, 28 bit otherwise
+    pub user_addressable_sectors: u64,
+    pub lba48_supported: bool,
+    pub smart_supported: bool,
+    /// DATA SET MANAGEMENT with the TRIM bit
+    pub trim_supported: bool,
+    pub security_supported: bool,
+    pub security_enabled: bool,
+    pub security_locked: bool,
+    pub security_frozen: bool,
+    pub apm_supported: bool,
+    /// Only set while APM is enabled, 1 is the most aggressive power saving and 0xfe the best performance
+    pub apm_level: Option<u8>,
+    pub sct_supported: bool,
+}
+
+impl IdentifyDevice {
+    pub fn decode(sector: &[u8]) -> IdentifyDevice {
+        let word = |index: usize| {
+            sector
+                .get(index * 2..index * 2 + 2)
+                .map(|w| u16::from_le_bytes([w[0], w[1]]))
+                .unwrap_or_default()
+        };
+        let bit = |index: usize, bit: u16| word(index) & (1 << bit) != 0;
+        let string = |(start, end): (usize, usize)| {
+            let bytes: Vec<u8> = (start..end).flat_map(|i| word(i).to_be_bytes()).collect();
+            String::from_utf8_lossy(&bytes)
+                .trim_matches([' ', '\0'])
+                .to_owned()
+        };
+
+        let lba48_supported = bit(COMMAND_SET_SUPPORTED_EXT_WORD, 10);
+        let user_addressable_sectors = match lba48_supported {
+            true => (0..4).rev().fold(0u64, |sectors, i| {
+                sectors << 16 | word(TOTAL_SECTORS_48_WORDS + i) as u64
+            }),
+            false => {
+                (word(TOTAL_SECTORS_28_WORDS + 1) as u64) << 16
+                    | word(TOTAL_SECTORS_28_WORDS) as u64
+            }
+        };
+
+        // Only valid if bit 14 is set and bit 15 is cleared
+        let sector_size = match word(SECTOR_SIZE_WORD) >> 14 {
+            0b01 => word(SECTOR_SIZE_WORD),
+            _ => 0,
+        };
+        let logical_sector_size = match sector_size & (1 << 12) {
+            0 => DEFAULT_LOGICAL_SECTOR_SIZE,
+            _ => {
+                ((word(LOGICAL_SECTOR_SIZE_WORDS + 1) as u32) << 16
+                    | word(LOGICAL_SECTOR_SIZE_WORDS) as u32)
+                    * 2
+            }
+        };
+        let logical_sectors_per_physical = match sector_size & (1 << 13) {
+            0 => 1,
+            _ => 1 << (sector_size & 0x0f),
+        };
+
+        let rotation_rate = match word(ROTATION_RATE_WORD) {
+            0x0001 => RotationRate::NonRotating,
+            rpm @ 0x0401..=0xfffe => RotationRate::Rpm(rpm),
+            _ => RotationRate::NotReported,
+        };
+
+        let apm_supported = bit(COMMAND_SET_SUPPORTED_EXT_WORD, 3);
+        let apm_level = match apm_supported && bit(COMMAND_SET_ENABLED_EXT_WORD, 3) {
+            true => Some(word(APM_LEVEL_WORD) as u8),
+            false => None,
+        };
+
+        IdentifyDevice {
+            model_number: string(MODEL_NUMBER_WORDS),
+            serial_number: string(SERIAL_NUMBER_WORDS),
+            firmware_revision: string(FIRMWARE_REVISION_WORDS),
+            logical_sector_size,
+            physical_sector_size: logical_sector_size * logical_sectors_per_physical,
+            rotation_rate,
+            user_addressable_sectors,
+            lba48_supported,
+            smart_supported: bit(COMMAND_SET_SUPPORTED_WORD, 0),
+            trim_supported: bit(DATA_SET_MANAGEMENT_WORD, 0),
+            security_supported: bit(SECURITY_STATUS_WORD, 0),
+            security_enabled: bit(SECURITY_STATUS_WORD, 1),
+            security_locked: bit(SECURITY_STATUS_WORD, 2),
+            security_frozen: bit(SECURITY_STATUS_WORD, 3),
+            apm_supported,
+            apm_level,
+            sct_supported: bit(SCT_COMMAND_TRANSPORT_WORD, 0),
+        }
+    }
+
+    /// In bytes
+    pub fn capacity(&self) -> u64 {
+        self.user_addressable_sectors * self.logical_sector_size as u64
+    }
+}
+
+fn check_integrity(sector: &[u8]) -> crate::Result<()> {
+    // The checksum is optional, it's only there if the signature is
+    if sector.get(INTEGRITY_WORD * 2) != Some(&INTEGRITY_SIGNATURE) {
+        return Ok(());
+    }
+
+    match sector.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) {
+        0 => Ok(()),
+        _ => Err(crate::Error::Other(
+            "Invalid IDENTIFY DEVICE checksum".to_owned(),
+        )),
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// IDENTIFY DEVICE of the ATA disk behind the bridge.
+    pub fn ata_identify_device(&self) -> crate::Result<IdentifyDevice> {
+        let response: AtaResponse<AtaSector> = self.ata_pass_through_16(&AtaCommand {
+            protocol: AtaProtocol::PioDataIn,
+            command: IDENTIFY_DEVICE,
+            count: 1,
+            ..Default::default()
+        })?;
+        check_integrity(&response.data.0)?;
+
+        Ok(IdentifyDevice::decode(&response.data.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ATA_SECTOR_SIZE;
+    use super::*;
+    use crate::{DataDirection, ReplayTransport, TraceRecord};
+
+    fn set_word(sector: &mut [u8], index: usize, value: u16) {
+        sector[index * 2..index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_string(sector: &mut [u8], (start, end): (usize, usize), value: &str) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize((end - start) * 2, b' ');
+        for (i, pair) in bytes.chunks(2).enumerate() {
+            set_word(sector, start + i, u16::from_be_bytes([pair[0], pair[1]]));
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let mut sector = [0u8; ATA_SECTOR_SIZE];
+        set_string(&mut sector, MODEL_NUMBER_WORDS, "WDC WD20SDRW-11VUUS0");
+        set_string(&mut sector, SERIAL_NUMBER_WORDS, "WD-WX11A1234567");
+        set_string(&mut sector, FIRMWARE_REVISION_WORDS, "01.01A01");
+        set_word(&mut sector, COMMAND_SET_SUPPORTED_WORD, 0x0003);
+        set_word(&mut sector, COMMAND_SET_SUPPORTED_EXT_WORD, 0x0408);
+        set_word(&mut sector, COMMAND_SET_ENABLED_EXT_WORD, 0x0408);
+        set_word(&mut sector, APM_LEVEL_WORD, 0x0080);
+        set_word(&mut sector, TOTAL_SECTORS_48_WORDS, 0x88b0);
+        set_word(&mut sector, TOTAL_SECTORS_48_WORDS + 1, 0xe8e0);
+        set_word(&mut sector, SECTOR_SIZE_WORD, 0x6003);
+        set_word(&mut sector, SECURITY_STATUS_WORD, 0x0009);
+        set_word(&mut sector, SCT_COMMAND_TRANSPORT_WORD, 0x0001);
+        set_word(&mut sector, ROTATION_RATE_WORD, 5400);
+
+        let identify = IdentifyDevice::decode(&sector);
+        assert_eq!(identify.model_number, "WDC WD20SDRW-11VUUS0");
+        assert_eq!(identify.serial_number, "WD-WX11A1234567");
+        assert_eq!(identify.firmware_revision, "01.01A01");
+        assert_eq!(identify.user_addressable_sectors, 3_907_029_168);
+        assert_eq!(identify.capacity(), 2_000_398_934_016);
+        assert_eq!(identify.logical_sector_size, 512);
+        assert_eq!(identify.physical_sector_size, 4096);
+        assert_eq!(identify.rotation_rate, RotationRate::Rpm(5400));
+        assert!(identify.lba48_supported && identify.smart_supported);
+        assert!(!identify.trim_supported);
+        assert!(identify.security_supported && identify.security_frozen);
+        assert!(!identify.security_enabled && !identify.security_locked);
+        assert_eq!(identify.apm_level, Some(0x80));
+        assert!(identify.sct_supported);
+
+        check_integrity(&sector).unwrap();
+        sector[INTEGRITY_WORD * 2] = INTEGRITY_SIGNATURE;
+        assert!(check_integrity(&sector).is_err());
+    }
+
+    #[test]
+    fn identify_device() {
+        let mut sector = [0u8; ATA_SECTOR_SIZE];
+        set_string(&mut sector, MODEL_NUMBER_WORDS, "WDC WD20SDRW-11VUUS0");
+        set_word(&mut sector, ROTATION_RATE_WORD, 5400);
+        sector[INTEGRITY_WORD * 2] = INTEGRITY_SIGNATURE;
+        let sum = sector.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        sector[INTEGRITY_WORD * 2 + 1] = sum.wrapping_neg();
+        let mut corrupted = sector;
+        corrupted[0] ^= 0x01;
+
+        let command = [
+            0x85,
+            0x08,
+            0x0e,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            IDENTIFY_DEVICE,
+            0x00,
+        ];
+        let transport = ReplayTransport::from_records(vec![
+            TraceRecord::good(&command, DataDirection::FromDevice, &sector),
+            TraceRecord::good(&command, DataDirection::FromDevice, &corrupted),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        let identify = device.ata_identify_device().unwrap();
+        assert_eq!(identify.model_number, "WDC WD20SDRW-11VUUS0");
+        assert_eq!(identify.rotation_rate, RotationRate::Rpm(5400));
+        assert!(device.ata_identify_device().is_err());
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -0,0 +1,597 @@
+#![allow(dead_code)]
+
+use std::{fmt, marker::PhantomData};
+
+use bitflags::bitflags;
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, SenseData, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+mod identify_device;
+mod smart;
+
+pub use identify_device::{IdentifyDevice, RotationRate};
+pub use smart::{SmartAttribute, SmartHealth, SmartReport};
+
+const OPERATION_CODE_12: u8 = 0xa1;
+const OPERATION_CODE_16: u8 = 0x85;
+
+const PROTOCOL_NON_DATA: u8 = 0x3;
+const PROTOCOL_PIO_DATA_IN: u8 = 0x4;
+const PROTOCOL_DMA: u8 = 0x6;
+
+const T_LENGTH_NO_DATA: u8 = 0x0;
+/// The transfer length is in the COUNT field
+const T_LENGTH_COUNT: u8 = 0x2;
+
+const ATA_SECTOR_SIZE: usize = 512;
+
+const ATA_STATUS_RETURN_DESCRIPTOR: u8 = 0x09;
+const ATA_STATUS_RETURN_DESCRIPTOR_LENGTH: usize = 14;
+
+/// ATA PASS THROUGH INFORMATION AVAILABLE
+const ASC_ATA_INFORMATION_AVAILABLE: u8 = 0x00;
+const ASCQ_ATA_INFORMATION_AVAILABLE: u8 = 0x1d;
+
+#[bitfield]
+struct AtaPassThrough12Command {
+    operation_code: B8,
+    multiple_count: B3,
+    protocol: B4,
+    reserved_0: B1,
+    off_line: B2,
+    ck_cond: B1,
+    t_type: B1,
+    t_dir: B1,
+    byte_block: B1,
+    t_length: B2,
+    features: B8,
+    count: B8,
+    lba_7_0: B8,
+    lba_15_8: B8,
+    lba_23_16: B8,
+    device: B8,
+    command: B8,
+    reserved_1: B8,
+    control: B8,
+}
+
+#[bitfield]
+struct AtaPassThrough16Command {
+    operation_code: B8,
+    multiple_count: B3,
+    protocol: B4,
+    extend: B1,
+    off_line: B2,
+    ck_cond: B1,
+    t_type: B1,
+    t_dir: B1,
+    byte_block: B1,
+    t_length: B2,
+    features: B16,
+    count: B16,
+    lba_31_24: B8,
+    lba_7_0: B8,
+    lba_39_32: B8,
+    lba_15_8: B8,
+    lba_47_40: B8,
+    lba_23_16: B8,
+    device: B8,
+    command: B8,
+    control: B8,
+}
+
+bitflags! {
+    pub struct AtaStatus: u8 {
+        const ERR                   = 0x01;
+        const SENSE_DATA_AVAILABLE  = 0x02;
+        const ALIGNMENT_ERROR       = 0x04;
+        const DRQ                   = 0x08;
+        const DEFERRED_WRITE_ERROR  = 0x10;
+        const DEVICE_FAULT          = 0x20;
+        const DRDY                  = 0x40;
+        const BSY                   = 0x80;
+    }
+}
+
+bitflags! {
+    pub struct AtaError: u8 {
+        const COMMAND_TIMEOUT       = 0x01;
+        const END_OF_MEDIA          = 0x02;
+        const ABORT                 = 0x04;
+        const ID_NOT_FOUND          = 0x10;
+        const UNCORRECTABLE         = 0x40;
+        const INTERFACE_CRC         = 0x80;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AtaProtocol {
+    #[default]
+    NonData,
+    PioDataIn,
+    /// Only transfers from the device are supported
+    Dma,
+}
+
+impl AtaProtocol {
+    fn code(self) -> u8 {
+        match self {
+            AtaProtocol::NonData => PROTOCOL_NON_DATA,
+            AtaProtocol::PioDataIn => PROTOCOL_PIO_DATA_IN,
+            AtaProtocol::Dma => PROTOCOL_DMA,
+        }
+    }
+
+    fn direction(self) -> DataDirection {
+        match self {
+            AtaProtocol::NonData => DataDirection::None,
+            AtaProtocol::PioDataIn | AtaProtocol::Dma => DataDirection::FromDevice,
+        }
+    }
+}
+
+/// ATA input registers.
+#[derive(Debug, Clone, Default)]
+pub struct AtaCommand {
+    pub protocol: AtaProtocol,
+    pub command: u8,
+    pub features: u16,
+    /// Number of 512 byte sectors for data transfers
+    pub count: u16,
+    /// 28 bit unless `extend` is set
+    pub lba: u64,
+    pub device: u8,
+    /// 48 bit command, ATA PASS-THROUGH (16) only
+    pub extend: bool,
+    /// Return the output registers even if the command succeeds
+    pub check_condition: bool,
+}
+
+impl AtaCommand {
+    fn device_register(&self) -> u8 {
+        match self.extend {
+            true => self.device,
+            false => self.device | ((self.lba >> 24) as u8 & 0x0f),
+        }
+    }
+
+    fn t_length(&self) -> u8 {
+        match self.protocol {
+            AtaProtocol::NonData => T_LENGTH_NO_DATA,
+            _ => T_LENGTH_COUNT,
+        }
+    }
+}
+
+/// ATA output registers, from the ATA Status Return sense descriptor or from fixed format sense data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtaRegisters {
+    pub extend: bool,
+    pub error: AtaError,
+    pub status: AtaStatus,
+    pub device: u8,
+    /// Bits 15:8 are only valid if `extend` is set
+    pub count: u16,
+    /// Bits 47:24 are only valid if `extend` is set, and never returned in fixed format
+    pub lba: u64,
+}
+
+impl AtaRegisters {
+    pub fn from_sense(bytes: &[u8]) -> Option<AtaRegisters> {
+        let sense = SenseData::parse(bytes)?;
+
+        if let Some(d) = sense.descriptor(ATA_STATUS_RETURN_DESCRIPTOR) {
+            if d.len() < ATA_STATUS_RETURN_DESCRIPTOR_LENGTH {
+                return None;
+            }
+
+            return Some(AtaRegisters {
+                extend: d[2] & 0x01 != 0,
+                error: AtaError::from_bits_truncate(d[3]),
+                status: AtaStatus::from_bits_truncate(d[13]),
+                device: d[12],
+                count: u16::from_be_bytes([d[4], d[5]]),
+                lba: u64::from_be_bytes([0, 0, d[10], d[8], d[6], d[11], d[9], d[7]]),
+            });
+        }
+
+        if sense.is_descriptor_format()
+            || sense.additional_sense_code != ASC_ATA_INFORMATION_AVAILABLE
+            || sense.additional_sense_code_qualifier != ASCQ_ATA_INFORMATION_AVAILABLE
+            || bytes.len() < 12
+        {
+            return None;
+        }
+
+        // The information field holds ERROR, STATUS, DEVICE and COUNT (7:0),
+        // the command-specific information field the low 24 bits of LBA.
+        Some(AtaRegisters {
+            extend: bytes[8] & 0x80 != 0,
+            error: AtaError::from_bits_truncate(bytes[3]),
+            status: AtaStatus::from_bits_truncate(bytes[4]),
+            device: bytes[5],
+            count: bytes[6] as u16,
+            lba: u32::from_be_bytes([0, bytes[11], bytes[10], bytes[9]]) as u64,
+        })
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.status
+            .intersects(AtaStatus::ERR | AtaStatus::DEVICE_FAULT)
+    }
+}
+
+impl fmt::Display for AtaRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status: {:?}, error: {:?}", self.status, self.error)
+    }
+}
+
+/// Data-in buffer for single sector PIO commands such as IDENTIFY DEVICE
+#[derive(Clone)]
+struct AtaSector([u8; ATA_SECTOR_SIZE]);
+
+impl Default for AtaSector {
+    fn default() -> Self {
+        AtaSector([0; ATA_SECTOR_SIZE])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AtaResponse<D> {
+    pub data: D,
+    /// Only returned if the command failed or `check_condition` was set
+    pub registers: Option<AtaRegisters>,
+}
+
+struct ThisCommand12<'a, D> {
+    command: &'a AtaCommand,
+
+    phantom_data: PhantomData<D>,
+}
+
+struct ThisCommand16<'a, D> {
+    command: &'a AtaCommand,
+
+    phantom_data: PhantomData<D>,
+}
+
+impl<D> Command for ThisCommand12<'_, D>
+where
+    D: Default + Clone,
+{
+    type CommandBuffer = AtaPassThrough12Command;
+
+    type DataBuffer = D;
+
+    type DataBufferWrapper = D;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<AtaResponse<D>>;
+
+    fn get_direction(&self) -> DataDirection {
+        self.command.protocol.direction()
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        let command = self.command;
+        let lba = command.lba.to_be_bytes();
+
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE_12)
+            .with_protocol(command.protocol.code())
+            .with_ck_cond(command.check_condition as u8)
+            .with_t_dir((self.get_direction() == DataDirection::FromDevice) as u8)
+            .with_byte_block(1)
+            .with_t_length(command.t_length())
+            .with_features(command.features as u8)
+            .with_count(command.count as u8)
+            .with_lba_7_0(lba[7])
+            .with_lba_15_8(lba[6])
+            .with_lba_23_16(lba[5])
+            .with_device(command.device_register())
+            .with_command(command.command)
+    }
+
+    fn get_data(&self) -> Self::DataBuffer {
+        Self::DataBuffer::default()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        process_ata_result(result)
+    }
+}
+
+impl<D> Command for ThisCommand16<'_, D>
+where
+    D: Default + Clone,
+{
+    type CommandBuffer = AtaPassThrough16Command;
+
+    type DataBuffer = D;
+
+    type DataBufferWrapper = D;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<AtaResponse<D>>;
+
+    fn get_direction(&self) -> DataDirection {
+        self.command.protocol.direction()
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        let command = self.command;
+        let lba = command.lba.to_be_bytes();
+
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE_16)
+            .with_protocol(command.protocol.code())
+            .with_extend(command.extend as u8)
+            .with_ck_cond(command.check_condition as u8)
+            .with_t_dir((self.get_direction() == DataDirection::FromDevice) as u8)
+            .with_byte_block(1)
+            .with_t_length(command.t_length())
+            .with_features(command.features)
+            .with_count(command.count)
+            .with_lba_7_0(lba[7])
+            .with_lba_15_8(lba[6])
+            .with_lba_23_16(lba[5])
+            .with_lba_31_24(lba[4])
+            .with_lba_39_32(lba[3])
+            .with_lba_47_40(lba[2])
+            .with_device(command.device_register())
+            .with_command(command.command)
+    }
+
+    fn get_data(&self) -> Self::DataBuffer {
+        Self::DataBuffer::default()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        process_ata_result(result)
+    }
+}
+
+fn process_ata_result<D: Default + Clone>(
+    result: &ResultData<D, BytesSenseBuffer>,
+) -> crate::Result<AtaResponse<D>> {
+    result.check_ioctl_error()?;
+
+    let sense = result.sense_buffer.as_ref().unwrap().as_byte_slice();
+    let sense = &sense[..usize::min(result.transfered_sense_length, sense.len())];
+
+    // With CK_COND set, a successful command ends with RECOVERED ERROR,
+    // ATA PASS THROUGH INFORMATION AVAILABLE. Everything else is left to the usual checks.
+    let registers = AtaRegisters::from_sense(sense);
+    match (registers, result.sense_data()) {
+        (Some(registers), _) if registers.is_error() => {
+            return Err(crate::Error::Ata(registers));
+        }
+        (Some(_), Some(sense_data))
+            if sense_data.additional_sense_code == ASC_ATA_INFORMATION_AVAILABLE
+                && sense_data.additional_sense_code_qualifier == ASCQ_ATA_INFORMATION_AVAILABLE => {
+        }
+        _ => result.check_common_error()?,
+    }
+
+    Ok(AtaResponse {
+        // there is no data buffer for non-data commands
+        data: result.data.as_deref().cloned().unwrap_or_default(),
+        registers,
+    })
+}
+
+impl<T: Transport> Scsi<T> {
+    /// 28 bit commands only, `extend` is ignored. Some USB bridges only support this one.
+    ///
+    /// `D` is the data-in buffer and has to match `count` sectors, use `()` for non-data commands.
+    /// ATA errors are returned as `Error::Ata`.
+    pub fn ata_pass_through_12<D: Default + Clone>(
+        &self,
+        command: &AtaCommand,
+    ) -> crate::Result<AtaResponse<D>> {
+        self.execute_command(&ThisCommand12 {
+            command,
+            phantom_data: PhantomData,
+        })
+    }
+
+    /// `D` is the data-in buffer and has to match `count` sectors, use `()` for non-data commands.
+    /// ATA errors are returned as `Error::Ata`.
+    pub fn ata_pass_through_16<D: Default + Clone>(
+        &self,
+        command: &AtaCommand,
+    ) -> crate::Result<AtaResponse<D>> {
+        self.execute_command(&ThisCommand16 {
+            command,
+            phantom_data: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DriverStatus, MaskedStatus, ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const ATA_PASS_THROUGH_12_COMMAND_LENGTH: usize = 12;
+    const ATA_PASS_THROUGH_16_COMMAND_LENGTH: usize = 16;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<AtaPassThrough12Command>(),
+            ATA_PASS_THROUGH_12_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(AtaPassThrough12Command))
+        );
+        assert_eq!(
+            size_of::<AtaPassThrough16Command>(),
+            ATA_PASS_THROUGH_16_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(AtaPassThrough16Command))
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let command = AtaCommand {
+            protocol: AtaProtocol::PioDataIn,
+            command: 0xb0,
+            features: 0xd0,
+            count: 1,
+            lba: 0xc24f00,
+            device: 0xa0,
+            ..Default::default()
+        };
+        let this_command: ThisCommand12<()> = ThisCommand12 {
+            command: &command,
+            phantom_data: PhantomData,
+        };
+        assert_eq!(
+            this_command.get_command().into_bytes(),
+            [0xa1, 0x08, 0x0e, 0xd0, 0x01, 0x00, 0x4f, 0xc2, 0xa0, 0xb0, 0x00, 0x00]
+        );
+
+        let command = AtaCommand {
+            protocol: AtaProtocol::Dma,
+            command: 0x25,
+            count: 8,
+            lba: 0x0123_4567_89ab,
+            device: 0x40,
+            extend: true,
+            ..Default::default()
+        };
+        let this_command: ThisCommand16<()> = ThisCommand16 {
+            command: &command,
+            phantom_data: PhantomData,
+        };
+        assert_eq!(
+            this_command.get_command().into_bytes(),
+            [
+                0x85, 0x0d, 0x0e, 0x00, 0x00, 0x00, 0x08, 0x45, 0xab, 0x23, 0x89, 0x01, 0x67, 0x40,
+                0x25, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_status_return() {
+        let descriptor = [
+            0x72, 0x01, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x0e, 0x09, 0x0c, 0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x4f, 0x00, 0xc2, 0x00, 0x50,
+        ];
+        let registers = AtaRegisters::from_sense(&descriptor).unwrap();
+        assert!(registers.extend);
+        assert_eq!(
+            registers.status,
+            AtaStatus::DRDY | AtaStatus::DEFERRED_WRITE_ERROR
+        );
+        assert_eq!(registers.lba, 0xc24f00);
+        assert!(!registers.is_error());
+
+        let fixed = [
+            0x70, 0x00, 0x0b, 0x04, 0x51, 0x40, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1d,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let registers = AtaRegisters::from_sense(&fixed).unwrap();
+        assert_eq!(registers.error, AtaError::ABORT);
+        assert_eq!(
+            registers.status,
+            AtaStatus::DRDY | AtaStatus::DEFERRED_WRITE_ERROR | AtaStatus::ERR
+        );
+        assert!(registers.is_error());
+
+        // Not ATA PASS THROUGH INFORMATION AVAILABLE
+        let mut fixed = fixed;
+        fixed[13] = 0x00;
+        assert!(AtaRegisters::from_sense(&fixed).is_none());
+    }
+
+    #[test]
+    fn pass_through() {
+        let record = |command: &[u8], sense_key, error, count, status| {
+            let (asc, ascq) = match sense_key {
+                0x01 => (
+                    ASC_ATA_INFORMATION_AVAILABLE,
+                    ASCQ_ATA_INFORMATION_AVAILABLE,
+                ),
+                _ => (0x00, 0x00),
+            };
+            TraceRecord {
+                sense: vec![
+                    0x72, sense_key, asc, ascq, 0x00, 0x00, 0x00, 0x0e, 0x09, 0x0c, 0x00, error,
+                    0x00, count, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, status,
+                ],
+                masked_status: MaskedStatus::CHECK_CONDITION,
+                driver_status: DriverStatus::SENSE,
+                ..TraceRecord::good(command, DataDirection::None, &[])
+            }
+        };
+        let mut check_power_mode_16 = [0u8; 16];
+        check_power_mode_16[..3].copy_from_slice(&[OPERATION_CODE_16, 0x06, 0x04]);
+        check_power_mode_16[14] = 0xe5;
+        let mut nop = check_power_mode_16;
+        nop[14] = 0x00;
+        let transport = ReplayTransport::from_records(vec![
+            record(
+                &[OPERATION_CODE_12, 0x06, 0x24, 0, 0, 0, 0, 0, 0, 0xe5, 0, 0],
+                0x01,
+                0x00,
+                0xff,
+                0x40,
+            ),
+            TraceRecord::good(&check_power_mode_16, DataDirection::None, &[]),
+            record(&nop, 0x0b, 0x04, 0x00, 0x41),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+        let check_power_mode = AtaCommand {
+            command: 0xe5,
+            check_condition: true,
+            ..Default::default()
+        };
+
+        let registers = device
+            .ata_pass_through_12::<()>(&check_power_mode)
+            .unwrap()
+            .registers
+            .unwrap();
+        assert_eq!(registers.count, 0xff);
+        assert_eq!(registers.status, AtaStatus::DRDY);
+
+        // without CK_COND there's nothing to return
+        let check_power_mode = AtaCommand {
+            check_condition: false,
+            ..check_power_mode
+        };
+        let response = device.ata_pass_through_16::<()>(&check_power_mode).unwrap();
+        assert!(response.registers.is_none());
+
+        match device.ata_pass_through_16::<()>(&AtaCommand::default()) {
+            Err(crate::Error::Ata(registers)) => {
+                assert_eq!(registers.error, AtaError::ABORT);
+                assert!(registers.status.contains(AtaStatus::ERR));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
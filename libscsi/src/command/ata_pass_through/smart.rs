@@ -0,0 +1,318 @@
+use crate::{Scsi, Transport};
+
+use super::{AtaCommand, AtaProtocol, AtaResponse, AtaSector};
+
+const SMART: u8 = 0xb0;
+const SMART_READ_DATA: u16 = 0xd0;
+const SMART_READ_THRESHOLDS: u16 = 0xd1;
+const SMART_RETURN_STATUS: u16 = 0xda;
+/// LBA mid 0x4f and LBA high 0xc2, required by every SMART command
+const SMART_SIGNATURE: u64 = 0xc2_4f00;
+/// LBA mid 0xf4 and LBA high 0x2c, returned by RETURN STATUS if a threshold was exceeded
+const SMART_THRESHOLD_EXCEEDED_SIGNATURE: u64 = 0x2c_f400;
+const SMART_SIGNATURE_MASK: u64 = 0xff_ff00;
+
+const ATTRIBUTE_TABLE_OFFSET: usize = 2;
+const ATTRIBUTE_ENTRY_LENGTH: usize = 12;
+const ATTRIBUTE_ENTRY_COUNT: usize = 30;
+const PREFAILURE_FLAG: u16 = 0x0001;
+
+/// Names smartctl uses, vendors may give some ids a different meaning.
+const ATTRIBUTE_NAMES: &[(u8, &str)] = &[
+    (1, "Raw_Read_Error_Rate"),
+    (2, "Throughput_Performance"),
+    (3, "Spin_Up_Time"),
+    (4, "Start_Stop_Count"),
+    (5, "Reallocated_Sector_Ct"),
+    (7, "Seek_Error_Rate"),
+    (8, "Seek_Time_Performance"),
+    (9, "Power_On_Hours"),
+    (10, "Spin_Retry_Count"),
+    (11, "Calibration_Retry_Count"),
+    (12, "Power_Cycle_Count"),
+    (22, "Helium_Level"),
+    (183, "Runtime_Bad_Block"),
+    (184, "End-to-End_Error"),
+    (187, "Reported_Uncorrect"),
+    (188, "Command_Timeout"),
+    (189, "High_Fly_Writes"),
+    (190, "Airflow_Temperature_Cel"),
+    (191, "G-Sense_Error_Rate"),
+    (192, "Power-Off_Retract_Count"),
+    (193, "Load_Cycle_Count"),
+    (194, "Temperature_Celsius"),
+    (195, "Hardware_ECC_Recovered"),
+    (196, "Reallocated_Event_Count"),
+    (197, "Current_Pending_Sector"),
+    (198, "Offline_Uncorrectable"),
+    (199, "UDMA_CRC_Error_Count"),
+    (200, "Multi_Zone_Error_Rate"),
+    (240, "Head_Flying_Hours"),
+    (241, "Total_LBAs_Written"),
+    (242, "Total_LBAs_Read"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmartHealth {
+    Passed,
+    Failing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    pub name: &'static str,
+    pub flags: u16,
+    /// Normalized value, higher is better
+    pub current: u8,
+    pub worst: u8,
+    /// 0 if the attribute can't fail
+    pub threshold: u8,
+    /// 48 bit, vendor specific
+    pub raw_value: u64,
+}
+
+impl SmartAttribute {
+    /// Parses the attribute table of SMART READ DATA and matches it with SMART READ THRESHOLDS.
+    pub fn parse_all(data: &[u8], thresholds: &[u8]) -> Vec<SmartAttribute> {
+        let entries = |bytes: &'_ [u8]| {
+            bytes
+                .get(ATTRIBUTE_TABLE_OFFSET..)
+                .unwrap_or_default()
+                .chunks_exact(ATTRIBUTE_ENTRY_LENGTH)
+                .take(ATTRIBUTE_ENTRY_COUNT)
+                .filter(|entry| entry[0] != 0)
+                .map(|entry| entry.to_vec())
+                .collect::<Vec<_>>()
+        };
+        let thresholds = entries(thresholds);
+
+        entries(data)
+            .into_iter()
+            .map(|entry| {
+                let id = entry[0];
+                let mut raw_value = [0; 8];
+                raw_value[..6].copy_from_slice(&entry[5..11]);
+
+                SmartAttribute {
+                    id,
+                    name: attribute_name(id),
+                    flags: u16::from_le_bytes([entry[1], entry[2]]),
+                    current: entry[3],
+                    worst: entry[4],
+                    threshold: thresholds
+                        .iter()
+                        .find(|t| t[0] == id)
+                        .map(|t| t[1])
+                        .unwrap_or_default(),
+                    raw_value: u64::from_le_bytes(raw_value),
+                }
+            })
+            .collect()
+    }
+
+    /// A failing pre-failure attribute predicts a drive failure, a failing advisory one only means
+    /// the drive is getting old.
+    pub fn is_prefailure(&self) -> bool {
+        self.flags & PREFAILURE_FLAG != 0
+    }
+
+    pub fn is_failing(&self) -> bool {
+        self.threshold != 0 && self.current <= self.threshold
+    }
+}
+
+fn attribute_name(id: u8) -> &'static str {
+    ATTRIBUTE_NAMES
+        .iter()
+        .find(|(i, _)| *i == id)
+        .map(|(_, name)| *name)
+        .unwrap_or("Unknown_Attribute")
+}
+
+#[derive(Debug, Clone)]
+pub struct SmartReport {
+    /// FAILING if the drive says a threshold was exceeded or a pre-failure attribute is failing
+    pub health: SmartHealth,
+    pub attributes: Vec<SmartAttribute>,
+}
+
+fn check_checksum(sector: &[u8], name: &str) -> crate::Result<()> {
+    match sector.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) {
+        0 => Ok(()),
+        _ => Err(crate::Error::Other(format!("Invalid {} checksum", name))),
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    fn smart_command<D: Default + Clone>(
+        &self,
+        feature: u16,
+        protocol: AtaProtocol,
+    ) -> crate::Result<AtaResponse<D>> {
+        self.ata_pass_through_16(&AtaCommand {
+            protocol,
+            command: SMART,
+            features: feature,
+            count: match protocol {
+                AtaProtocol::NonData => 0,
+                _ => 1,
+            },
+            lba: SMART_SIGNATURE,
+            check_condition: protocol == AtaProtocol::NonData,
+            ..Default::default()
+        })
+    }
+
+    pub fn smart_return_status(&self) -> crate::Result<SmartHealth> {
+        let response: AtaResponse<()> =
+            self.smart_command(SMART_RETURN_STATUS, AtaProtocol::NonData)?;
+        let registers = response.registers.ok_or_else(|| {
+            crate::Error::Other("The device didn't return the SMART status".to_owned())
+        })?;
+
+        match registers.lba & SMART_SIGNATURE_MASK {
+            SMART_SIGNATURE => Ok(SmartHealth::Passed),
+            SMART_THRESHOLD_EXCEEDED_SIGNATURE => Ok(SmartHealth::Failing),
+            _ => Err(crate::Error::Other(format!(
+                "Unknown SMART status: {:06x}",
+                registers.lba
+            ))),
+        }
+    }
+
+    /// SMART READ DATA together with SMART READ THRESHOLDS.
+    pub fn smart_read_attributes(&self) -> crate::Result<Vec<SmartAttribute>> {
+        let data: AtaResponse<AtaSector> =
+            self.smart_command(SMART_READ_DATA, AtaProtocol::PioDataIn)?;
+        check_checksum(&data.data.0, "SMART data")?;
+
+        let thresholds: AtaResponse<AtaSector> =
+            self.smart_command(SMART_READ_THRESHOLDS, AtaProtocol::PioDataIn)?;
+        check_checksum(&thresholds.data.0, "SMART thresholds")?;
+
+        Ok(SmartAttribute::parse_all(&data.data.0, &thresholds.data.0))
+    }
+
+    pub fn smart(&self) -> crate::Result<SmartReport> {
+        let health = self.smart_return_status()?;
+        let attributes = self.smart_read_attributes()?;
+
+        let health = match attributes
+            .iter()
+            .any(|a| a.is_prefailure() && a.is_failing())
+        {
+            true => SmartHealth::Failing,
+            false => health,
+        };
+
+        Ok(SmartReport { health, attributes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ATA_SECTOR_SIZE;
+    use super::*;
+    use crate::{DataDirection, DriverStatus, MaskedStatus, ReplayTransport, TraceRecord};
+
+    fn with_checksum(mut sector: [u8; ATA_SECTOR_SIZE]) -> [u8; ATA_SECTOR_SIZE] {
+        let sum = sector[..ATA_SECTOR_SIZE - 1]
+            .iter()
+            .fold(0u8, |sum, b| sum.wrapping_add(*b));
+        sector[ATA_SECTOR_SIZE - 1] = sum.wrapping_neg();
+        sector
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let mut data = [0u8; ATA_SECTOR_SIZE];
+        data[0] = 0x10;
+        data[2..14].copy_from_slice(&[
+            0x05, 0x33, 0x00, 0xc8, 0xc8, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        data[14..26].copy_from_slice(&[
+            0xc2, 0x22, 0x00, 0x70, 0x60, 0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        data[26..38].copy_from_slice(&[
+            0x09, 0x32, 0x00, 0x01, 0x01, 0x10, 0x27, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        let data = with_checksum(data);
+
+        let mut thresholds = [0u8; ATA_SECTOR_SIZE];
+        thresholds[0] = 0x10;
+        thresholds[2..4].copy_from_slice(&[0x05, 0x8c]);
+        thresholds[14..16].copy_from_slice(&[0x09, 0x00]);
+        let thresholds = with_checksum(thresholds);
+
+        check_checksum(&data, "SMART data").unwrap();
+        check_checksum(&thresholds, "SMART thresholds").unwrap();
+        let mut corrupted = data;
+        corrupted[3] ^= 0x01;
+        assert!(check_checksum(&corrupted, "SMART data").is_err());
+
+        let attributes = SmartAttribute::parse_all(&data, &thresholds);
+        assert_eq!(attributes.len(), 3);
+        assert_eq!(attributes[0].name, "Reallocated_Sector_Ct");
+        assert_eq!(attributes[0].threshold, 140);
+        assert_eq!(attributes[0].raw_value, 0x0102);
+        assert!(attributes[0].is_prefailure());
+        assert!(!attributes[0].is_failing());
+        assert_eq!(attributes[1].name, "Temperature_Celsius");
+        assert_eq!(attributes[1].raw_value, 35);
+        // no threshold, can't fail even at the lowest value
+        assert_eq!(attributes[2].raw_value, 10000);
+        assert!(!attributes[2].is_failing());
+    }
+
+    #[test]
+    fn smart_report() {
+        let command = |feature: u8, protocol: u8, t_length: u8, count: u8| {
+            [
+                0x85, protocol, t_length, 0x00, feature, 0x00, count, 0x00, 0x00, 0x00, 0x4f, 0x00,
+                0xc2, 0x00, SMART, 0x00,
+            ]
+        };
+        let return_status = |lba_mid, lba_high| TraceRecord {
+            sense: vec![
+                0x72, 0x01, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x0e, 0x09, 0x0c, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, lba_mid, 0x00, lba_high, 0x00, 0x40,
+            ],
+            masked_status: MaskedStatus::CHECK_CONDITION,
+            driver_status: DriverStatus::SENSE,
+            ..TraceRecord::good(&command(0xda, 0x06, 0x24, 0), DataDirection::None, &[])
+        };
+        let mut data = [0u8; ATA_SECTOR_SIZE];
+        data[0] = 0x10;
+        data[2..14].copy_from_slice(&[
+            0x05, 0x33, 0x00, 0x08, 0x08, 0xd0, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        let mut thresholds = [0u8; ATA_SECTOR_SIZE];
+        thresholds[0] = 0x10;
+        thresholds[2..4].copy_from_slice(&[0x05, 0x8c]);
+        let transport = ReplayTransport::from_records(vec![
+            return_status(0x4f, 0xc2),
+            TraceRecord::good(
+                &command(0xd0, 0x08, 0x0e, 1),
+                DataDirection::FromDevice,
+                &with_checksum(data),
+            ),
+            TraceRecord::good(
+                &command(0xd1, 0x08, 0x0e, 1),
+                DataDirection::FromDevice,
+                &with_checksum(thresholds),
+            ),
+            return_status(0xf4, 0x2c),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        // the drive says it's fine, but a pre-failure attribute is below its threshold
+        let report = device.smart().unwrap();
+        assert_eq!(report.health, SmartHealth::Failing);
+        assert_eq!(report.attributes.len(), 1);
+        assert_eq!(report.attributes[0].raw_value, 2000);
+        assert!(report.attributes[0].is_prefailure() && report.attributes[0].is_failing());
+        assert_eq!(device.smart_return_status().unwrap(), SmartHealth::Failing);
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{Error, Scsi, Transport};
+
+/// Blocks per WRITE SAME if the device doesn't report a limit, small enough for frequent progress updates.
+const DEFAULT_WRITE_SAME_LENGTH: u64 = 0x0010_0000;
+/// Blocks per WRITE if the device doesn't report a limit
+const DEFAULT_TRANSFER_LENGTH: u32 = 0x800;
+
+/// How `erase` got rid of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseMethod {
+    Unmap,
+    /// WRITE SAME(16) with the UNMAP bit
+    WriteSameUnmap,
+    /// Zeros written by the device with WRITE SAME(16)
+    WriteSame,
+    /// Zeros sent for every block with WRITE(16), only if allowed
+    ZeroFill,
+}
+
+/// The method `erase_range` uses and the device limits it's sent with, see [`Scsi::erase_plan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErasePlan {
+    method: EraseMethod,
+    zero_fill: bool,
+    logical_block_length: u32,
+    unmap_block_limit: u32,
+    unmap_descriptor_limit: u32,
+    write_same_length: u64,
+    transfer_length: u32,
+}
+
+impl ErasePlan {
+    /// `WriteSame` turns into `ZeroFill` once the device rejects it, if that's allowed.
+    pub fn method(&self) -> EraseMethod {
+        self.method
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Deallocates or zeros the whole medium, the fastest way the device supports.
+    /// Fails with [`Error::InUse`] unless the disk can be claimed, see [`Scsi::claim_exclusive`].
+    /// Sending zeros for every block takes hours, it's only done if `zero_fill` is set, otherwise
+    /// devices without UNMAP or WRITE SAME fail with [`Error::EraseNotSupported`].
+    /// `progress` gets the number of blocks done so far and the total.
+    pub fn erase<F: FnMut(u64, u64)>(
+        &self,
+        zero_fill: bool,
+        progress: F,
+    ) -> crate::Result<EraseMethod> {
+        let capacity = self.read_capacity16()?;
+        let mut plan = self.erase_plan(capacity.logical_block_length_in_bytes, zero_fill);
+
+        self.erase_range(0, capacity.logical_block_count, &mut plan, progress)?;
+
+        Ok(plan.method())
+    }
+
+    /// Picks the erase method from the VPD pages, for erasing in several `erase_range` calls.
+    pub fn erase_plan(&self, logical_block_length: u32, zero_fill: bool) -> ErasePlan {
+        // bridges without the VPD pages don't support any of it
+        let unmap_block_limit = match self.inquiry_unmap_support().unwrap_or(false) {
+            true => self.inquiry_unmap_block_limit().unwrap_or(0),
+            false => 0,
+        };
+        let method = match unmap_block_limit {
+            0 if self.inquiry_write_same_unmap_support().unwrap_or(false) => {
+                EraseMethod::WriteSameUnmap
+            }
+            0 => EraseMethod::WriteSame,
+            _ => EraseMethod::Unmap,
+        };
+        let unmap_descriptor_limit = match method {
+            EraseMethod::Unmap => self.inquiry_unmap_descriptor_limit().unwrap_or(1),
+            _ => 1,
+        };
+        let write_same_length = match self.inquiry_maximum_write_same_length().unwrap_or(0) {
+            0 => DEFAULT_WRITE_SAME_LENGTH,
+            n => u64::min(n, u32::MAX as u64),
+        };
+        let transfer_length = match self.inquiry_maximum_transfer_length().unwrap_or(0) {
+            0 => DEFAULT_TRANSFER_LENGTH,
+            n => u32::min(n, DEFAULT_TRANSFER_LENGTH),
+        };
+
+        ErasePlan {
+            method,
+            zero_fill,
+            logical_block_length,
+            unmap_block_limit,
+            unmap_descriptor_limit,
+            write_same_length,
+            transfer_length,
+        }
+    }
+
+    /// Like `erase`, but only `block_count` blocks starting at `lba`, the way `plan` says.
+    pub fn erase_range<F: FnMut(u64, u64)>(
+        &self,
+        lba: u64,
+        block_count: u64,
+        plan: &mut ErasePlan,
+        mut progress: F,
+    ) -> crate::Result<()> {
+        let _claim = self.claim_exclusive()?;
+
+        let zeros = vec![0; plan.logical_block_length as usize];
+        let write_same = |unmap: bool, progress: &mut F| {
+            let end = lba + block_count;
+            let mut next = lba;
+            while next < end {
+                let blocks = u64::min(end - next, plan.write_same_length);
+                self.write_same16(next, blocks as u32, &zeros, unmap)?;
+                next += blocks;
+                progress(next - lba, block_count);
+            }
+
+            Ok::<_, Error>(())
+        };
+
+        match plan.method {
+            EraseMethod::Unmap => self.unmap(
+                lba,
+                block_count,
+                plan.unmap_block_limit,
+                plan.unmap_descriptor_limit,
+                |done| progress(done, block_count),
+            ),
+            EraseMethod::WriteSameUnmap => write_same(true, &mut progress),
+            EraseMethod::WriteSame => match write_same(false, &mut progress) {
+                Err(Error::CheckCondition(sense)) if sense.is_unsupported_command() => {
+                    if !plan.zero_fill {
+                        return Err(Error::EraseNotSupported("WRITE SAME(16)".to_owned()));
+                    }
+
+                    plan.method = EraseMethod::ZeroFill;
+                    self.zero_fill(lba, block_count, plan, progress)
+                }
+                result => result,
+            },
+            EraseMethod::ZeroFill => self.zero_fill(lba, block_count, plan, progress),
+        }
+    }
+
+    fn zero_fill<F: FnMut(u64, u64)>(
+        &self,
+        lba: u64,
+        block_count: u64,
+        plan: &ErasePlan,
+        mut progress: F,
+    ) -> crate::Result<()> {
+        let logical_block_length = plan.logical_block_length;
+        let transfer_length = plan.transfer_length;
+        let zeros = vec![0; transfer_length as usize * logical_block_length as usize];
+
+        let end = lba + block_count;
+        let mut next = lba;
+        while next < end {
+            let blocks = u64::min(end - next, transfer_length as u64) as usize;
+            self.write16(
+                next,
+                &zeros[..blocks * logical_block_length as usize],
+                logical_block_length,
+            )?;
+            next += blocks as u64;
+            progress(next - lba, block_count);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataDirection, ReplayTransport, TraceRecord};
+
+    const BLOCK_LENGTH: u32 = 512;
+
+    fn provisioning_page(byte_5: u8) -> TraceRecord {
+        TraceRecord::good(
+            &[0x12, 0x01, 0xb2, 0x00, 0x40, 0x00],
+            DataDirection::FromDevice,
+            &[0x00, 0xb2, 0x00, 0x04, 0x00, byte_5, 0x00, 0x00],
+        )
+    }
+
+    fn block_limits_page() -> TraceRecord {
+        let mut page = vec![0; 0x40];
+        page[1] = 0xb0;
+        page[3] = 0x3c;
+        // maximum transfer length
+        page[8..12].copy_from_slice(&0x800u32.to_be_bytes());
+        // maximum unmap LBA count and block descriptor count
+        page[20..24].copy_from_slice(&8u32.to_be_bytes());
+        page[24..28].copy_from_slice(&1u32.to_be_bytes());
+        // maximum write same length
+        page[36..44].copy_from_slice(&2u64.to_be_bytes());
+
+        TraceRecord::good(
+            &[0x12, 0x01, 0xb0, 0x00, 0x40, 0x00],
+            DataDirection::FromDevice,
+            &page,
+        )
+    }
+
+    fn command_16(operation_code: u8, byte_1: u8, lba: u64, blocks: u32) -> Vec<u8> {
+        let mut command = vec![operation_code, byte_1];
+        command.extend_from_slice(&lba.to_be_bytes());
+        command.extend_from_slice(&blocks.to_be_bytes());
+        command.extend_from_slice(&[0x00, 0x00]);
+        command
+    }
+
+    #[test]
+    fn unmap() {
+        let mut capacity = vec![0; 0x20];
+        capacity[..8].copy_from_slice(&15u64.to_be_bytes());
+        capacity[8..12].copy_from_slice(&BLOCK_LENGTH.to_be_bytes());
+        let unmap = TraceRecord::good(
+            &[0x42, 0, 0, 0, 0, 0, 0, 0, 0x18, 0],
+            DataDirection::ToDevice,
+            &[],
+        );
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                TraceRecord::good(
+                    &command_16(0x9e, 0x10, 0, 0x20),
+                    DataDirection::FromDevice,
+                    &capacity,
+                ),
+                provisioning_page(0xc0),
+                block_limits_page(),
+                block_limits_page(),
+                block_limits_page(),
+                block_limits_page(),
+                unmap.clone(),
+                unmap,
+            ]),
+        );
+
+        let mut updates = vec![];
+        let method = device
+            .erase(false, |done, total| updates.push((done, total)))
+            .unwrap();
+        assert_eq!(method, EraseMethod::Unmap);
+        assert_eq!(updates, [(8, 16), (16, 16)]);
+        assert_eq!(device.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn write_same() {
+        let write_same = |unmap: bool, lba| {
+            TraceRecord::good(
+                &command_16(0x93, (unmap as u8) << 3, lba, 2),
+                DataDirection::ToDevice,
+                &[],
+            )
+        };
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                provisioning_page(0x40),
+                provisioning_page(0x40),
+                block_limits_page(),
+                block_limits_page(),
+                write_same(true, 0),
+                write_same(true, 2),
+            ]),
+        );
+
+        let mut plan = device.erase_plan(BLOCK_LENGTH, false);
+        assert_eq!(plan.method(), EraseMethod::WriteSameUnmap);
+        let mut updates = vec![];
+        device
+            .erase_range(0, 4, &mut plan, |done, total| updates.push((done, total)))
+            .unwrap();
+        assert_eq!(updates, [(2, 4), (4, 4)]);
+        assert_eq!(device.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn zero_fill() {
+        let rejected = TraceRecord::check_condition(
+            &command_16(0x93, 0x00, 0, 2),
+            DataDirection::ToDevice,
+            (0x05, 0x20, 0x00),
+        );
+        let write = |lba| {
+            TraceRecord::good(
+                &command_16(0x8a, 0x00, lba, 2),
+                DataDirection::ToDevice,
+                &[],
+            )
+        };
+        let plan = [
+            provisioning_page(0x00),
+            provisioning_page(0x00),
+            block_limits_page(),
+            block_limits_page(),
+        ];
+        let mut records = plan.to_vec();
+        records.push(rejected.clone());
+        records.extend_from_slice(&plan);
+        records.extend_from_slice(&[rejected, write(0), write(2)]);
+        let device = Scsi::with_transport("replay", ReplayTransport::from_records(records));
+
+        // sending zeros for every block has to be allowed
+        let mut plan = device.erase_plan(BLOCK_LENGTH, false);
+        assert_eq!(plan.method(), EraseMethod::WriteSame);
+        assert!(matches!(
+            device.erase_range(0, 2, &mut plan, |_, _| {}),
+            Err(Error::EraseNotSupported(_))
+        ));
+
+        let mut plan = device.erase_plan(BLOCK_LENGTH, true);
+        device.erase_range(0, 2, &mut plan, |_, _| {}).unwrap();
+        assert_eq!(plan.method(), EraseMethod::ZeroFill);
+        // WRITE SAME isn't tried again
+        device.erase_range(2, 2, &mut plan, |_, _| {}).unwrap();
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -1,6 +1,6 @@
 use modular_bitfield_msb::prelude::*;
 
-use crate::Scsi;
+use crate::{Scsi, Transport};
 
 const BLOCK_LIMITS_VPD_PAGE_CODE: u8 = 0xb0;
 
@@ -37,12 +37,40 @@ impl Default for BlockLimitsVPDPage {
     }
 }
 
-impl Scsi {
+impl<T: Transport> Scsi<T> {
     pub fn inquiry_unmap_block_limit(&self) -> crate::Result<u32> {
         let data: BlockLimitsVPDPage = self.inquiry_general(Some(BLOCK_LIMITS_VPD_PAGE_CODE))?;
 
         Ok(data.maximum_unmap_lba_count())
     }
+
+    /// Maximum number of block descriptors in one UNMAP command.
+    pub fn inquiry_unmap_descriptor_limit(&self) -> crate::Result<u32> {
+        let data: BlockLimitsVPDPage = self.inquiry_general(Some(BLOCK_LIMITS_VPD_PAGE_CODE))?;
+
+        Ok(data.maximum_unmap_block_descriptor_count())
+    }
+
+    /// In logical blocks, 0 if the device doesn't report a limit.
+    pub fn inquiry_maximum_write_same_length(&self) -> crate::Result<u64> {
+        let data: BlockLimitsVPDPage = self.inquiry_general(Some(BLOCK_LIMITS_VPD_PAGE_CODE))?;
+
+        Ok(data.maximum_write_same_length())
+    }
+
+    /// In logical blocks, 0 if the device doesn't report a limit.
+    pub fn inquiry_maximum_transfer_length(&self) -> crate::Result<u32> {
+        let data: BlockLimitsVPDPage = self.inquiry_general(Some(BLOCK_LIMITS_VPD_PAGE_CODE))?;
+
+        Ok(data.maximum_transfer_length())
+    }
+
+    /// In logical blocks, 0 if the device doesn't report it.
+    pub fn inquiry_optimal_transfer_length(&self) -> crate::Result<u32> {
+        let data: BlockLimitsVPDPage = self.inquiry_general(Some(BLOCK_LIMITS_VPD_PAGE_CODE))?;
+
+        Ok(data.optimal_transfer_length())
+    }
 }
 
 #[cfg(test)]
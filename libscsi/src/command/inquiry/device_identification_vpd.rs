@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::{Scsi, Transport};
+
+const DEVICE_IDENTIFICATION_VPD_PAGE_CODE: u8 = 0x83;
+const DESIGNATION_DESCRIPTOR_HEADER_LENGTH: usize = 4;
+
+const CODE_SET_BINARY: u8 = 0x1;
+
+const DESIGNATOR_TYPE_VENDOR_SPECIFIC: u8 = 0x0;
+const DESIGNATOR_TYPE_T10_VENDOR_ID: u8 = 0x1;
+const DESIGNATOR_TYPE_EUI_64: u8 = 0x2;
+const DESIGNATOR_TYPE_NAA: u8 = 0x3;
+const DESIGNATOR_TYPE_SCSI_NAME_STRING: u8 = 0x8;
+
+const T10_VENDOR_IDENTIFICATION_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Association {
+    LogicalUnit,
+    TargetPort,
+    TargetDevice,
+    Reserved,
+}
+
+impl From<u8> for Association {
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0x0 => Association::LogicalUnit,
+            0x1 => Association::TargetPort,
+            0x2 => Association::TargetDevice,
+            _ => Association::Reserved,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Designator {
+    VendorSpecific(Vec<u8>),
+    T10VendorId {
+        vendor_identification: String,
+        vendor_specific_identifier: String,
+    },
+    /// 8, 12 or 16 bytes
+    Eui64(Vec<u8>),
+    /// 8 or 16 bytes, the first nibble is the NAA type. This is the WWN.
+    Naa(Vec<u8>),
+    ScsiName(String),
+    Other {
+        designator_type: u8,
+        designator: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Designator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Designator::T10VendorId {
+                vendor_identification,
+                vendor_specific_identifier,
+            } => write!(
+                f,
+                "{} {}",
+                vendor_identification, vendor_specific_identifier
+            ),
+            Designator::Eui64(bytes) | Designator::Naa(bytes) => {
+                f.write_str("0x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            Designator::ScsiName(name) => f.write_str(name),
+            Designator::VendorSpecific(bytes)
+            | Designator::Other {
+                designator: bytes, ..
+            } => write!(f, "{:02x?}", bytes),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesignationDescriptor {
+    pub protocol_identifier: u8,
+    pub code_set: u8,
+    /// Only meaningful if `protocol_identifier_valid` is set
+    pub protocol_identifier_valid: bool,
+    pub association: Association,
+    pub designator: Designator,
+}
+
+impl DesignationDescriptor {
+    /// Parses all descriptors in a Device Identification page, without its 4 byte page header.
+    pub fn parse_all(mut bytes: &[u8]) -> Vec<DesignationDescriptor> {
+        let mut descriptors = vec![];
+
+        while bytes.len() >= DESIGNATION_DESCRIPTOR_HEADER_LENGTH {
+            let designator_length = bytes[3] as usize;
+            let end = usize::min(
+                bytes.len(),
+                DESIGNATION_DESCRIPTOR_HEADER_LENGTH + designator_length,
+            );
+            let designator = &bytes[DESIGNATION_DESCRIPTOR_HEADER_LENGTH..end];
+            let code_set = bytes[0] & 0x0f;
+            let designator_type = bytes[1] & 0x0f;
+
+            descriptors.push(DesignationDescriptor {
+                protocol_identifier: bytes[0] >> 4,
+                code_set,
+                protocol_identifier_valid: bytes[1] & 0x80 != 0,
+                association: (bytes[1] >> 4).into(),
+                designator: parse_designator(designator_type, code_set, designator),
+            });
+
+            bytes = &bytes[end..];
+        }
+
+        descriptors
+    }
+}
+
+fn parse_designator(designator_type: u8, code_set: u8, designator: &[u8]) -> Designator {
+    match designator_type {
+        DESIGNATOR_TYPE_VENDOR_SPECIFIC => Designator::VendorSpecific(designator.to_vec()),
+        DESIGNATOR_TYPE_T10_VENDOR_ID => {
+            let split = usize::min(designator.len(), T10_VENDOR_IDENTIFICATION_LENGTH);
+            let (vendor_identification, vendor_specific_identifier) = designator.split_at(split);
+            Designator::T10VendorId {
+                vendor_identification: ascii_field(vendor_identification),
+                vendor_specific_identifier: ascii_field(vendor_specific_identifier),
+            }
+        }
+        DESIGNATOR_TYPE_EUI_64 => Designator::Eui64(designator.to_vec()),
+        DESIGNATOR_TYPE_NAA => Designator::Naa(designator.to_vec()),
+        DESIGNATOR_TYPE_SCSI_NAME_STRING if code_set != CODE_SET_BINARY => {
+            Designator::ScsiName(ascii_field(designator))
+        }
+        _ => Designator::Other {
+            designator_type,
+            designator: designator.to_vec(),
+        },
+    }
+}
+
+fn ascii_field(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_matches([' ', '\0'])
+        .to_owned()
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn inquiry_device_identification(&self) -> crate::Result<Vec<DesignationDescriptor>> {
+        let data = self.inquiry_variable_length_vpd(DEVICE_IDENTIFICATION_VPD_PAGE_CODE)?;
+
+        Ok(DesignationDescriptor::parse_all(&data))
+    }
+
+    /// The NAA designator of the logical unit, if the device reports one.
+    pub fn inquiry_world_wide_name(&self) -> crate::Result<Option<Designator>> {
+        let descriptors = self.inquiry_device_identification()?;
+
+        Ok(descriptors
+            .into_iter()
+            .find(|d| {
+                d.association == Association::LogicalUnit
+                    && matches!(d.designator, Designator::Naa(_))
+            })
+            .map(|d| d.designator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_designators() {
+        let naa: &[u8] = &[
+            0x01, 0x03, 0x00, 0x08, 0x50, 0x01, 0x4e, 0xe2, 0xb1, 0x23, 0x45, 0x67,
+        ];
+        let t10_vendor_id: &[u8] = &[0x02, 0x01, 0x00, 0x0c];
+        let t10_vendor_id = [t10_vendor_id, b"WD      1234"].concat();
+        // UTF-8, target device
+        let scsi_name: &[u8] = &[0x03, 0xa8, 0x00, 0x08];
+        let scsi_name = [scsi_name, b"naa.50\0\0"].concat();
+        let eui_64: &[u8] = &[
+            0x01, 0x02, 0x00, 0x08, 0x00, 0x14, 0xee, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        let bytes = [naa, &t10_vendor_id, &scsi_name, eui_64].concat();
+
+        let descriptors = DesignationDescriptor::parse_all(&bytes);
+        assert_eq!(descriptors.len(), 4);
+        assert_eq!(
+            descriptors[0].designator,
+            Designator::Naa(vec![0x50, 0x01, 0x4e, 0xe2, 0xb1, 0x23, 0x45, 0x67])
+        );
+        assert_eq!(descriptors[0].designator.to_string(), "0x50014ee2b1234567");
+        assert_eq!(
+            descriptors[1].designator,
+            Designator::T10VendorId {
+                vendor_identification: "WD".to_owned(),
+                vendor_specific_identifier: "1234".to_owned(),
+            }
+        );
+        assert_eq!(descriptors[2].association, Association::TargetDevice);
+        assert_eq!(
+            descriptors[2].designator,
+            Designator::ScsiName("naa.50".to_owned())
+        );
+        assert!(matches!(descriptors[3].designator, Designator::Eui64(_)));
+    }
+}
@@ -1,6 +1,6 @@
 use modular_bitfield_msb::prelude::*;
 
-use crate::Scsi;
+use crate::{Scsi, Transport};
 
 const LOGICAL_BLOCK_PROVISIONING_VPD_PAGE_CODE: u8 = 0xB2;
 
@@ -33,13 +33,21 @@ impl Default for LogicalBlockProvisioningVPDPage {
     }
 }
 
-impl Scsi {
+impl<T: Transport> Scsi<T> {
     pub fn inquiry_unmap_support(&self) -> crate::Result<bool> {
         let data: LogicalBlockProvisioningVPDPage =
             self.inquiry_general(Some(LOGICAL_BLOCK_PROVISIONING_VPD_PAGE_CODE))?;
 
         Ok(data.lbpu() != 0)
     }
+
+    /// WRITE SAME(16) with the UNMAP bit set.
+    pub fn inquiry_write_same_unmap_support(&self) -> crate::Result<bool> {
+        let data: LogicalBlockProvisioningVPDPage =
+            self.inquiry_general(Some(LOGICAL_BLOCK_PROVISIONING_VPD_PAGE_CODE))?;
+
+        Ok(data.lbpws() != 0)
+    }
 }
 
 #[cfg(test)]
@@ -4,13 +4,20 @@ use std::marker::PhantomData;
 
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
 
 use super::sense::{BytesSenseBuffer, Sense};
 
 mod block_limits_vpd;
+mod device_identification_vpd;
 mod logical_block_provisioning_vpd;
-mod product_identification;
+mod standard_inquiry;
+mod supported_vpd_pages;
+mod unit_serial_number_vpd;
+mod variable_length_vpd;
+
+pub use device_identification_vpd::{Association, DesignationDescriptor, Designator};
+pub use standard_inquiry::StandardInquiry;
 
 const OPERATION_CODE: u8 = 0x12;
 
@@ -77,8 +84,56 @@ where
     }
 }
 
-impl Scsi {
-    fn inquiry_general<T: Default + Clone>(&self, page_code: Option<u8>) -> crate::Result<T> {
+/// For responses whose length is only known at runtime
+struct ThisBytesCommand {
+    page_code: u8,
+    allocation_length: u16,
+}
+
+impl Command for ThisBytesCommand {
+    type CommandBuffer = InquiryCommand;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<Vec<u8>>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_allocation_length(self.allocation_length)
+            .with_evpd(1)
+            .with_page_code(self.page_code)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        vec![0; self.allocation_length as usize]
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()?;
+
+        Ok(result.transfered_data().to_vec())
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    fn inquiry_general<D: Default + Clone>(&self, page_code: Option<u8>) -> crate::Result<D> {
         let this_command = ThisCommand {
             enable_vpd: page_code.is_some(),
             page_code: page_code.unwrap_or_default(),
@@ -87,6 +142,13 @@ impl Scsi {
 
         self.execute_command(&this_command)
     }
+
+    fn inquiry_vpd_bytes(&self, page_code: u8, allocation_length: u16) -> crate::Result<Vec<u8>> {
+        self.execute_command(&ThisBytesCommand {
+            page_code,
+            allocation_length,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,170 @@
+use modular_bitfield_msb::prelude::*;
+
+use crate::{Scsi, Transport};
+
+const VERSION_DESCRIPTOR_COUNT: usize = 8;
+
+#[bitfield]
+#[derive(Debug, Clone)]
+pub struct StandardInquiryData {
+    peripheral_qualifier: B3,
+    peripheral_device_type: B5,
+    rmb: B1,
+    lu_cong: B1,
+    reserved_0: B6,
+    version: B8,
+    obsolete_0: B2,
+    normaca: B1,
+    hisup: B1,
+    response_data_format: B4,
+    additional_length: B8,
+    sccs: B1,
+    acc: B1,
+    tpgs: B2,
+    third_party_copy: B1,
+    reserved_1: B2,
+    protect: B1,
+    obsolete_1: B1,
+    encserv: B1,
+    vs_0: B1,
+    multip: B1,
+    obsolete_2: B4,
+    obsolete_3: B6,
+    cmdque: B1,
+    vs_1: B1,
+    vendor_identification: B64,
+    product_identification: B128,
+    product_revision_level: B32,
+    vendor_specific_0: B128,
+    vendor_specific_1: B32,
+    reserved_2: B4,
+    clocking: B2,
+    qas: B1,
+    ius: B1,
+    reserved_3: B8,
+    version_descriptor_1: B16,
+    version_descriptor_2: B16,
+    version_descriptor_3: B16,
+    version_descriptor_4: B16,
+    version_descriptor_5: B16,
+    version_descriptor_6: B16,
+    version_descriptor_7: B16,
+    version_descriptor_8: B16,
+    reserved_4: B128,
+    reserved_5: B48,
+}
+
+impl Default for StandardInquiryData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StandardInquiry {
+    pub peripheral_qualifier: u8,
+    pub peripheral_device_type: u8,
+    pub removable_medium: bool,
+    pub version: u8,
+    pub response_data_format: u8,
+    /// Trailing spaces are trimmed
+    pub vendor_identification: String,
+    /// Trailing spaces are trimmed
+    pub product_identification: String,
+    /// Trailing spaces are trimmed
+    pub product_revision_level: String,
+    /// Unused (zero) descriptors are left out
+    pub version_descriptors: Vec<u16>,
+}
+
+impl From<&StandardInquiryData> for StandardInquiry {
+    fn from(data: &StandardInquiryData) -> Self {
+        let version_descriptors: [u16; VERSION_DESCRIPTOR_COUNT] = [
+            data.version_descriptor_1(),
+            data.version_descriptor_2(),
+            data.version_descriptor_3(),
+            data.version_descriptor_4(),
+            data.version_descriptor_5(),
+            data.version_descriptor_6(),
+            data.version_descriptor_7(),
+            data.version_descriptor_8(),
+        ];
+
+        StandardInquiry {
+            peripheral_qualifier: data.peripheral_qualifier(),
+            peripheral_device_type: data.peripheral_device_type(),
+            removable_medium: data.rmb() != 0,
+            version: data.version(),
+            response_data_format: data.response_data_format(),
+            vendor_identification: ascii_field(&data.vendor_identification().to_be_bytes()),
+            product_identification: ascii_field(&data.product_identification().to_be_bytes()),
+            product_revision_level: ascii_field(&data.product_revision_level().to_be_bytes()),
+            version_descriptors: version_descriptors
+                .into_iter()
+                .filter(|d| *d != 0)
+                .collect(),
+        }
+    }
+}
+
+fn ascii_field(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches([' ', '\0'])
+        .to_owned()
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn inquiry_standard(&self) -> crate::Result<StandardInquiry> {
+        let data: StandardInquiryData = self.inquiry_general(None)?;
+
+        Ok((&data).into())
+    }
+
+    pub fn inquiry_product_identification(&self) -> crate::Result<String> {
+        let data: StandardInquiryData = self.inquiry_general(None)?;
+
+        let product_identification =
+            String::from_utf8_lossy(&data.product_identification().to_be_bytes()).to_string();
+
+        Ok(product_identification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    const STANDARD_INQUIRY_DATA_LENGTH: usize = 96;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<StandardInquiryData>(),
+            STANDARD_INQUIRY_DATA_LENGTH,
+            concat!("Size of: ", stringify!(StandardInquiryData))
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let mut bytes = [0u8; STANDARD_INQUIRY_DATA_LENGTH];
+        bytes[1] = 0x80;
+        bytes[2] = 0x06;
+        bytes[3] = 0x02;
+        bytes[8..16].copy_from_slice(b"WD      ");
+        bytes[16..32].copy_from_slice(b"My Passport 25E2");
+        bytes[32..36].copy_from_slice(b"4004");
+        bytes[58..60].copy_from_slice(&0x0460u16.to_be_bytes());
+        bytes[60..62].copy_from_slice(&0x04c0u16.to_be_bytes());
+
+        let inquiry = StandardInquiry::from(&StandardInquiryData::from_bytes(bytes));
+        assert!(inquiry.removable_medium);
+        assert_eq!(inquiry.version, 0x06);
+        assert_eq!(inquiry.response_data_format, 0x02);
+        assert_eq!(inquiry.vendor_identification, "WD");
+        assert_eq!(inquiry.product_identification, "My Passport 25E2");
+        assert_eq!(inquiry.product_revision_level, "4004");
+        assert_eq!(inquiry.version_descriptors, vec![0x0460, 0x04c0]);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::{Scsi, Transport};
+
+const SUPPORTED_VPD_PAGES_PAGE_CODE: u8 = 0x00;
+
+impl<T: Transport> Scsi<T> {
+    pub fn inquiry_supported_vpd_pages(&self) -> crate::Result<Vec<u8>> {
+        self.inquiry_variable_length_vpd(SUPPORTED_VPD_PAGES_PAGE_CODE)
+    }
+}
@@ -0,0 +1,14 @@
+use crate::{Scsi, Transport};
+
+const UNIT_SERIAL_NUMBER_VPD_PAGE_CODE: u8 = 0x80;
+
+impl<T: Transport> Scsi<T> {
+    /// Leading and trailing spaces are trimmed, the field is right aligned by some devices.
+    pub fn inquiry_unit_serial_number(&self) -> crate::Result<String> {
+        let data = self.inquiry_variable_length_vpd(UNIT_SERIAL_NUMBER_VPD_PAGE_CODE)?;
+
+        Ok(String::from_utf8_lossy(&data)
+            .trim_matches([' ', '\0'])
+            .to_owned())
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{Scsi, Transport};
+
+use super::super::read_in_full;
+
+const VPD_PAGE_HEADER_LENGTH: usize = 4;
+
+impl<T: Transport> Scsi<T> {
+    /// Returns the page without its 4 byte header.
+    pub(super) fn inquiry_variable_length_vpd(&self, page_code: u8) -> crate::Result<Vec<u8>> {
+        let data = read_in_full(
+            |allocation_length| self.inquiry_vpd_bytes(page_code, allocation_length),
+            page_length,
+        )?;
+        if data.len() < VPD_PAGE_HEADER_LENGTH {
+            return Err(crate::Error::Other(format!(
+                "VPD page {:02X}h is too short",
+                page_code
+            )));
+        }
+        if data[1] != page_code {
+            return Err(crate::Error::Other(format!(
+                "Requested VPD page {:02X}h, got {:02X}h",
+                page_code, data[1]
+            )));
+        }
+
+        let end = usize::min(data.len(), page_length(&data));
+
+        Ok(data[VPD_PAGE_HEADER_LENGTH..end].to_vec())
+    }
+}
+
+/// Header included
+fn page_length(data: &[u8]) -> usize {
+    match data {
+        [_, _, high, low, ..] => {
+            VPD_PAGE_HEADER_LENGTH + u16::from_be_bytes([*high, *low]) as usize
+        }
+        _ => VPD_PAGE_HEADER_LENGTH,
+    }
+}
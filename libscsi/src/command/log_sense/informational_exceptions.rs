@@ -0,0 +1,59 @@
+use crate::{additional_sense_description, Scsi, Transport};
+
+use super::{find_parameter, LogParameter};
+
+const INFORMATIONAL_EXCEPTIONS_PAGE_CODE: u8 = 0x2f;
+const INFORMATIONAL_EXCEPTIONS_GENERAL_PARAMETER_CODE: u16 = 0x0000;
+const FAILURE_PREDICTION_THRESHOLD_EXCEEDED: u8 = 0x5d;
+const TEMPERATURE_NOT_AVAILABLE: u8 = 0xff;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InformationalExceptions {
+    /// 0 if there is nothing to report
+    pub additional_sense_code: u8,
+    pub additional_sense_code_qualifier: u8,
+    /// In degrees Celsius
+    pub most_recent_temperature: Option<u8>,
+}
+
+impl InformationalExceptions {
+    pub fn decode(parameters: &[LogParameter]) -> Option<InformationalExceptions> {
+        let value =
+            &find_parameter(parameters, INFORMATIONAL_EXCEPTIONS_GENERAL_PARAMETER_CODE)?.value;
+        if value.len() < 2 {
+            return None;
+        }
+
+        Some(InformationalExceptions {
+            additional_sense_code: value[0],
+            additional_sense_code_qualifier: value[1],
+            most_recent_temperature: value
+                .get(2)
+                .copied()
+                .filter(|t| *t != TEMPERATURE_NOT_AVAILABLE),
+        })
+    }
+
+    /// The device predicts it's going to fail.
+    pub fn failure_predicted(&self) -> bool {
+        self.additional_sense_code == FAILURE_PREDICTION_THRESHOLD_EXCEEDED
+            && self.additional_sense_code_qualifier != 0xff
+    }
+
+    pub fn description(&self) -> &'static str {
+        additional_sense_description(
+            self.additional_sense_code,
+            self.additional_sense_code_qualifier,
+        )
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn log_sense_informational_exceptions(
+        &self,
+    ) -> crate::Result<Option<InformationalExceptions>> {
+        let parameters = self.log_sense(INFORMATIONAL_EXCEPTIONS_PAGE_CODE, 0)?;
+
+        Ok(InformationalExceptions::decode(&parameters))
+    }
+}
@@ -0,0 +1,296 @@
+#![allow(dead_code)]
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::{
+    read_in_full,
+    sense::{BytesSenseBuffer, Sense},
+};
+
+mod informational_exceptions;
+mod self_test_results;
+mod start_stop_cycle_counter;
+mod supported_log_pages;
+mod temperature;
+
+pub use informational_exceptions::InformationalExceptions;
+pub use self_test_results::{SelfTestResult, SelfTestStatus};
+pub use start_stop_cycle_counter::StartStopCycleCounter;
+pub use temperature::Temperature;
+
+const OPERATION_CODE: u8 = 0x4d;
+const LOG_PAGE_HEADER_LENGTH: usize = 4;
+const LOG_PARAMETER_HEADER_LENGTH: usize = 4;
+
+/// Cumulative values are what the device has counted so far.
+const PAGE_CONTROL_CUMULATIVE_VALUES: u8 = 0x1;
+
+#[bitfield]
+struct LogSenseCommand {
+    operation_code: B8,
+    reserved_0: B6,
+    obsolete: B1,
+    sp: B1,
+    pc: B2,
+    page_code: B6,
+    subpage_code: B8,
+    reserved_1: B8,
+    parameter_pointer: B16,
+    allocation_length: B16,
+    control: B8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogParameter {
+    pub parameter_code: u16,
+    /// DU, TSD, ETC, TMC and FORMAT AND LINKING bits
+    pub control: u8,
+    pub value: Vec<u8>,
+}
+
+impl LogParameter {
+    /// Parses all parameters of a log page, without its 4 byte page header.
+    pub fn parse_all(mut bytes: &[u8]) -> Vec<LogParameter> {
+        let mut parameters = vec![];
+
+        while bytes.len() >= LOG_PARAMETER_HEADER_LENGTH {
+            let parameter_length = bytes[3] as usize;
+            let end = usize::min(bytes.len(), LOG_PARAMETER_HEADER_LENGTH + parameter_length);
+
+            parameters.push(LogParameter {
+                parameter_code: u16::from_be_bytes([bytes[0], bytes[1]]),
+                control: bytes[2],
+                value: bytes[LOG_PARAMETER_HEADER_LENGTH..end].to_vec(),
+            });
+
+            bytes = &bytes[end..];
+        }
+
+        parameters
+    }
+
+    /// Big endian counter value, `None` if it doesn't fit in 8 bytes.
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.value.len() > 8 {
+            return None;
+        }
+
+        Some(self.value.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+    }
+}
+
+fn find_parameter(parameters: &[LogParameter], parameter_code: u16) -> Option<&LogParameter> {
+    parameters
+        .iter()
+        .find(|p| p.parameter_code == parameter_code)
+}
+
+struct ThisCommand {
+    page_code: u8,
+    subpage_code: u8,
+    allocation_length: u16,
+}
+
+impl Command for ThisCommand {
+    type CommandBuffer = LogSenseCommand;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<Vec<u8>>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_pc(PAGE_CONTROL_CUMULATIVE_VALUES)
+            .with_page_code(self.page_code)
+            .with_subpage_code(self.subpage_code)
+            .with_allocation_length(self.allocation_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        vec![0; self.allocation_length as usize]
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()?;
+
+        Ok(result.transfered_data().to_vec())
+    }
+}
+
+/// Header included
+fn page_length(page: &[u8]) -> usize {
+    match page {
+        [_, _, high, low, ..] => {
+            LOG_PAGE_HEADER_LENGTH + u16::from_be_bytes([*high, *low]) as usize
+        }
+        _ => LOG_PAGE_HEADER_LENGTH,
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Returns the page without its 4 byte header.
+    pub fn log_sense_page(&self, page_code: u8, subpage_code: u8) -> crate::Result<Vec<u8>> {
+        let page = read_in_full(
+            |allocation_length| {
+                self.execute_command(&ThisCommand {
+                    page_code,
+                    subpage_code,
+                    allocation_length,
+                })
+            },
+            page_length,
+        )?;
+
+        if page.len() < LOG_PAGE_HEADER_LENGTH {
+            return Err(crate::Error::Other(format!(
+                "Log page {:02X}h is too short",
+                page_code
+            )));
+        }
+        if page[0] & 0x3f != page_code {
+            return Err(crate::Error::Other(format!(
+                "Requested log page {:02X}h, got {:02X}h",
+                page_code,
+                page[0] & 0x3f
+            )));
+        }
+
+        let end = usize::min(page.len(), page_length(&page));
+
+        Ok(page[LOG_PAGE_HEADER_LENGTH..end].to_vec())
+    }
+
+    pub fn log_sense(&self, page_code: u8, subpage_code: u8) -> crate::Result<Vec<LogParameter>> {
+        let page = self.log_sense_page(page_code, subpage_code)?;
+
+        Ok(LogParameter::parse_all(&page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const LOG_SENSE_COMMAND_LENGTH: usize = 10;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<LogSenseCommand>(),
+            LOG_SENSE_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(LogSenseCommand))
+        );
+    }
+
+    #[test]
+    fn parse_parameters() {
+        let bytes = [
+            0x00, 0x00, 0x03, 0x02, 0x00, 0x23, 0x00, 0x01, 0x03, 0x02, 0x00, 0x3c,
+        ];
+        let parameters = LogParameter::parse_all(&bytes);
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(parameters[1].parameter_code, 0x0001);
+        assert_eq!(parameters[1].as_u64(), Some(0x3c));
+    }
+
+    #[test]
+    fn log_pages() {
+        let record = |page_code: u8, page: &[u8]| {
+            TraceRecord::good(
+                &[
+                    OPERATION_CODE,
+                    0x00,
+                    0x40 | page_code,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0x02,
+                    0x00,
+                    0,
+                ],
+                DataDirection::FromDevice,
+                page,
+            )
+        };
+        let mut self_test_results = vec![0x10, 0x00, 0x00, 0x28];
+        for parameter_code in 1..=2 {
+            self_test_results.extend_from_slice(&[0x00, parameter_code, 0x03, 0x10]);
+            self_test_results.extend_from_slice(&[0; 0x10]);
+        }
+        let transport = ReplayTransport::from_records(vec![
+            record(0x00, &[0x00, 0x00, 0x00, 0x03, 0x00, 0x0d, 0x2f]),
+            record(
+                0x0d,
+                &[
+                    0x0d, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x03, 0x02, 0x00, 0x23, 0x00, 0x01, 0x03,
+                    0x02, 0x00, 0x3c,
+                ],
+            ),
+            record(
+                0x0e,
+                &[
+                    0x0e, 0x00, 0x00, 0x1a, 0x00, 0x01, 0x03, 0x06, 0x32, 0x30, 0x32, 0x31, 0x31,
+                    0x34, 0x00, 0x04, 0x03, 0x04, 0x00, 0x00, 0x00, 0x07, 0x00, 0x06, 0x03, 0x04,
+                    0x00, 0x00, 0x00, 0x09,
+                ],
+            ),
+            record(
+                0x2f,
+                &[
+                    0x2f, 0x00, 0x00, 0x07, 0x00, 0x00, 0x03, 0x03, 0x5d, 0x00, 0x23,
+                ],
+            ),
+            record(0x10, &self_test_results),
+            record(0x0d, &[0x0e, 0x00, 0x00, 0x00]),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        assert_eq!(
+            device.log_sense_supported_pages().unwrap(),
+            [0x00, 0x0d, 0x2f]
+        );
+        let temperature = device.log_sense_temperature().unwrap();
+        assert_eq!(temperature.current, Some(0x23));
+        assert_eq!(temperature.reference, Some(0x3c));
+        let counter = device.log_sense_start_stop_cycle_counter().unwrap();
+        assert_eq!(
+            counter.date_of_manufacture,
+            Some(("2021".to_owned(), "14".to_owned()))
+        );
+        assert_eq!(counter.accumulated_start_stop_cycles, Some(7));
+        assert_eq!(counter.accumulated_load_unload_cycles, Some(9));
+        assert_eq!(counter.specified_start_stop_cycles, None);
+        let exceptions = device
+            .log_sense_informational_exceptions()
+            .unwrap()
+            .unwrap();
+        assert!(exceptions.failure_predicted());
+        assert_eq!(exceptions.most_recent_temperature, Some(0x23));
+        // unused entries are left out
+        assert!(device.log_sense_self_test_results().unwrap().is_empty());
+        assert!(device.log_sense_temperature().is_err());
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{Scsi, SenseKey, Transport};
+
+use super::LogParameter;
+
+const SELF_TEST_RESULTS_PAGE_CODE: u8 = 0x10;
+const SELF_TEST_RESULT_PARAMETER_LENGTH: usize = 0x10;
+const NO_FAILURE_ADDRESS: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestStatus {
+    Completed,
+    AbortedBySendDiagnostic,
+    AbortedByOther,
+    UnknownError,
+    FailedUnknownSegment,
+    FailedFirstSegment,
+    FailedSecondSegment,
+    FailedOtherSegment,
+    InProgress,
+    Reserved(u8),
+}
+
+impl From<u8> for SelfTestStatus {
+    fn from(value: u8) -> Self {
+        match value & 0x0f {
+            0x0 => SelfTestStatus::Completed,
+            0x1 => SelfTestStatus::AbortedBySendDiagnostic,
+            0x2 => SelfTestStatus::AbortedByOther,
+            0x3 => SelfTestStatus::UnknownError,
+            0x4 => SelfTestStatus::FailedUnknownSegment,
+            0x5 => SelfTestStatus::FailedFirstSegment,
+            0x6 => SelfTestStatus::FailedSecondSegment,
+            0x7 => SelfTestStatus::FailedOtherSegment,
+            0xf => SelfTestStatus::InProgress,
+            n => SelfTestStatus::Reserved(n),
+        }
+    }
+}
+
+impl SelfTestStatus {
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            SelfTestStatus::UnknownError
+                | SelfTestStatus::FailedUnknownSegment
+                | SelfTestStatus::FailedFirstSegment
+                | SelfTestStatus::FailedSecondSegment
+                | SelfTestStatus::FailedOtherSegment
+        )
+    }
+}
+
+/// One entry of the self-test log, the most recent test comes first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestResult {
+    pub self_test_code: u8,
+    pub status: SelfTestStatus,
+    pub self_test_number: u8,
+    pub accumulated_power_on_hours: u16,
+    /// Logical block address of the first failure
+    pub address_of_first_failure: Option<u64>,
+    pub sense_key: SenseKey,
+    pub additional_sense_code: u8,
+    pub additional_sense_code_qualifier: u8,
+}
+
+impl SelfTestResult {
+    /// Unused log entries are left out.
+    pub fn decode(parameters: &[LogParameter]) -> Vec<SelfTestResult> {
+        let mut parameters: Vec<_> = parameters
+            .iter()
+            .filter(|p| p.value.len() >= SELF_TEST_RESULT_PARAMETER_LENGTH - 1)
+            .filter(|p| p.value[..4].iter().any(|b| *b != 0))
+            .collect();
+        parameters.sort_by_key(|p| p.parameter_code);
+
+        parameters
+            .into_iter()
+            .map(|p| {
+                let value = &p.value;
+                let address = u64::from_be_bytes(value[4..12].try_into().unwrap());
+
+                SelfTestResult {
+                    self_test_code: value[0] >> 5,
+                    status: value[0].into(),
+                    self_test_number: value[1],
+                    accumulated_power_on_hours: u16::from_be_bytes([value[2], value[3]]),
+                    address_of_first_failure: match address {
+                        NO_FAILURE_ADDRESS => None,
+                        address => Some(address),
+                    },
+                    sense_key: value[12].into(),
+                    additional_sense_code: value[13],
+                    additional_sense_code_qualifier: value[14],
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn log_sense_self_test_results(&self) -> crate::Result<Vec<SelfTestResult>> {
+        let parameters = self.log_sense(SELF_TEST_RESULTS_PAGE_CODE, 0)?;
+
+        Ok(SelfTestResult::decode(&parameters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let mut bytes = vec![];
+        for (parameter_code, code_and_result, number) in [(1u16, 0x27, 2), (2, 0x40, 1), (3, 0, 0)]
+        {
+            bytes.extend_from_slice(&parameter_code.to_be_bytes());
+            bytes.extend_from_slice(&[0x03, 0x10, code_and_result, number, number, 0x00]);
+            let address = match code_and_result & 0x0f {
+                0 => u64::MAX,
+                _ => 0x1234,
+            };
+            bytes.extend_from_slice(&address.to_be_bytes());
+            bytes.extend_from_slice(&[0x03, 0x11, 0x00, 0x00]);
+        }
+
+        let results = SelfTestResult::decode(&LogParameter::parse_all(&bytes));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].self_test_code, 0x1);
+        assert_eq!(results[0].status, SelfTestStatus::FailedOtherSegment);
+        assert_eq!(results[0].address_of_first_failure, Some(0x1234));
+        assert_eq!(results[0].sense_key, SenseKey::MediumError);
+        assert_eq!(results[1].status, SelfTestStatus::Completed);
+        assert_eq!(results[1].address_of_first_failure, None);
+        assert_eq!(results[1].accumulated_power_on_hours, 0x0100);
+        assert_eq!(results[0].accumulated_power_on_hours, 0x0200);
+    }
+}
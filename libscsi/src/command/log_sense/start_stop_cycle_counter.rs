@@ -0,0 +1,57 @@
+use crate::{Scsi, Transport};
+
+use super::{find_parameter, LogParameter};
+
+const START_STOP_CYCLE_COUNTER_PAGE_CODE: u8 = 0x0e;
+const DATE_OF_MANUFACTURE_PARAMETER_CODE: u16 = 0x0001;
+const SPECIFIED_START_STOP_CYCLES_PARAMETER_CODE: u16 = 0x0003;
+const ACCUMULATED_START_STOP_CYCLES_PARAMETER_CODE: u16 = 0x0004;
+const SPECIFIED_LOAD_UNLOAD_CYCLES_PARAMETER_CODE: u16 = 0x0005;
+const ACCUMULATED_LOAD_UNLOAD_CYCLES_PARAMETER_CODE: u16 = 0x0006;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartStopCycleCounter {
+    /// Year and week, as reported in ASCII
+    pub date_of_manufacture: Option<(String, String)>,
+    /// Over the device lifetime
+    pub specified_start_stop_cycles: Option<u32>,
+    pub accumulated_start_stop_cycles: Option<u32>,
+    /// Over the device lifetime
+    pub specified_load_unload_cycles: Option<u32>,
+    pub accumulated_load_unload_cycles: Option<u32>,
+}
+
+impl StartStopCycleCounter {
+    pub fn decode(parameters: &[LogParameter]) -> StartStopCycleCounter {
+        let counter = |parameter_code| {
+            find_parameter(parameters, parameter_code)
+                .and_then(|p| p.as_u64())
+                .map(|c| c as u32)
+        };
+
+        let date_of_manufacture = find_parameter(parameters, DATE_OF_MANUFACTURE_PARAMETER_CODE)
+            .filter(|p| p.value.len() >= 6)
+            .map(|p| {
+                (
+                    String::from_utf8_lossy(&p.value[..4]).trim().to_owned(),
+                    String::from_utf8_lossy(&p.value[4..6]).trim().to_owned(),
+                )
+            });
+
+        StartStopCycleCounter {
+            date_of_manufacture,
+            specified_start_stop_cycles: counter(SPECIFIED_START_STOP_CYCLES_PARAMETER_CODE),
+            accumulated_start_stop_cycles: counter(ACCUMULATED_START_STOP_CYCLES_PARAMETER_CODE),
+            specified_load_unload_cycles: counter(SPECIFIED_LOAD_UNLOAD_CYCLES_PARAMETER_CODE),
+            accumulated_load_unload_cycles: counter(ACCUMULATED_LOAD_UNLOAD_CYCLES_PARAMETER_CODE),
+        }
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn log_sense_start_stop_cycle_counter(&self) -> crate::Result<StartStopCycleCounter> {
+        let parameters = self.log_sense(START_STOP_CYCLE_COUNTER_PAGE_CODE, 0)?;
+
+        Ok(StartStopCycleCounter::decode(&parameters))
+    }
+}
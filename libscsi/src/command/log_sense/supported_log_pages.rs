@@ -0,0 +1,11 @@
+use crate::{Scsi, Transport};
+
+const SUPPORTED_LOG_PAGES_PAGE_CODE: u8 = 0x00;
+
+impl<T: Transport> Scsi<T> {
+    pub fn log_sense_supported_pages(&self) -> crate::Result<Vec<u8>> {
+        let page = self.log_sense_page(SUPPORTED_LOG_PAGES_PAGE_CODE, 0)?;
+
+        Ok(page.into_iter().map(|p| p & 0x3f).collect())
+    }
+}
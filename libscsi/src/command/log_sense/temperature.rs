@@ -0,0 +1,54 @@
+use crate::{Scsi, Transport};
+
+use super::{find_parameter, LogParameter};
+
+const TEMPERATURE_PAGE_CODE: u8 = 0x0d;
+const TEMPERATURE_PARAMETER_CODE: u16 = 0x0000;
+const REFERENCE_TEMPERATURE_PARAMETER_CODE: u16 = 0x0001;
+const TEMPERATURE_NOT_AVAILABLE: u8 = 0xff;
+
+/// Both in degrees Celsius.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Temperature {
+    pub current: Option<u8>,
+    /// Maximum temperature the device is specified to operate at
+    pub reference: Option<u8>,
+}
+
+impl Temperature {
+    pub fn decode(parameters: &[LogParameter]) -> Temperature {
+        let celsius = |parameter_code| {
+            find_parameter(parameters, parameter_code)
+                .and_then(|p| p.value.get(1).copied())
+                .filter(|t| *t != TEMPERATURE_NOT_AVAILABLE)
+        };
+
+        Temperature {
+            current: celsius(TEMPERATURE_PARAMETER_CODE),
+            reference: celsius(REFERENCE_TEMPERATURE_PARAMETER_CODE),
+        }
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    pub fn log_sense_temperature(&self) -> crate::Result<Temperature> {
+        let parameters = self.log_sense(TEMPERATURE_PAGE_CODE, 0)?;
+
+        Ok(Temperature::decode(&parameters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let bytes = [
+            0x00, 0x00, 0x03, 0x02, 0x00, 0x23, 0x00, 0x01, 0x03, 0x02, 0x00, 0xff,
+        ];
+        let temperature = Temperature::decode(&LogParameter::parse_all(&bytes));
+        assert_eq!(temperature.current, Some(35));
+        assert_eq!(temperature.reference, None);
+    }
+}
@@ -1,20 +1,44 @@
+mod ata_pass_through;
+mod erase;
 mod inquiry;
+mod log_sense;
 mod mode_select;
 mod mode_sense;
+mod read;
 mod read_capacity;
+mod request_sense;
 mod send_diagnostic;
 pub mod sense;
+mod start_stop_unit;
+mod test_unit_ready;
 mod unmap;
+mod verify;
+mod write;
+mod write_same;
 
-use std::{borrow::BorrowMut, mem::size_of};
+use std::{borrow::BorrowMut, mem::size_of, time::Duration};
 
 use crate::{result_data::ResultData, DataDirection};
 
-pub use send_diagnostic::TestResult;
+pub use ata_pass_through::{
+    AtaCommand, AtaError, AtaProtocol, AtaRegisters, AtaResponse, AtaStatus, IdentifyDevice,
+    RotationRate, SmartAttribute, SmartHealth, SmartReport,
+};
+pub use erase::{EraseMethod, ErasePlan};
+pub use inquiry::{Association, DesignationDescriptor, Designator, StandardInquiry};
+pub use log_sense::{
+    InformationalExceptions, LogParameter, SelfTestResult, SelfTestStatus, StartStopCycleCounter,
+    Temperature,
+};
+pub use mode_select::{check_changeable, ModePageField};
+pub use mode_sense::{BlockDescriptor, ModeParameterHeader, ModeSenseData, PageControl};
+pub use send_diagnostic::{SelfTestCode, SelfTestProgress, TestResult};
+pub use start_stop_unit::PowerCondition;
 
 pub trait Command {
     type CommandBuffer;
-    type DataBuffer;
+    /// Either a fixed size type, or `[u8]` for buffers sized at runtime
+    type DataBuffer: ?Sized + DataBufferSize;
     /// usually set it to the same as DataBuffer, but it can also be something like Box<DataBuffer>
     type DataBufferWrapper: BorrowMut<Self::DataBuffer>;
     type SenseBuffer;
@@ -25,9 +49,22 @@ pub trait Command {
     fn get_data(&self) -> Self::DataBufferWrapper;
     fn get_sense_buffer(&self) -> Self::SenseBuffer;
 
-    /// useful if have some custom data wrapper or want to trim data
+    /// useful if have some custom data wrapper or want to trim data,
+    /// runtime sized data buffers are transferred in full by default
     fn get_data_size(&self) -> u32 {
-        size_of::<Self::DataBuffer>() as u32
+        Self::DataBuffer::SIZE.map_or(u32::MAX, |size| size as u32)
+    }
+
+    /// How long the device may take, `None` for the timeout set on `Scsi`.
+    /// `Scsi::set_timeout_override` takes precedence.
+    fn get_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether sending it again does no harm if the host timed out or gave up on it, the device
+    /// may have carried it out anyway. Reads are, commands that change anything aren't.
+    fn is_retry_safe(&self) -> bool {
+        self.get_direction() == DataDirection::FromDevice
     }
 
     fn process_result(
@@ -35,3 +72,125 @@ pub trait Command {
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
     ) -> Self::ReturnType;
 }
+
+/// Size of a data buffer, `None` if it's only known at runtime.
+pub trait DataBufferSize {
+    const SIZE: Option<usize>;
+}
+
+impl<T> DataBufferSize for T {
+    const SIZE: Option<usize> = Some(size_of::<T>());
+}
+
+impl DataBufferSize for [u8] {
+    const SIZE: Option<usize> = None;
+}
+
+/// Enough for most variable length pages in one go.
+const DEFAULT_ALLOCATION_LENGTH: u16 = 512;
+
+/// Reads a variable length response with `read`, which gets the allocation length,
+/// and once more if `full_length` says the response got truncated.
+fn read_in_full<R, F>(read: R, full_length: F) -> crate::Result<Vec<u8>>
+where
+    R: Fn(u16) -> crate::Result<Vec<u8>>,
+    F: Fn(&[u8]) -> usize,
+{
+    let data = read(DEFAULT_ALLOCATION_LENGTH)?;
+    let length = full_length(&data);
+    if length <= data.len() || data.len() < DEFAULT_ALLOCATION_LENGTH as usize {
+        return Ok(data);
+    }
+
+    read(u16::try_from(length).unwrap_or(u16::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{
+        DriverStatus, HostStatus, MaskedStatus, ReplayTransport, Scsi, TraceRecord, Transport,
+        TransportRequest, TransportResult,
+    };
+
+    /// Answers from a replay, which doesn't keep timeouts, and remembers the last one
+    struct Timeouts {
+        replay: ReplayTransport,
+        last: Cell<Duration>,
+    }
+
+    impl Transport for Timeouts {
+        fn transport(&self, request: TransportRequest) -> TransportResult {
+            self.last.set(request.timeout);
+            self.replay.transport(request)
+        }
+    }
+
+    #[test]
+    fn read_twice_if_truncated() {
+        let page: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let full_length = |data: &[u8]| u16::from_be_bytes([data[0], data[1]]) as usize + 2;
+        let read = |page: &[u8], allocation_length: u16| {
+            Ok(page[..usize::min(page.len(), allocation_length as usize)].to_vec())
+        };
+
+        let mut long_page = page.clone();
+        long_page[..2].copy_from_slice(&998u16.to_be_bytes());
+        assert_eq!(
+            read_in_full(|length| read(&long_page, length), full_length).unwrap(),
+            long_page
+        );
+
+        let mut short_page = page[..100].to_vec();
+        short_page[..2].copy_from_slice(&98u16.to_be_bytes());
+        assert_eq!(
+            read_in_full(|length| read(&short_page, length), full_length).unwrap(),
+            short_page
+        );
+    }
+
+    #[test]
+    fn command_timeouts() {
+        let record = |command: &[u8], direction| TraceRecord {
+            command: command.to_vec(),
+            direction,
+            data_out: vec![],
+            data_in: vec![],
+            sense: vec![],
+            masked_status: MaskedStatus::empty(),
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::empty(),
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        };
+        let unmap = [0x42, 0, 0, 0, 0, 0, 0, 0, 0x18, 0];
+        let mut device = Scsi::with_transport(
+            "replay",
+            Timeouts {
+                replay: ReplayTransport::from_records(vec![
+                    record(&[0; 6], DataDirection::None),
+                    record(&unmap, DataDirection::ToDevice),
+                    record(&[0x1d, 0xa0, 0, 0, 0, 0], DataDirection::ToDevice),
+                    record(&unmap, DataDirection::ToDevice),
+                ]),
+                last: Cell::new(Duration::ZERO),
+            },
+        );
+
+        device.test_unit_ready().unwrap();
+        assert_eq!(device.transport().last.get(), device.timeout());
+        device.unmap(0, 8, 0, 0, |_| {}).unwrap();
+        assert_eq!(device.transport().last.get(), Duration::from_secs(600));
+        device
+            .start_self_test(SelfTestCode::ForegroundShort)
+            .unwrap();
+        assert_eq!(device.transport().last.get(), Duration::from_secs(300));
+
+        device.set_timeout_override(Some(Duration::from_secs(1)));
+        device.unmap(0, 8, 0, 0, |_| {}).unwrap();
+        assert_eq!(device.transport().last.get(), Duration::from_secs(1));
+        assert_eq!(device.transport().replay.remaining(), 0);
+    }
+}
@@ -1,12 +1,22 @@
 #![allow(dead_code)]
 
+use std::ops::Range;
+
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
 
-use super::sense::{BytesSenseBuffer, Sense};
+use super::{
+    sense::{BytesSenseBuffer, Sense},
+    PageControl,
+};
 
 const OPERATION_CODE: u8 = 0x55;
+const MODE_PARAMETER_HEADER_LENGTH: usize = 8;
+const PAGE_0_HEADER_LENGTH: usize = 2;
+const SUB_PAGE_HEADER_LENGTH: usize = 4;
+const SUB_PAGE_FORMAT: u8 = 0x40;
+const PARAMETER_SAVABLE: u8 = 0x80;
 
 #[bitfield]
 struct ModeSelectCommand {
@@ -22,6 +32,7 @@ struct ModeSelectCommand {
 
 struct ThisCommand<T> {
     data: T,
+    save_pages: bool,
 }
 
 impl<T> Command for ThisCommand<T>
@@ -46,7 +57,7 @@ where
         Self::CommandBuffer::new()
             .with_operation_code(OPERATION_CODE)
             .with_page_format(1)
-            .with_saved_pages(1)
+            .with_saved_pages(self.save_pages as u8)
             .with_parameter_list_length(self.get_data_size() as u16)
     }
 
@@ -69,15 +80,153 @@ where
     }
 }
 
-impl Scsi {
-    pub fn mode_select<T: Copy>(&self, data: T) -> crate::Result<()> {
-        self.execute_command(&ThisCommand { data })
+/// For pages whose length is only known at runtime
+struct ThisBytesCommand {
+    data: Vec<u8>,
+    save_pages: bool,
+}
+
+impl Command for ThisBytesCommand {
+    type CommandBuffer = ModeSelectCommand;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::ToDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_page_format(1)
+            .with_saved_pages(self.save_pages as u8)
+            .with_parameter_list_length(self.data.len() as u16)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.data.clone()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()?;
+
+        Ok(())
+    }
+}
+
+/// A named part of a mode page, used to tell which field can't be changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModePageField {
+    pub name: &'static str,
+    /// Byte offsets in the page, page header included
+    pub bytes: Range<usize>,
+    /// Bits of each of those bytes that belong to the field
+    pub bits: u8,
+}
+
+/// Checks that `modified` only differs from `current` in bits set in the `changeable` mask,
+/// otherwise names the first offending field in `fields`.
+pub fn check_changeable(
+    current: &[u8],
+    modified: &[u8],
+    changeable: &[u8],
+    fields: &[ModePageField],
+) -> crate::Result<()> {
+    let header_length = match current.first() {
+        Some(page_code) if page_code & SUB_PAGE_FORMAT != 0 => SUB_PAGE_HEADER_LENGTH,
+        _ => PAGE_0_HEADER_LENGTH,
+    };
+
+    for index in header_length..usize::max(current.len(), modified.len()) {
+        let byte = |page: &[u8]| page.get(index).copied().unwrap_or_default();
+        let unchangeable = (byte(current) ^ byte(modified)) & !byte(changeable);
+        if unchangeable == 0 {
+            continue;
+        }
+
+        let name = fields
+            .iter()
+            .find(|field| field.bytes.contains(&index) && field.bits & unchangeable != 0)
+            .map(|field| field.name.to_owned())
+            .unwrap_or_else(|| format!("Byte {} bits {:08b}", index, unchangeable));
+
+        return Err(crate::Error::NotChangeable(name));
+    }
+
+    Ok(())
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Sends `page` with its page header, after an empty mode parameter header and no block descriptors.
+    /// With `save_pages` the device also keeps it across power cycles, if the page is savable.
+    pub fn mode_select_page(&self, page: &[u8], save_pages: bool) -> crate::Result<()> {
+        let mut data = vec![0; MODE_PARAMETER_HEADER_LENGTH];
+        data.extend_from_slice(page);
+        if u16::try_from(data.len()).is_err() {
+            return Err(crate::Error::Other(format!(
+                "Mode page too long: {} bytes",
+                page.len()
+            )));
+        }
+
+        self.execute_command(&ThisBytesCommand { data, save_pages })
+    }
+
+    /// Like `mode_select_page`, but refuses to send changes the device's changeable mask doesn't allow,
+    /// and fails with [`crate::Error::NotSavable`] if `save_pages` is asked for a page the device
+    /// can't save.
+    /// `current` is the page `modified` was made from, as read from the device.
+    pub fn mode_select_page_checked(
+        &self,
+        current: &[u8],
+        modified: &[u8],
+        fields: &[ModePageField],
+        save_pages: bool,
+    ) -> crate::Result<()> {
+        let (page_code, subpage_code) = match modified {
+            [page_code, subpage_code, ..] if page_code & SUB_PAGE_FORMAT != 0 => {
+                (page_code & 0x3f, *subpage_code)
+            }
+            [page_code, ..] => (page_code & 0x3f, 0),
+            [] => return Err(crate::Error::Other("Empty mode page".to_owned())),
+        };
+        let savable = matches!(current.first(), Some(byte) if byte & PARAMETER_SAVABLE != 0);
+        if save_pages && !savable {
+            return Err(crate::Error::NotSavable(format!(
+                "Mode page {:#04x}",
+                page_code
+            )));
+        }
+
+        let changeable = self.mode_sense_page(page_code, subpage_code, PageControl::Changeable)?;
+        check_changeable(current, modified, &changeable.pages, fields)?;
+
+        self.mode_select_page(modified, save_pages)
+    }
+
+    pub fn mode_select<D: Copy>(&self, data: D, save_pages: bool) -> crate::Result<()> {
+        self.execute_command(&ThisCommand { data, save_pages })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{DataDirection, ReplayTransport, TraceRecord};
     use std::mem::size_of;
 
     const MODE_SENSE_COMMAND_LENGTH: usize = 10;
@@ -90,4 +239,87 @@ mod tests {
             concat!("Size of: ", stringify!(ModeSenseCommand))
         );
     }
+
+    #[test]
+    fn changeable() {
+        let fields = [
+            ModePageField {
+                name: "LED brightness",
+                bytes: 8..9,
+                bits: 0xff,
+            },
+            ModePageField {
+                name: "Inverted LCD",
+                bytes: 10..11,
+                bits: 0x01,
+            },
+        ];
+        let current = [0xa1, 0x0a, 0x30, 0, 0, 0, 0, 0, 0xff, 0, 0, 0];
+        let mut mask = [0u8; 12];
+        mask[8] = 0xff;
+
+        let mut modified = current;
+        modified[0] = 0x21;
+        modified[8] = 0x00;
+        check_changeable(&current, &modified, &mask, &fields).unwrap();
+
+        modified[10] = 0x01;
+        let error = check_changeable(&current, &modified, &mask, &fields).unwrap_err();
+        assert!(matches!(error, crate::Error::NotChangeable(name) if name == "Inverted LCD"));
+
+        modified[10] = 0x00;
+        modified[11] = 0x80;
+        let error = check_changeable(&current, &modified, &mask, &fields).unwrap_err();
+        assert!(
+            matches!(error, crate::Error::NotChangeable(name) if name == "Byte 11 bits 10000000")
+        );
+    }
+
+    #[test]
+    fn not_savable() {
+        let device = Scsi::with_transport("replay", ReplayTransport::from_records(vec![]));
+        let current = [0x21, 0x0a, 0x30, 0, 0, 0, 0, 0, 0xff, 0, 0, 0];
+        let mut modified = current;
+        modified[8] = 0x00;
+
+        let error = device
+            .mode_select_page_checked(&current, &modified, &[], true)
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::NotSavable(name) if name == "Mode page 0x21"));
+    }
+
+    #[test]
+    fn checked() {
+        let mut changeable = vec![0x00, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        changeable.extend_from_slice(&[0x21, 0x0a, 0, 0, 0, 0, 0, 0, 0xff, 0, 0, 0]);
+        let mode_sense = TraceRecord::good(
+            &[0x5a, 0x10, 0x61, 0x00, 0, 0, 0, 0x02, 0x00, 0],
+            DataDirection::FromDevice,
+            &changeable,
+        );
+        let transport = ReplayTransport::from_records(vec![
+            mode_sense.clone(),
+            TraceRecord::good(
+                &[OPERATION_CODE, 0x11, 0, 0, 0, 0, 0, 0x00, 0x14, 0],
+                DataDirection::ToDevice,
+                &[],
+            ),
+            mode_sense,
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+        let current = [0xa1, 0x0a, 0x30, 0, 0, 0, 0, 0, 0xff, 0, 0, 0];
+        let mut modified = current;
+        modified[0] = 0x21;
+        modified[8] = 0x00;
+
+        device
+            .mode_select_page_checked(&current, &modified, &[], true)
+            .unwrap();
+        modified[10] = 0x01;
+        let error = device
+            .mode_select_page_checked(&current, &modified, &[], true)
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::NotChangeable(_)));
+        assert_eq!(device.transport().remaining(), 0);
+    }
 }
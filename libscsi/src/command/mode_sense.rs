@@ -4,11 +4,18 @@ use std::marker::PhantomData;
 
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
 
-use super::sense::{BytesSenseBuffer, Sense};
+use super::{
+    read_in_full,
+    sense::{BytesSenseBuffer, Sense},
+};
 
 const OPERATION_CODE: u8 = 0x5a;
+const MODE_DATA_LENGTH_LENGTH: usize = 2;
+const MODE_PARAMETER_HEADER_LENGTH: usize = 8;
+const SHORT_BLOCK_DESCRIPTOR_LENGTH: usize = 8;
+const LONG_BLOCK_DESCRIPTOR_LENGTH: usize = 16;
 
 #[bitfield]
 struct ModeSenseCommand {
@@ -75,8 +82,210 @@ where
     }
 }
 
-impl Scsi {
-    pub fn mode_sense<T: Default + Clone>(&self, page_code: u8) -> crate::Result<T> {
+/// Which values MODE SENSE returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageControl {
+    #[default]
+    Current,
+    /// A mask, set bits can be changed with MODE SELECT
+    Changeable,
+    Default,
+    /// Values restored after a power cycle
+    Saved,
+}
+
+impl From<PageControl> for u8 {
+    fn from(value: PageControl) -> Self {
+        match value {
+            PageControl::Current => 0b00,
+            PageControl::Changeable => 0b01,
+            PageControl::Default => 0b10,
+            PageControl::Saved => 0b11,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeParameterHeader {
+    pub medium_type: u8,
+    /// WP and DPOFUA bits for block devices
+    pub device_specific_parameter: u8,
+    /// Block descriptors are 16 bytes long instead of 8
+    pub long_lba: bool,
+}
+
+impl ModeParameterHeader {
+    pub fn is_write_protected(&self) -> bool {
+        self.device_specific_parameter & 0x80 != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockDescriptor {
+    /// 0 means all remaining blocks, short descriptors saturate at `u32::MAX`
+    pub number_of_logical_blocks: u64,
+    pub logical_block_length: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeSenseData {
+    pub header: ModeParameterHeader,
+    pub block_descriptors: Vec<BlockDescriptor>,
+    /// Mode pages with their page headers, more than one if all pages or subpages were requested
+    pub pages: Vec<u8>,
+}
+
+impl ModeSenseData {
+    /// Parses MODE SENSE(10) parameter data, the 8 byte header included.
+    pub fn parse(data: &[u8]) -> crate::Result<ModeSenseData> {
+        if data.len() < MODE_PARAMETER_HEADER_LENGTH {
+            return Err(crate::Error::Other(format!(
+                "Mode parameter data too short: {} bytes",
+                data.len()
+            )));
+        }
+
+        let data = &data[..usize::min(data.len(), mode_data_length(data))];
+        let header = ModeParameterHeader {
+            medium_type: data[2],
+            device_specific_parameter: data[3],
+            long_lba: data[4] & 0x01 != 0,
+        };
+        let block_descriptor_length = u16::from_be_bytes([data[6], data[7]]) as usize;
+        let block_descriptors = data
+            .get(
+                MODE_PARAMETER_HEADER_LENGTH
+                    ..MODE_PARAMETER_HEADER_LENGTH + block_descriptor_length,
+            )
+            .ok_or_else(|| {
+                crate::Error::Other(format!(
+                    "Block descriptor length {} exceeds mode parameter data",
+                    block_descriptor_length
+                ))
+            })?;
+
+        let block_descriptors = match header.long_lba {
+            true => block_descriptors
+                .chunks_exact(LONG_BLOCK_DESCRIPTOR_LENGTH)
+                .map(|d| BlockDescriptor {
+                    number_of_logical_blocks: u64::from_be_bytes(d[..8].try_into().unwrap()),
+                    logical_block_length: u32::from_be_bytes(d[12..16].try_into().unwrap()),
+                })
+                .collect(),
+            false => block_descriptors
+                .chunks_exact(SHORT_BLOCK_DESCRIPTOR_LENGTH)
+                .map(|d| BlockDescriptor {
+                    number_of_logical_blocks: u32::from_be_bytes(d[..4].try_into().unwrap()) as u64,
+                    logical_block_length: u32::from_be_bytes([0, d[5], d[6], d[7]]),
+                })
+                .collect(),
+        };
+
+        Ok(ModeSenseData {
+            header,
+            block_descriptors,
+            pages: data[MODE_PARAMETER_HEADER_LENGTH + block_descriptor_length..].to_vec(),
+        })
+    }
+}
+
+/// For responses whose length is only known at runtime
+struct ThisBytesCommand {
+    page_code: u8,
+    subpage_code: u8,
+    page_control: PageControl,
+    allocation_length: u16,
+}
+
+impl Command for ThisBytesCommand {
+    type CommandBuffer = ModeSenseCommand;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<Vec<u8>>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_llbaa(1)
+            .with_pc(self.page_control.into())
+            .with_page_code(self.page_code)
+            .with_subpage_code(self.subpage_code)
+            .with_allocation_length(self.allocation_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        vec![0; self.allocation_length as usize]
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()?;
+
+        Ok(result.transfered_data().to_vec())
+    }
+}
+
+/// Mode parameter header included
+fn mode_data_length(data: &[u8]) -> usize {
+    match data {
+        [high, low, ..] => MODE_DATA_LENGTH_LENGTH + u16::from_be_bytes([*high, *low]) as usize,
+        _ => MODE_DATA_LENGTH_LENGTH,
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Returns the whole mode parameter data, with its 8 byte header and block descriptors, read in full.
+    /// Subpage 0 is the page itself, 0xff are all subpages.
+    pub fn mode_sense_bytes(
+        &self,
+        page_code: u8,
+        subpage_code: u8,
+        page_control: PageControl,
+    ) -> crate::Result<Vec<u8>> {
+        let data = read_in_full(
+            |allocation_length| {
+                self.execute_command(&ThisBytesCommand {
+                    page_code,
+                    subpage_code,
+                    page_control,
+                    allocation_length,
+                })
+            },
+            mode_data_length,
+        )?;
+        let end = usize::min(data.len(), mode_data_length(&data));
+
+        Ok(data[..end].to_vec())
+    }
+
+    pub fn mode_sense_page(
+        &self,
+        page_code: u8,
+        subpage_code: u8,
+        page_control: PageControl,
+    ) -> crate::Result<ModeSenseData> {
+        let data = self.mode_sense_bytes(page_code, subpage_code, page_control)?;
+
+        ModeSenseData::parse(&data)
+    }
+
+    pub fn mode_sense<D: Default + Clone>(&self, page_code: u8) -> crate::Result<D> {
         self.execute_command(&ThisCommand {
             page_code,
             phantom_data: PhantomData,
@@ -87,6 +296,7 @@ impl Scsi {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ReplayTransport, TraceRecord};
     use std::mem::size_of;
 
     const MODE_SENSE_COMMAND_LENGTH: usize = 10;
@@ -99,4 +309,89 @@ mod tests {
             concat!("Size of: ", stringify!(ModeSenseCommand))
         );
     }
+
+    #[test]
+    fn parse() {
+        let data = [
+            0x00, 0x1e, 0x00, 0x90, 0x01, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0xe8, 0xe0,
+            0x88, 0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0xa1, 0x06, 0x30, 0x00,
+            0x10, 0x00, 0x00, 0x00,
+        ];
+        let mode_sense = ModeSenseData::parse(&data).unwrap();
+        assert!(mode_sense.header.long_lba);
+        assert!(mode_sense.header.is_write_protected());
+        assert_eq!(
+            mode_sense.block_descriptors,
+            [BlockDescriptor {
+                number_of_logical_blocks: 3_907_029_168,
+                logical_block_length: 512,
+            }]
+        );
+        assert_eq!(
+            mode_sense.pages,
+            [0xa1, 0x06, 0x30, 0x00, 0x10, 0x00, 0x00, 0x00]
+        );
+
+        let short = [
+            0x00, 0x0e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+            0x10, 0x00,
+        ];
+        let mode_sense = ModeSenseData::parse(&short).unwrap();
+        assert_eq!(mode_sense.block_descriptors[0].logical_block_length, 4096);
+        assert!(mode_sense.pages.is_empty());
+
+        assert!(ModeSenseData::parse(&short[..12]).is_err());
+    }
+
+    #[test]
+    fn mode_sense_page() {
+        let command = |page_code, subpage_code| {
+            [
+                OPERATION_CODE,
+                0x10,
+                page_code,
+                subpage_code,
+                0,
+                0,
+                0,
+                0x02,
+                0x00,
+                0,
+            ]
+        };
+        let mut data = vec![
+            0x00, 0x3e, 0x00, 0x00, 0x01, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0xe8, 0xdf,
+            0x88, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00,
+        ];
+        let page = [
+            0x9a, 0x26, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x50,
+        ];
+        data.extend_from_slice(&page);
+        data.resize(0x40, 0);
+        let transport = ReplayTransport::from_records(vec![
+            TraceRecord::good(&command(0x9a, 0x00), DataDirection::FromDevice, &data),
+            TraceRecord::check_condition(
+                &command(0x1a, 0x01),
+                DataDirection::FromDevice,
+                (0x05, 0x24, 0x00),
+            ),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        let mode_sense = device
+            .mode_sense_page(0x1a, 0, PageControl::Default)
+            .unwrap();
+        assert_eq!(
+            mode_sense.block_descriptors[0].number_of_logical_blocks,
+            3_906_963_456
+        );
+        assert_eq!(mode_sense.pages[..page.len()], page);
+        assert_eq!(mode_sense.pages.len(), 0x28);
+        // the page has no subpages
+        assert!(matches!(
+            device.mode_sense_page(0x1a, 0x01, PageControl::Current),
+            Err(crate::Error::CheckCondition(_))
+        ));
+        assert_eq!(device.transport().remaining(), 0);
+    }
 }
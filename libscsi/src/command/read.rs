@@ -0,0 +1,452 @@
+#![allow(dead_code)]
+
+use std::{cell::Cell, io::IoSliceMut};
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const READ_10_OPERATION_CODE: u8 = 0x28;
+const READ_16_OPERATION_CODE: u8 = 0x88;
+
+#[bitfield]
+struct Read10Command {
+    operation_code: B8,
+    rdprotect: B3,
+    dpo: B1,
+    fua: B1,
+    rarc: B1,
+    obsolete: B2,
+    logical_block_address: B32,
+    reserved: B3,
+    group_number: B5,
+    transfer_length: B16,
+    control: B8,
+}
+
+#[bitfield]
+struct Read16Command {
+    operation_code: B8,
+    rdprotect: B3,
+    dpo: B1,
+    fua: B1,
+    rarc: B1,
+    obsolete: B1,
+    dld_2: B1,
+    logical_block_address: B64,
+    transfer_length: B32,
+    dld_1: B1,
+    dld_0: B1,
+    group_number: B6,
+    control: B8,
+}
+
+struct ThisCommand10<'a> {
+    logical_block_address: u32,
+    transfer_length: u16,
+    /// Handed over to the transport by `get_data`
+    data: Cell<Option<&'a mut [u8]>>,
+    data_size: u32,
+}
+
+struct ThisCommand16<'a> {
+    logical_block_address: u64,
+    transfer_length: u32,
+    /// Handed over to the transport by `get_data`
+    data: Cell<Option<&'a mut [u8]>>,
+    data_size: u32,
+}
+
+impl<'a> Command for ThisCommand10<'a> {
+    type CommandBuffer = Read10Command;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = &'a mut [u8];
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(READ_10_OPERATION_CODE)
+            .with_logical_block_address(self.logical_block_address)
+            .with_transfer_length(self.transfer_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.data.take().unwrap()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn get_data_size(&self) -> u32 {
+        self.data_size
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        process_read_result(result, self.data_size)
+    }
+}
+
+impl<'a> Command for ThisCommand16<'a> {
+    type CommandBuffer = Read16Command;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = &'a mut [u8];
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(READ_16_OPERATION_CODE)
+            .with_logical_block_address(self.logical_block_address)
+            .with_transfer_length(self.transfer_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.data.take().unwrap()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn get_data_size(&self) -> u32 {
+        self.data_size
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        process_read_result(result, self.data_size)
+    }
+}
+
+fn process_read_result(
+    result: &ResultData<[u8], BytesSenseBuffer>,
+    data_size: u32,
+) -> crate::Result<()> {
+    result.check_ioctl_error()?;
+    result.check_common_error()?;
+
+    check_short_read(result.transfered_data_length, data_size as usize)
+}
+
+fn check_short_read(transfered: usize, length: usize) -> crate::Result<()> {
+    if transfered < length {
+        return Err(crate::Error::Other(format!(
+            "Short read, got {} of {} bytes",
+            transfered, length
+        )));
+    }
+
+    Ok(())
+}
+
+/// Number of logical blocks in a buffer of `length` bytes.
+pub(super) fn transfer_length(length: usize, logical_block_length: u32) -> crate::Result<u64> {
+    match length.checked_rem(logical_block_length as usize) {
+        Some(0) => Ok((length / logical_block_length as usize) as u64),
+        _ => Err(crate::Error::Other(format!(
+            "Buffer length {} is not a multiple of the logical block length {}",
+            length, logical_block_length
+        ))),
+    }
+}
+
+/// Bytes in a transfer of `maximum_transfer_length` blocks, 0 means no limit.
+pub(super) fn chunk_length(logical_block_length: u32, maximum_transfer_length: u32) -> usize {
+    let maximum_transfer_length = match maximum_transfer_length {
+        0 => u32::MAX,
+        n => n,
+    };
+
+    (maximum_transfer_length as usize).saturating_mul(logical_block_length as usize)
+}
+
+fn data_size(buffer: &[u8]) -> crate::Result<u32> {
+    u32::try_from(buffer.len())
+        .map_err(|_| crate::Error::Other(format!("Buffer too large: {} bytes", buffer.len())))
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Fills `buffer` with as many logical blocks as it holds.
+    /// Size it with `logical_block_length_in_bytes` from `read_capacity16`.
+    pub fn read10(
+        &self,
+        lba: u32,
+        buffer: &mut [u8],
+        logical_block_length: u32,
+    ) -> crate::Result<()> {
+        let transfer_length = transfer_length(buffer.len(), logical_block_length)?;
+        let transfer_length = u16::try_from(transfer_length).map_err(|_| {
+            crate::Error::Other(format!("Too many blocks for READ(10): {}", transfer_length))
+        })?;
+
+        self.execute_command(&ThisCommand10 {
+            logical_block_address: lba,
+            transfer_length,
+            data_size: data_size(buffer)?,
+            data: Cell::new(Some(buffer)),
+        })
+    }
+
+    /// Fills `buffer` with as many logical blocks as it holds.
+    /// Size it with `logical_block_length_in_bytes` from `read_capacity16`.
+    pub fn read16(
+        &self,
+        lba: u64,
+        buffer: &mut [u8],
+        logical_block_length: u32,
+    ) -> crate::Result<()> {
+        let transfer_length = transfer_length(buffer.len(), logical_block_length)?;
+        let transfer_length = u32::try_from(transfer_length).map_err(|_| {
+            crate::Error::Other(format!("Too many blocks for READ(16): {}", transfer_length))
+        })?;
+
+        self.execute_command(&ThisCommand16 {
+            logical_block_address: lba,
+            transfer_length,
+            data_size: data_size(buffer)?,
+            data: Cell::new(Some(buffer)),
+        })
+    }
+
+    /// Like `read16`, but fills `buffers` one after another with a single command,
+    /// without a bounce buffer on Linux. Together they must hold whole logical blocks.
+    pub fn read16_vectored(
+        &self,
+        lba: u64,
+        buffers: &mut [IoSliceMut],
+        logical_block_length: u32,
+    ) -> crate::Result<()> {
+        let length = buffers.iter().map(|buffer| buffer.len()).sum();
+        let transfer_length = transfer_length(length, logical_block_length)?;
+        let transfer_length = u32::try_from(transfer_length).map_err(|_| {
+            crate::Error::Other(format!("Too many blocks for READ(16): {}", transfer_length))
+        })?;
+
+        let command = Read16Command::new()
+            .with_operation_code(READ_16_OPERATION_CODE)
+            .with_logical_block_address(lba)
+            .with_transfer_length(transfer_length)
+            .into_bytes();
+        let transfered = self.execute_vectored(&command, &[], buffers, None)?;
+
+        check_short_read(transfered, length)
+    }
+
+    /// Like `read16`, but split into commands of at most `maximum_transfer_length` blocks,
+    /// see `inquiry_maximum_transfer_length`.
+    pub fn read_blocks(
+        &self,
+        lba: u64,
+        buffer: &mut [u8],
+        logical_block_length: u32,
+        maximum_transfer_length: u32,
+    ) -> crate::Result<()> {
+        transfer_length(buffer.len(), logical_block_length)?;
+
+        let chunk_length = chunk_length(logical_block_length, maximum_transfer_length);
+        let mut lba = lba;
+        for chunk in buffer.chunks_mut(chunk_length) {
+            let blocks = chunk.len() as u64 / logical_block_length as u64;
+            self.read16(lba, chunk, logical_block_length)?;
+            lba += blocks;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const READ_10_COMMAND_LENGTH: usize = 10;
+    const READ_16_COMMAND_LENGTH: usize = 16;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<Read10Command>(),
+            READ_10_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(Read10Command))
+        );
+        assert_eq!(
+            size_of::<Read16Command>(),
+            READ_16_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(Read16Command))
+        );
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(transfer_length(4096, 512).unwrap(), 8);
+        assert!(transfer_length(1000, 512).is_err());
+        assert!(transfer_length(512, 0).is_err());
+        assert_eq!(chunk_length(512, 128), 65536);
+        assert_eq!(chunk_length(512, 0), u32::MAX as usize * 512);
+    }
+
+    #[test]
+    fn vectored_transfers() {
+        use std::io::{ErrorKind, IoSlice, IoSliceMut};
+        use std::time::Duration;
+
+        use crate::{
+            DataDirection, DriverStatus, HostStatus, MaskedStatus, ReplayTransport, Scsi,
+            TraceRecord,
+        };
+
+        let block_length = 512;
+        let data: Vec<u8> = (0..block_length * 3).map(|i| (i % 253) as u8).collect();
+        let record = |command: &[u8], direction, data_out: &[u8], data_in: &[u8]| TraceRecord {
+            command: command.to_vec(),
+            direction,
+            data_out: data_out.to_vec(),
+            data_in: data_in.to_vec(),
+            sense: vec![],
+            masked_status: MaskedStatus::empty(),
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::empty(),
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        };
+        let write_10 = [0x2a, 0, 0, 0, 0, 7, 0, 0, 3, 0];
+        let read_16 = [0x88, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 3, 0, 0];
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                record(&write_10, DataDirection::ToDevice, &data, &[]),
+                record(&read_16, DataDirection::FromDevice, &[], &data),
+            ]),
+        );
+
+        // WRITE(10) of 3 blocks at lba 7, gathered from uneven buffers
+        let (head, tail) = data.split_at(700);
+        let written = device
+            .execute_vectored(
+                &write_10,
+                &[IoSlice::new(head), IoSlice::new(tail)],
+                &mut [],
+                None,
+            )
+            .unwrap();
+        assert_eq!(written, data.len());
+
+        let mut first = vec![0; block_length + 12];
+        let mut second = vec![0; block_length * 2 - 12];
+        device
+            .read16_vectored(
+                7,
+                &mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)],
+                block_length as u32,
+            )
+            .unwrap();
+        assert_eq!([first, second].concat(), data);
+
+        assert!(device
+            .read16_vectored(
+                7,
+                &mut [IoSliceMut::new(&mut [0; 100])],
+                block_length as u32
+            )
+            .is_err());
+
+        let error = device
+            .execute_vectored(
+                &write_10,
+                &[IoSlice::new(&data)],
+                &mut [IoSliceMut::new(&mut [0; 16])],
+                None,
+            )
+            .unwrap_err();
+        assert!(matches!(error, crate::Error::IO(e) if e.kind() == ErrorKind::Unsupported));
+        assert_eq!(device.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn split_reads() {
+        let block_length = 512;
+        let data: Vec<u8> = (0..block_length * 5).map(|i| (i % 251) as u8).collect();
+        let record = |lba: u64, blocks: u32| {
+            let mut command = vec![READ_16_OPERATION_CODE, 0x00];
+            command.extend_from_slice(&lba.to_be_bytes());
+            command.extend_from_slice(&blocks.to_be_bytes());
+            command.extend_from_slice(&[0x00, 0x00]);
+            let start = (lba as usize - 100) * block_length;
+            let end = start + blocks as usize * block_length;
+            TraceRecord::good(&command, DataDirection::FromDevice, &data[start..end])
+        };
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                record(100, 2),
+                record(102, 2),
+                record(104, 1),
+                TraceRecord::check_condition(
+                    &[
+                        READ_16_OPERATION_CODE,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        1,
+                        0,
+                        0,
+                    ],
+                    DataDirection::FromDevice,
+                    (0x05, 0x21, 0x00),
+                ),
+            ]),
+        );
+
+        let mut buffer = vec![0; data.len()];
+        device
+            .read_blocks(100, &mut buffer, block_length as u32, 2)
+            .unwrap();
+        assert_eq!(buffer, data);
+        // not a whole number of blocks, nothing is sent
+        assert!(device
+            .read_blocks(0, &mut buffer[..block_length - 1], block_length as u32, 2)
+            .is_err());
+        let error = device.read16(0, &mut buffer[..block_length], block_length as u32);
+        assert!(matches!(
+            error,
+            Err(crate::Error::CheckCondition(sense)) if sense.sense_key == crate::SenseKey::IllegalRequest
+        ));
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -2,7 +2,7 @@
 
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
 
 use super::sense::{BytesSenseBuffer, Sense};
 
@@ -90,7 +90,7 @@ impl Command for ThisCommand {
     }
 }
 
-impl Scsi {
+impl<T: Transport> Scsi<T> {
     pub fn read_capacity16(&self) -> crate::Result<Capacity> {
         let this_command = ThisCommand {};
         self.execute_command(&this_command)
@@ -0,0 +1,96 @@
+#![allow(dead_code)]
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, SenseData, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const OPERATION_CODE: u8 = 0x03;
+const REQUEST_SENSE_DATA_LENGTH: usize = 252;
+
+#[bitfield]
+struct RequestSenseCommand {
+    operation_code: B8,
+    reserved_0: B7,
+    desc: B1,
+    reserved_1: B16,
+    allocation_length: B8,
+    control: B8,
+}
+
+struct ThisCommand {
+    descriptor_format: bool,
+}
+
+impl Command for ThisCommand {
+    type CommandBuffer = RequestSenseCommand;
+
+    type DataBuffer = [u8; REQUEST_SENSE_DATA_LENGTH];
+
+    type DataBufferWrapper = Self::DataBuffer;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<SenseData>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::FromDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_desc(self.descriptor_format as u8)
+            .with_allocation_length(self.get_data_size() as u8)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        [0; REQUEST_SENSE_DATA_LENGTH]
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()?;
+
+        let data = result.data.as_ref().unwrap();
+        let length = usize::min(result.transfered_data_length, REQUEST_SENSE_DATA_LENGTH);
+
+        SenseData::parse(&data[..length]).ok_or_else(|| {
+            crate::Error::Other(format!("Invalid sense data: {:02X?}", &data[..length]))
+        })
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Returns the pending sense data, NO SENSE if there is nothing to report.
+    ///
+    /// The device may ignore `descriptor_format` and answer in fixed format.
+    pub fn request_sense(&self, descriptor_format: bool) -> crate::Result<SenseData> {
+        self.execute_command(&ThisCommand { descriptor_format })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    const REQUEST_SENSE_COMMAND_LENGTH: usize = 6;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<RequestSenseCommand>(),
+            REQUEST_SENSE_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(RequestSenseCommand))
+        );
+    }
+}
@@ -1,12 +1,24 @@
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, SenseKey, Transport};
+
+#[cfg(target_os = "linux")]
+use crate::QueuedTransport;
 
-use super::sense::{FixedSenseBuffer, Sense};
+use super::{
+    sense::{FixedSenseBuffer, Sense},
+    SelfTestResult, SelfTestStatus,
+};
 
 const OPERATION_CODE: u8 = 0x1d;
+/// A short self-test is meant to finish within two minutes
+const FOREGROUND_SHORT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Reads the whole disk, the log page reports an estimate for it
+const FOREGROUND_EXTENDED_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
 
 #[bitfield]
 struct SendDiagnosticCommand {
@@ -22,7 +34,43 @@ struct SendDiagnosticCommand {
     control: B8,
 }
 
-struct ThisCommand {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestCode {
+    BackgroundShort,
+    BackgroundExtended,
+    /// Abort the running background self-test
+    AbortBackground,
+    /// Returns once the test has finished
+    ForegroundShort,
+    /// Returns once the test has finished, this may take hours
+    ForegroundExtended,
+}
+
+impl From<SelfTestCode> for u8 {
+    fn from(value: SelfTestCode) -> Self {
+        match value {
+            SelfTestCode::BackgroundShort => 0b001,
+            SelfTestCode::BackgroundExtended => 0b010,
+            SelfTestCode::AbortBackground => 0b100,
+            SelfTestCode::ForegroundShort => 0b101,
+            SelfTestCode::ForegroundExtended => 0b110,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfTestProgress {
+    /// In 1/65536 units, if the device reports it
+    InProgress(Option<u16>),
+    /// The most recent self-test
+    Finished(SelfTestResult),
+    NeverRun,
+}
+
+struct ThisCommand {
+    /// `None` for the default self-test
+    self_test_code: Option<SelfTestCode>,
+}
 
 impl Command for ThisCommand {
     type CommandBuffer = SendDiagnosticCommand;
@@ -40,9 +88,11 @@ impl Command for ThisCommand {
     }
 
     fn get_command(&self) -> Self::CommandBuffer {
-        Self::CommandBuffer::new()
-            .with_operation_code(OPERATION_CODE)
-            .with_selftest(1)
+        let command = Self::CommandBuffer::new().with_operation_code(OPERATION_CODE);
+        match self.self_test_code {
+            Some(self_test_code) => command.with_self_test_code(self_test_code.into()),
+            None => command.with_selftest(1),
+        }
     }
 
     fn get_data(&self) -> Self::DataBufferWrapper {}
@@ -51,14 +101,24 @@ impl Command for ThisCommand {
         Self::SenseBuffer::default()
     }
 
+    fn get_timeout(&self) -> Option<Duration> {
+        match self.self_test_code {
+            None | Some(SelfTestCode::ForegroundShort) => Some(FOREGROUND_SHORT_TIMEOUT),
+            Some(SelfTestCode::ForegroundExtended) => Some(FOREGROUND_EXTENDED_TIMEOUT),
+            // Background ones return right away
+            _ => None,
+        }
+    }
+
     fn process_result(
         &self,
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
     ) -> Self::ReturnType {
         result.check_ioctl_error()?;
-        let sense = result.sense_buffer.as_ref().unwrap();
-        if sense.sense_key() == 0x04 {
-            return Ok(TestResult::HardwareError);
+        if let Some(sense) = result.sense_data() {
+            if sense.sense_key == SenseKey::HardwareError {
+                return Ok(TestResult::HardwareError);
+            }
         }
 
         result.check_common_error()?;
@@ -72,15 +132,58 @@ pub enum TestResult {
     HardwareError,
 }
 
-impl Scsi {
+impl<T: Transport> Scsi<T> {
     pub fn send_diagnostic(&self) -> crate::Result<TestResult> {
-        self.execute_command(&ThisCommand {})
+        self.execute_command(&ThisCommand {
+            self_test_code: None,
+        })
+    }
+
+    /// Background self-tests return right away, poll them with [`Scsi::self_test_progress`].
+    pub fn start_self_test(&self, self_test_code: SelfTestCode) -> crate::Result<TestResult> {
+        self.execute_command(&ThisCommand {
+            self_test_code: Some(self_test_code),
+        })
+    }
+
+    pub fn self_test_progress(&self) -> crate::Result<SelfTestProgress> {
+        let results = self.log_sense_self_test_results()?;
+
+        Ok(match results.into_iter().next() {
+            Some(result) if result.status == SelfTestStatus::InProgress => {
+                SelfTestProgress::InProgress(self.request_sense(false)?.progress_indication())
+            }
+            Some(result) => SelfTestProgress::Finished(result),
+            None => SelfTestProgress::NeverRun,
+        })
+    }
+}
+
+/// Foreground self-tests keep the device busy for minutes, these don't block the thread meanwhile.
+#[cfg(target_os = "linux")]
+impl Scsi<QueuedTransport> {
+    pub async fn send_diagnostic_async(&self) -> crate::Result<TestResult> {
+        self.execute_command_async(&ThisCommand {
+            self_test_code: None,
+        })
+        .await
+    }
+
+    pub async fn start_self_test_async(
+        &self,
+        self_test_code: SelfTestCode,
+    ) -> crate::Result<TestResult> {
+        self.execute_command_async(&ThisCommand {
+            self_test_code: Some(self_test_code),
+        })
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ReplayTransport, TraceRecord};
     use std::mem::size_of;
 
     const SEND_DIAGNOSTIC_COMMAND_LENGTH: usize = 6;
@@ -93,4 +196,72 @@ mod tests {
             concat!("Size of: ", stringify!(SendDiagnosticCommand))
         );
     }
+
+    #[test]
+    fn self_test() {
+        let log_sense = |code_and_status: Option<u8>| {
+            let mut page = vec![0x10, 0x00, 0x00, 0x00];
+            if let Some(code_and_status) = code_and_status {
+                page.extend_from_slice(&[0x00, 0x01, 0x03, 0x10, code_and_status, 0x01, 0, 0]);
+                page.extend_from_slice(&u64::MAX.to_be_bytes());
+                page.extend_from_slice(&[0; 4]);
+                page[3] = 0x14;
+            }
+            TraceRecord::good(
+                &[0x4d, 0x00, 0x50, 0, 0, 0, 0, 0x02, 0x00, 0],
+                DataDirection::FromDevice,
+                &page,
+            )
+        };
+        let request_sense = TraceRecord::good(
+            &[0x03, 0x00, 0x00, 0x00, 0xfc, 0x00],
+            DataDirection::FromDevice,
+            &[
+                0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x80, 0x40, 0x00,
+            ],
+        );
+        let transport = ReplayTransport::from_records(vec![
+            log_sense(None),
+            TraceRecord::good(
+                &[OPERATION_CODE, 0x40, 0, 0, 0, 0],
+                DataDirection::ToDevice,
+                &[],
+            ),
+            log_sense(Some(0x4f)),
+            request_sense,
+            log_sense(Some(0x40)),
+            TraceRecord::check_condition(
+                &[OPERATION_CODE, 0x04, 0, 0, 0, 0],
+                DataDirection::ToDevice,
+                (0x04, 0x40, 0x80),
+            ),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        assert_eq!(
+            device.self_test_progress().unwrap(),
+            SelfTestProgress::NeverRun
+        );
+        device
+            .start_self_test(SelfTestCode::BackgroundExtended)
+            .unwrap();
+        assert_eq!(
+            device.self_test_progress().unwrap(),
+            SelfTestProgress::InProgress(Some(0x4000))
+        );
+        match device.self_test_progress().unwrap() {
+            SelfTestProgress::Finished(result) => {
+                assert_eq!(result.self_test_code, 0b010);
+                assert_eq!(result.status, SelfTestStatus::Completed);
+                assert_eq!(result.address_of_first_failure, None);
+            }
+            progress => panic!("Unexpected progress {:?}", progress),
+        }
+        assert!(matches!(
+            device.send_diagnostic().unwrap(),
+            TestResult::HardwareError
+        ));
+        assert_eq!(device.transport().remaining(), 0);
+    }
 }
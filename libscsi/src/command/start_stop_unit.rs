@@ -0,0 +1,187 @@
+#![allow(dead_code)]
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const OPERATION_CODE: u8 = 0x1b;
+
+#[bitfield]
+struct StartStopUnitCommand {
+    operation_code: B8,
+    reserved_0: B7,
+    immed: B1,
+    reserved_1: B8,
+    reserved_2: B4,
+    power_condition_modifier: B4,
+    power_condition: B4,
+    reserved_3: B1,
+    no_flush: B1,
+    loej: B1,
+    start: B1,
+    control: B8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerCondition {
+    /// Use START and LOEJ
+    StartValid,
+    Active,
+    Idle,
+    Standby,
+    /// Hand power control back to the logical unit, timers apply again
+    LuControl,
+    ForceIdle0,
+    ForceStandby0,
+}
+
+impl From<PowerCondition> for u8 {
+    fn from(value: PowerCondition) -> Self {
+        match value {
+            PowerCondition::StartValid => 0x0,
+            PowerCondition::Active => 0x1,
+            PowerCondition::Idle => 0x2,
+            PowerCondition::Standby => 0x3,
+            PowerCondition::LuControl => 0x7,
+            PowerCondition::ForceIdle0 => 0xa,
+            PowerCondition::ForceStandby0 => 0xb,
+        }
+    }
+}
+
+struct ThisCommand {
+    start: bool,
+    load_eject: bool,
+    immediate: bool,
+    power_condition: PowerCondition,
+}
+
+impl Command for ThisCommand {
+    type CommandBuffer = StartStopUnitCommand;
+
+    type DataBuffer = ();
+
+    type DataBufferWrapper = ();
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(OPERATION_CODE)
+            .with_immed(self.immediate as u8)
+            .with_power_condition(self.power_condition.into())
+            .with_loej(self.load_eject as u8)
+            .with_start(self.start as u8)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {}
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// `start` and `load_eject` are ignored by the device unless `power_condition` is
+    /// [`PowerCondition::StartValid`]. With `immediate` the device reports completion before the
+    /// operation has finished.
+    pub fn start_stop_unit(
+        &self,
+        start: bool,
+        load_eject: bool,
+        immediate: bool,
+        power_condition: PowerCondition,
+    ) -> crate::Result<()> {
+        self.execute_command(&ThisCommand {
+            start,
+            load_eject,
+            immediate,
+            power_condition,
+        })
+    }
+
+    /// Spin up and make the medium accessible.
+    pub fn spin_up(&self) -> crate::Result<()> {
+        self.start_stop_unit(true, false, false, PowerCondition::StartValid)
+    }
+
+    /// Spin down and park the heads now, returns once the drive has stopped.
+    pub fn spin_down(&self) -> crate::Result<()> {
+        self.start_stop_unit(false, false, false, PowerCondition::StartValid)
+    }
+
+    /// Enter the standby power condition now, the drive wakes up on the next media access.
+    pub fn standby(&self) -> crate::Result<()> {
+        self.start_stop_unit(false, false, false, PowerCondition::Standby)
+    }
+
+    /// Enter the idle power condition now.
+    pub fn idle(&self) -> crate::Result<()> {
+        self.start_stop_unit(false, false, false, PowerCondition::Idle)
+    }
+
+    /// Stop the unit and eject the medium, if the device supports it.
+    pub fn eject(&self) -> crate::Result<()> {
+        self.start_stop_unit(false, true, false, PowerCondition::StartValid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const START_STOP_UNIT_COMMAND_LENGTH: usize = 6;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<StartStopUnitCommand>(),
+            START_STOP_UNIT_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(StartStopUnitCommand))
+        );
+    }
+
+    #[test]
+    fn power_conditions() {
+        let record = |byte_4| {
+            TraceRecord::good(
+                &[OPERATION_CODE, 0x00, 0x00, 0x00, byte_4, 0x00],
+                DataDirection::None,
+                &[],
+            )
+        };
+        let transport = ReplayTransport::from_records(vec![
+            record(0x30),
+            record(0x20),
+            record(0x00),
+            record(0x01),
+            record(0x02),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        device.standby().unwrap();
+        device.idle().unwrap();
+        device.spin_down().unwrap();
+        device.spin_up().unwrap();
+        device.eject().unwrap();
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+#![allow(dead_code)]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Error, Scsi, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const OPERATION_CODE: u8 = 0x00;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Each reset or medium change is reported once, but several can be queued up.
+const MAX_UNIT_ATTENTIONS: usize = 8;
+
+#[bitfield]
+struct TestUnitReadyCommand {
+    operation_code: B8,
+    reserved: B32,
+    control: B8,
+}
+
+struct ThisCommand {}
+
+impl Command for ThisCommand {
+    type CommandBuffer = TestUnitReadyCommand;
+
+    type DataBuffer = ();
+
+    type DataBufferWrapper = ();
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    /// Only asks, it's what waits for a drive that's slow to spin up
+    fn is_retry_safe(&self) -> bool {
+        true
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new().with_operation_code(OPERATION_CODE)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {}
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Fails with [`Error::CheckCondition`] if the logical unit isn't ready.
+    pub fn test_unit_ready(&self) -> crate::Result<()> {
+        self.execute_command(&ThisCommand {})
+    }
+
+    /// Polls TEST UNIT READY until the logical unit is ready.
+    ///
+    /// Pending unit attentions are consumed and a logical unit that is becoming ready is waited for,
+    /// until `timeout` expires. A locked medium won't become ready by itself, so it fails right away.
+    pub fn wait_until_ready(&self, timeout: Duration) -> crate::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut unit_attentions = 0;
+
+        loop {
+            let sense = match self.test_unit_ready() {
+                Ok(()) => return Ok(()),
+                Err(Error::CheckCondition(sense)) => sense,
+                Err(e) => return Err(e),
+            };
+
+            if sense.is_unit_attention() && unit_attentions < MAX_UNIT_ATTENTIONS {
+                unit_attentions += 1;
+                continue;
+            }
+
+            if !sense.is_becoming_ready() || Instant::now() >= deadline {
+                return Err(Error::CheckCondition(sense));
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Whether the medium is present but can't be accessed until it's unlocked.
+    pub fn is_medium_locked(&self) -> crate::Result<bool> {
+        for _ in 0..=MAX_UNIT_ATTENTIONS {
+            match self.test_unit_ready() {
+                Ok(()) => return Ok(false),
+                Err(Error::CheckCondition(sense)) if sense.is_unit_attention() => continue,
+                Err(Error::CheckCondition(sense)) if sense.is_locked() => return Ok(true),
+                Err(Error::CheckCondition(sense)) if sense.is_becoming_ready() => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const TEST_UNIT_READY_COMMAND_LENGTH: usize = 6;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<TestUnitReadyCommand>(),
+            TEST_UNIT_READY_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(TestUnitReadyCommand))
+        );
+    }
+
+    fn record(sense: Option<(u8, u8, u8)>) -> TraceRecord {
+        let command = [OPERATION_CODE, 0x00, 0x00, 0x00, 0x00, 0x00];
+        match sense {
+            Some(sense) => TraceRecord::check_condition(&command, DataDirection::None, sense),
+            None => TraceRecord::good(&command, DataDirection::None, &[]),
+        }
+    }
+
+    #[test]
+    fn wait_until_ready() {
+        let transport = ReplayTransport::from_records(vec![
+            record(Some((0x06, 0x29, 0x00))),
+            record(Some((0x02, 0x04, 0x01))),
+            record(Some((0x02, 0x04, 0x01))),
+            record(None),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        device.wait_until_ready(Duration::from_secs(10)).unwrap();
+        assert_eq!(device.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn locked() {
+        let transport = ReplayTransport::from_records(vec![
+            record(Some((0x07, 0x74, 0x71))),
+            record(Some((0x06, 0x28, 0x00))),
+            record(Some((0x07, 0x74, 0x71))),
+        ]);
+        let device = Scsi::with_transport("replay", transport);
+
+        let error = device.wait_until_ready(Duration::from_secs(10));
+        assert!(matches!(error, Err(Error::CheckCondition(s)) if s.is_locked()));
+        assert!(device.is_medium_locked().unwrap());
+    }
+}
@@ -1,15 +1,19 @@
 #![allow(dead_code)]
 
-use std::mem::size_of;
+use std::time::Duration;
 
 use modular_bitfield_msb::prelude::*;
 
-use crate::{result_data::ResultData, Command, DataDirection, Scsi};
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
 
 use super::sense::{BytesSenseBuffer, Sense};
 
 const OPERATION_CODE: u8 = 0x42;
+const UNMAP_PARAMETER_LIST_HEADER_LENGTH: usize = 8;
+const UNMAP_BLOCK_DESCRIPTOR_LENGTH: usize = 16;
 
+/// Deallocating a whole disk can take minutes
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
 #[bitfield]
 struct UnmapCommand {
     operation_code: B8,
@@ -22,34 +26,39 @@ struct UnmapCommand {
     control: B8,
 }
 
-#[repr(packed)]
-struct UnmapParameterList<const N: usize> {
-    /// size of unmap_block_descriptor_data_length + _reserved + unmap_block_descriptors
-    unmap_data_length: u16,
-    /// size of unmap_block_descriptors
-    unmap_block_descriptor_data_length: u16,
-    _reserved: u32,
-    unmap_block_descriptors: [UnmapBlockDescriptor; N],
-}
+/// (logical block address, number of logical blocks)
+type UnmapBlockDescriptor = (u64, u32);
 
-#[repr(packed)]
-struct UnmapBlockDescriptor {
-    unmap_logical_block_address: u64,
-    number_of_logical_blocks: u32,
-    _reserved: u32,
+struct ThisCommand<'a> {
+    descriptors: &'a [UnmapBlockDescriptor],
 }
 
-struct ThisCommand {
-    unmap_logical_block_address: u64,
-    number_of_logical_blocks: u32,
+impl ThisCommand<'_> {
+    fn parameter_list(&self) -> Vec<u8> {
+        let descriptor_data_length = self.descriptors.len() * UNMAP_BLOCK_DESCRIPTOR_LENGTH;
+
+        let mut data =
+            Vec::with_capacity(UNMAP_PARAMETER_LIST_HEADER_LENGTH + descriptor_data_length);
+        // the unmap data length doesn't count itself
+        data.extend_from_slice(&((descriptor_data_length + 6) as u16).to_be_bytes());
+        data.extend_from_slice(&(descriptor_data_length as u16).to_be_bytes());
+        data.extend_from_slice(&[0; 4]);
+        for (logical_block_address, number_of_logical_blocks) in self.descriptors {
+            data.extend_from_slice(&logical_block_address.to_be_bytes());
+            data.extend_from_slice(&number_of_logical_blocks.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+        }
+
+        data
+    }
 }
 
-impl Command for ThisCommand {
+impl Command for ThisCommand<'_> {
     type CommandBuffer = UnmapCommand;
 
-    type DataBuffer = UnmapParameterList<1>;
+    type DataBuffer = [u8];
 
-    type DataBufferWrapper = Self::DataBuffer;
+    type DataBufferWrapper = Vec<u8>;
 
     type SenseBuffer = BytesSenseBuffer;
 
@@ -60,30 +69,26 @@ impl Command for ThisCommand {
     }
 
     fn get_command(&self) -> Self::CommandBuffer {
+        let parameter_list_length = UNMAP_PARAMETER_LIST_HEADER_LENGTH
+            + self.descriptors.len() * UNMAP_BLOCK_DESCRIPTOR_LENGTH;
+
         UnmapCommand::new()
             .with_operation_code(OPERATION_CODE)
-            .with_parameter_list_length(self.get_data_size() as u16)
+            .with_parameter_list_length(parameter_list_length as u16)
     }
 
-    fn get_data(&self) -> Self::DataBuffer {
-        type T = UnmapParameterList<1>;
-        T {
-            unmap_data_length: ((size_of::<T>() - size_of::<u16>()) as u16).to_be(),
-            unmap_block_descriptor_data_length: (size_of::<[UnmapBlockDescriptor; 1]>() as u16)
-                .to_be(),
-            _reserved: 0,
-            unmap_block_descriptors: [UnmapBlockDescriptor {
-                unmap_logical_block_address: self.unmap_logical_block_address.to_be(),
-                number_of_logical_blocks: self.number_of_logical_blocks.to_be(),
-                _reserved: 0,
-            }],
-        }
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.parameter_list()
     }
 
     fn get_sense_buffer(&self) -> Self::SenseBuffer {
         Self::SenseBuffer::default()
     }
 
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(TIMEOUT)
+    }
+
     fn process_result(
         &self,
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
@@ -93,23 +98,60 @@ impl Command for ThisCommand {
     }
 }
 
-impl Scsi {
-    pub fn unmap(&self, lba_offset: u64, lba_count: u64, max_unmap_lba: u32) -> crate::Result<()> {
-        // it's actually last_lba + 1
-        let last_lba = lba_offset.saturating_add(lba_count);
-        let lba_sets: Vec<_> = (lba_offset..last_lba - 1)
-            .step_by(max_unmap_lba as usize)
-            .into_iter()
-            .map(|n| (n, u64::min(last_lba - n, max_unmap_lba as u64) as u32))
-            .collect();
-
-        for lba_set in lba_sets.iter() {
-            let this_command = ThisCommand {
-                unmap_logical_block_address: lba_set.0,
-                number_of_logical_blocks: lba_set.1,
-            };
-
-            self.execute_command(&this_command)?;
+/// Splits the range into descriptors of at most `max_unmap_lba` blocks, as they are sent.
+fn block_descriptors(
+    lba_offset: u64,
+    lba_count: u64,
+    max_unmap_lba: u32,
+) -> impl Iterator<Item = UnmapBlockDescriptor> {
+    // it's actually last_lba + 1
+    let last_lba = lba_offset.saturating_add(lba_count);
+    let max_unmap_lba = match max_unmap_lba {
+        0 => u32::MAX,
+        n => n,
+    };
+
+    (lba_offset..last_lba)
+        .step_by(max_unmap_lba as usize)
+        .map(move |n| (n, u64::min(last_lba - n, max_unmap_lba as u64) as u32))
+}
+
+impl<T: Transport> Scsi<T> {
+    /// `max_unmap_lba` and `max_descriptors` come from the Block Limits VPD page, see
+    /// `inquiry_unmap_block_limit` and `inquiry_unmap_descriptor_limit`.
+    /// `progress` gets the number of blocks unmapped so far after each command.
+    pub fn unmap<F: FnMut(u64)>(
+        &self,
+        lba_offset: u64,
+        lba_count: u64,
+        max_unmap_lba: u32,
+        max_descriptors: u32,
+        mut progress: F,
+    ) -> crate::Result<()> {
+        // the parameter list length is only 16 bits
+        let max_descriptors = (max_descriptors as usize).clamp(
+            1,
+            (u16::MAX as usize - UNMAP_PARAMETER_LIST_HEADER_LENGTH)
+                / UNMAP_BLOCK_DESCRIPTOR_LENGTH,
+        );
+
+        let mut unmapped = 0;
+        let mut block_descriptors = block_descriptors(lba_offset, lba_count, max_unmap_lba);
+        loop {
+            let descriptors: Vec<_> = block_descriptors.by_ref().take(max_descriptors).collect();
+            if descriptors.is_empty() {
+                break;
+            }
+
+            self.execute_command(&ThisCommand {
+                descriptors: &descriptors,
+            })?;
+
+            unmapped += descriptors
+                .iter()
+                .map(|(_, count)| *count as u64)
+                .sum::<u64>();
+            progress(unmapped);
         }
 
         Ok(())
@@ -122,8 +164,6 @@ mod tests {
     use std::mem::size_of;
 
     const SG_UNMAP_CMD_LEN: usize = 10;
-    const SG_UNMAP_PARAMETER_1_LEN: usize = 8 + 16;
-    const SG_UNMAP_PARAMETER_2_LEN: usize = 8 + 16 * 2;
 
     #[test]
     fn layout_test() {
@@ -132,17 +172,26 @@ mod tests {
             SG_UNMAP_CMD_LEN,
             concat!("Size of: ", stringify!(UnmapCommand))
         );
+    }
 
-        assert_eq!(
-            size_of::<UnmapParameterList<1>>(),
-            SG_UNMAP_PARAMETER_1_LEN,
-            concat!("Size of: ", stringify!(UnmapParameterList<1>))
-        );
-
-        assert_eq!(
-            size_of::<UnmapParameterList<2>>(),
-            SG_UNMAP_PARAMETER_2_LEN,
-            concat!("Size of: ", stringify!(UnmapParameterList<2>))
-        );
+    #[test]
+    fn parameter_list() {
+        let descriptors: Vec<_> = block_descriptors(0x10, 0x25, 0x10).collect();
+        assert_eq!(descriptors, [(0x10, 0x10), (0x20, 0x10), (0x30, 0x05)]);
+        assert!(block_descriptors(0, 1, 0x10).eq([(0, 1)]));
+        assert_eq!(block_descriptors(5, 0, 0x10).next(), None);
+        // one at a time, the whole range wouldn't fit in memory
+        assert_eq!(block_descriptors(0, u64::MAX, 1).nth(3), Some((3, 1)));
+
+        let data = ThisCommand {
+            descriptors: &descriptors[1..],
+        }
+        .parameter_list();
+        assert_eq!(data.len(), 8 + 16 * 2);
+        assert_eq!(data[..8], [0x00, 0x26, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(data[8..16], 0x20u64.to_be_bytes());
+        assert_eq!(data[16..20], 0x10u32.to_be_bytes());
+        assert_eq!(data[24..32], 0x30u64.to_be_bytes());
+        assert_eq!(data[32..36], 0x05u32.to_be_bytes());
     }
 }
@@ -0,0 +1,157 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const VERIFY_16_OPERATION_CODE: u8 = 0x8f;
+
+/// Every block is read back from the medium
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+#[bitfield]
+struct Verify16Command {
+    operation_code: B8,
+    vrprotect: B3,
+    dpo: B1,
+    reserved_0: B1,
+    bytchk: B2,
+    reserved_1: B1,
+    logical_block_address: B64,
+    verification_length: B32,
+    restricted_for_mmc_6: B1,
+    reserved_2: B1,
+    group_number: B6,
+    control: B8,
+}
+
+struct ThisCommand {
+    logical_block_address: u64,
+    verification_length: u32,
+}
+
+impl Command for ThisCommand {
+    type CommandBuffer = Verify16Command;
+
+    type DataBuffer = ();
+
+    type DataBufferWrapper = ();
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        // BYTCHK 0, the device only checks the medium, no data is compared
+        Self::CommandBuffer::new()
+            .with_operation_code(VERIFY_16_OPERATION_CODE)
+            .with_logical_block_address(self.logical_block_address)
+            .with_verification_length(self.verification_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {}
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(TIMEOUT)
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Checks that `block_count` logical blocks starting at `lba` can be read back,
+    /// without transferring them.
+    pub fn verify16(&self, lba: u64, block_count: u32) -> crate::Result<()> {
+        self.execute_command(&ThisCommand {
+            logical_block_address: lba,
+            verification_length: block_count,
+        })
+    }
+
+    /// Like `verify16`, but split into commands of at most `maximum_transfer_length` blocks,
+    /// see `inquiry_maximum_transfer_length`.
+    pub fn verify_blocks(
+        &self,
+        lba: u64,
+        block_count: u64,
+        maximum_transfer_length: u32,
+    ) -> crate::Result<()> {
+        let maximum_transfer_length = match maximum_transfer_length {
+            0 => u32::MAX,
+            n => n,
+        } as u64;
+
+        let end = lba + block_count;
+        let mut lba = lba;
+        while lba < end {
+            let blocks = u64::min(end - lba, maximum_transfer_length);
+            self.verify16(lba, blocks as u32)?;
+            lba += blocks;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const VERIFY_16_COMMAND_LENGTH: usize = 16;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<Verify16Command>(),
+            VERIFY_16_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(Verify16Command))
+        );
+    }
+
+    #[test]
+    fn split_verifies() {
+        let command = |lba: u64, blocks: u32| {
+            let mut command = vec![VERIFY_16_OPERATION_CODE, 0x00];
+            command.extend_from_slice(&lba.to_be_bytes());
+            command.extend_from_slice(&blocks.to_be_bytes());
+            command.extend_from_slice(&[0x00, 0x00]);
+            command
+        };
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                TraceRecord::good(&command(0, 0x800), DataDirection::None, &[]),
+                TraceRecord::good(&command(0x800, 0x800), DataDirection::None, &[]),
+                TraceRecord::good(&command(0x1000, 0x388), DataDirection::None, &[]),
+                TraceRecord::check_condition(
+                    &command(0xffff, 2),
+                    DataDirection::None,
+                    (0x05, 0x21, 0x00),
+                ),
+            ]),
+        );
+
+        device.verify_blocks(0, 5000, 0x800).unwrap();
+        assert!(device.verify16(0xffff, 2).is_err());
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
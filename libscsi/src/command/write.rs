@@ -0,0 +1,244 @@
+#![allow(dead_code)]
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::{
+    read::{chunk_length, transfer_length},
+    sense::{BytesSenseBuffer, Sense},
+};
+
+const WRITE_10_OPERATION_CODE: u8 = 0x2a;
+const WRITE_16_OPERATION_CODE: u8 = 0x8a;
+
+#[bitfield]
+struct Write10Command {
+    operation_code: B8,
+    wrprotect: B3,
+    dpo: B1,
+    fua: B1,
+    reserved_0: B1,
+    obsolete: B2,
+    logical_block_address: B32,
+    reserved_1: B3,
+    group_number: B5,
+    transfer_length: B16,
+    control: B8,
+}
+
+#[bitfield]
+struct Write16Command {
+    operation_code: B8,
+    wrprotect: B3,
+    dpo: B1,
+    fua: B1,
+    reserved: B1,
+    obsolete: B1,
+    dld_2: B1,
+    logical_block_address: B64,
+    transfer_length: B32,
+    dld_1: B1,
+    dld_0: B1,
+    group_number: B6,
+    control: B8,
+}
+
+struct ThisCommand10<'a> {
+    logical_block_address: u32,
+    transfer_length: u16,
+    data: &'a [u8],
+}
+
+struct ThisCommand16<'a> {
+    logical_block_address: u64,
+    transfer_length: u32,
+    data: &'a [u8],
+}
+
+impl Command for ThisCommand10<'_> {
+    type CommandBuffer = Write10Command;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::ToDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(WRITE_10_OPERATION_CODE)
+            .with_logical_block_address(self.logical_block_address)
+            .with_transfer_length(self.transfer_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.data.to_vec()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl Command for ThisCommand16<'_> {
+    type CommandBuffer = Write16Command;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::ToDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(WRITE_16_OPERATION_CODE)
+            .with_logical_block_address(self.logical_block_address)
+            .with_transfer_length(self.transfer_length)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.data.to_vec()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Writes as many logical blocks as `data` holds.
+    pub fn write10(&self, lba: u32, data: &[u8], logical_block_length: u32) -> crate::Result<()> {
+        let transfer_length = transfer_length(data.len(), logical_block_length)?;
+        let transfer_length = u16::try_from(transfer_length).map_err(|_| {
+            crate::Error::Other(format!(
+                "Too many blocks for WRITE(10): {}",
+                transfer_length
+            ))
+        })?;
+
+        self.execute_command(&ThisCommand10 {
+            logical_block_address: lba,
+            transfer_length,
+            data,
+        })
+    }
+
+    /// Writes as many logical blocks as `data` holds.
+    pub fn write16(&self, lba: u64, data: &[u8], logical_block_length: u32) -> crate::Result<()> {
+        let transfer_length = transfer_length(data.len(), logical_block_length)?;
+        let transfer_length = u32::try_from(transfer_length)
+            .ok()
+            .filter(|_| u32::try_from(data.len()).is_ok())
+            .ok_or_else(|| {
+                crate::Error::Other(format!(
+                    "Too many blocks for WRITE(16): {}",
+                    transfer_length
+                ))
+            })?;
+
+        self.execute_command(&ThisCommand16 {
+            logical_block_address: lba,
+            transfer_length,
+            data,
+        })
+    }
+
+    /// Like `write16`, but split into commands of at most `maximum_transfer_length` blocks,
+    /// see `inquiry_maximum_transfer_length`.
+    pub fn write_blocks(
+        &self,
+        lba: u64,
+        data: &[u8],
+        logical_block_length: u32,
+        maximum_transfer_length: u32,
+    ) -> crate::Result<()> {
+        transfer_length(data.len(), logical_block_length)?;
+
+        let chunk_length = chunk_length(logical_block_length, maximum_transfer_length);
+        let mut lba = lba;
+        for chunk in data.chunks(chunk_length) {
+            self.write16(lba, chunk, logical_block_length)?;
+            lba += chunk.len() as u64 / logical_block_length as u64;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplayTransport, TraceRecord};
+    use std::mem::size_of;
+
+    const WRITE_10_COMMAND_LENGTH: usize = 10;
+    const WRITE_16_COMMAND_LENGTH: usize = 16;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<Write10Command>(),
+            WRITE_10_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(Write10Command))
+        );
+        assert_eq!(
+            size_of::<Write16Command>(),
+            WRITE_16_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(Write16Command))
+        );
+    }
+
+    #[test]
+    fn split_writes() {
+        let block_length = 512;
+        let data: Vec<u8> = (0..block_length * 5).map(|i| (i % 251) as u8).collect();
+        let record = |lba: u64, blocks: u32| {
+            let mut command = vec![WRITE_16_OPERATION_CODE, 0x00];
+            command.extend_from_slice(&lba.to_be_bytes());
+            command.extend_from_slice(&blocks.to_be_bytes());
+            command.extend_from_slice(&[0x00, 0x00]);
+            TraceRecord::good(&command, DataDirection::ToDevice, &[])
+        };
+        let device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![record(100, 2), record(102, 2), record(104, 1)]),
+        );
+
+        device
+            .write_blocks(100, &data, block_length as u32, 2)
+            .unwrap();
+        assert!(device
+            .write_blocks(0, &data[..block_length + 1], block_length as u32, 2)
+            .is_err());
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use modular_bitfield_msb::prelude::*;
+
+use crate::{result_data::ResultData, Command, DataDirection, Scsi, Transport};
+
+use super::sense::{BytesSenseBuffer, Sense};
+
+const WRITE_SAME_16_OPERATION_CODE: u8 = 0x93;
+
+/// Zeroing up to the maximum write same length in one go takes a while
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+#[bitfield]
+struct WriteSame16Command {
+    operation_code: B8,
+    wrprotect: B3,
+    anchor: B1,
+    unmap: B1,
+    obsolete: B2,
+    ndob: B1,
+    logical_block_address: B64,
+    number_of_logical_blocks: B32,
+    dld_1: B1,
+    dld_0: B1,
+    group_number: B6,
+    control: B8,
+}
+
+struct ThisCommand<'a> {
+    logical_block_address: u64,
+    number_of_logical_blocks: u32,
+    unmap: bool,
+    block: &'a [u8],
+}
+
+impl Command for ThisCommand<'_> {
+    type CommandBuffer = WriteSame16Command;
+
+    type DataBuffer = [u8];
+
+    type DataBufferWrapper = Vec<u8>;
+
+    type SenseBuffer = BytesSenseBuffer;
+
+    type ReturnType = crate::Result<()>;
+
+    fn get_direction(&self) -> DataDirection {
+        DataDirection::ToDevice
+    }
+
+    fn get_command(&self) -> Self::CommandBuffer {
+        Self::CommandBuffer::new()
+            .with_operation_code(WRITE_SAME_16_OPERATION_CODE)
+            .with_unmap(self.unmap as u8)
+            .with_logical_block_address(self.logical_block_address)
+            .with_number_of_logical_blocks(self.number_of_logical_blocks)
+    }
+
+    fn get_data(&self) -> Self::DataBufferWrapper {
+        self.block.to_vec()
+    }
+
+    fn get_sense_buffer(&self) -> Self::SenseBuffer {
+        Self::SenseBuffer::default()
+    }
+
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(TIMEOUT)
+    }
+
+    fn process_result(
+        &self,
+        result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
+    ) -> Self::ReturnType {
+        result.check_ioctl_error()?;
+        result.check_common_error()
+    }
+}
+
+impl<T: Transport> Scsi<T> {
+    /// Writes `block`, exactly one logical block, to `block_count` blocks starting at `lba`.
+    /// With `unmap` the device may deallocate them instead, `block` should be all zeros then.
+    /// A `block_count` of 0 means up to the last block, if the device allows it.
+    pub fn write_same16(
+        &self,
+        lba: u64,
+        block_count: u32,
+        block: &[u8],
+        unmap: bool,
+    ) -> crate::Result<()> {
+        self.execute_command(&ThisCommand {
+            logical_block_address: lba,
+            number_of_logical_blocks: block_count,
+            unmap,
+            block,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+
+    const WRITE_SAME_16_COMMAND_LENGTH: usize = 16;
+
+    #[test]
+    fn layout_test() {
+        assert_eq!(
+            size_of::<WriteSame16Command>(),
+            WRITE_SAME_16_COMMAND_LENGTH,
+            concat!("Size of: ", stringify!(WriteSame16Command))
+        );
+    }
+}
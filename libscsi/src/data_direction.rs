@@ -1,7 +1,7 @@
 use std::ffi::c_int;
 
 #[repr(i32)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataDirection {
     /// e.g. a SCSI Test Unit Ready command
     None = -1,
@@ -12,7 +12,8 @@ pub enum DataDirection {
     /// treated like FromDevice with the
     /// additional property than during indirect
     /// IO the user buffer is copied into the
-    /// kernel buffers before the transfer
+    /// kernel buffers before the transfer,
+    /// commands that really send and receive at once go through `Scsi::execute_vectored`
     ToFromDevice = -4,
     Unknown = -5,
 }
@@ -0,0 +1,713 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const SYSFS_ROOT: &str = "/sys";
+const DEV_ROOT: &str = "/dev";
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+/// Only SCSI disks have a SCSI Generic node worth talking to
+const DISK_PREFIX: &str = "sd";
+/// Unit serial number VPD page as exposed by the kernel, the serial starts after the page header
+const UNIT_SERIAL_NUMBER_VPD_FILE: &str = "vpd_pg80";
+const VPD_HEADER_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsbDriver {
+    /// USB Attached SCSI
+    Uas,
+    /// Bulk-only transport
+    UsbStorage,
+    Other(String),
+}
+
+impl UsbDriver {
+    fn from_name(name: &str) -> UsbDriver {
+        match name {
+            "uas" => UsbDriver::Uas,
+            "usb-storage" => UsbDriver::UsbStorage,
+            _ => UsbDriver::Other(name.to_owned()),
+        }
+    }
+}
+
+impl std::fmt::Display for UsbDriver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsbDriver::Uas => write!(f, "uas"),
+            UsbDriver::UsbStorage => write!(f, "usb-storage"),
+            UsbDriver::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A SCSI disk as seen by sysfs, nothing is sent to the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskInfo {
+    /// e.g. /dev/sdb
+    pub path: PathBuf,
+    /// e.g. /dev/sg2, if the sg driver is loaded
+    pub sg_path: Option<PathBuf>,
+    /// T10 vendor identification, trailing spaces are trimmed
+    pub vendor: String,
+    /// Product identification, trailing spaces are trimmed
+    pub model: String,
+    pub revision: String,
+    /// The USB serial number, or the unit serial number VPD page if the kernel read it
+    pub serial: Option<String>,
+    pub usb_vendor_id: Option<u16>,
+    pub usb_product_id: Option<u16>,
+    /// Driver bound to the USB interface, None if the disk isn't on USB
+    pub driver: Option<UsbDriver>,
+    /// e.g. /dev/sdb1, in order
+    pub partitions: Vec<PathBuf>,
+}
+
+/// Lists every SCSI disk (/dev/sd*) sorted by path.
+pub fn list_disks() -> crate::Result<Vec<DiskInfo>> {
+    list_disks_in(Path::new(SYSFS_ROOT))
+}
+
+fn list_disks_in(sysfs_root: &Path) -> crate::Result<Vec<DiskInfo>> {
+    let mut disks = vec![];
+    for entry in fs::read_dir(sysfs_root.join("block"))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(DISK_PREFIX) {
+            continue;
+        }
+
+        // The disk may vanish while we are looking at it
+        if let Ok(disk) = read_disk(&entry.path(), &name) {
+            disks.push(disk);
+        }
+    }
+
+    disks.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(disks)
+}
+
+/// The SCSI Generic node of a block device or one of its partitions.
+/// None if `path` isn't a block device or the sg driver isn't loaded.
+pub fn sg_path(path: &Path) -> crate::Result<Option<PathBuf>> {
+    use nix::libc::{major, minor};
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let metadata = fs::metadata(path)?;
+    if !metadata.file_type().is_block_device() {
+        return Ok(None);
+    }
+
+    let device = metadata.rdev();
+    Ok(sg_path_in(
+        Path::new(SYSFS_ROOT),
+        major(device),
+        minor(device),
+    ))
+}
+
+/// The bsg node (/dev/bsg/H:C:T:L) of a disk, one of its partitions or its sg node.
+/// `Scsi::new` takes it like any other node, bidirectional commands always go through it.
+/// None if it isn't a SCSI disk or the bsg driver isn't loaded.
+pub fn bsg_path(path: &Path) -> crate::Result<Option<PathBuf>> {
+    let (kind, major, minor) = device_number(path)?;
+
+    Ok(bsg_path_in(Path::new(SYSFS_ROOT), kind, major, minor))
+}
+
+/// Whether the character device `major`:`minor` is a bsg node.
+pub(crate) fn is_bsg(major: u32, minor: u32) -> bool {
+    is_bsg_in(Path::new(SYSFS_ROOT), major, minor)
+}
+
+fn is_bsg_in(sysfs_root: &Path, major: u32, minor: u32) -> bool {
+    sysfs_root
+        .join("dev/char")
+        .join(format!("{}:{}", major, minor))
+        .join("subsystem")
+        .canonicalize()
+        .is_ok_and(|subsystem| subsystem.ends_with("bsg"))
+}
+
+fn sg_path_in(sysfs_root: &Path, major: u32, minor: u32) -> Option<PathBuf> {
+    let block_path = disk_block_path(sysfs_root, "block", major, minor)?;
+
+    read_sg_path(&block_path.join("device"))
+}
+
+fn bsg_path_in(sysfs_root: &Path, kind: &str, major: u32, minor: u32) -> Option<PathBuf> {
+    let block_path = disk_block_path(sysfs_root, kind, major, minor)?;
+
+    read_bsg_path(&block_path.join("device"))
+}
+
+/// The sysfs directory of a disk, given one of its partitions, itself or its sg node.
+/// `kind` is "block" or "char".
+fn disk_block_path(sysfs_root: &Path, kind: &str, major: u32, minor: u32) -> Option<PathBuf> {
+    let device_path = sysfs_root
+        .join("dev")
+        .join(kind)
+        .join(format!("{}:{}", major, minor))
+        .canonicalize()
+        .ok()?;
+
+    let block_path = match kind {
+        "char" => fs::read_dir(device_path.join("device/block"))
+            .ok()?
+            .next()?
+            .ok()?
+            .path()
+            .canonicalize()
+            .ok()?,
+        _ => device_path,
+    };
+
+    // Partitions sit inside the directory of their disk
+    match block_path.join("partition").exists() {
+        true => Some(block_path.parent()?.to_owned()),
+        false => Some(block_path),
+    }
+}
+
+/// Something that keeps a disk or one of its partitions busy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiskUser {
+    Mounted {
+        device: PathBuf,
+        mount_point: PathBuf,
+    },
+    /// e.g. a device mapper or md device built on top of it
+    Held { device: PathBuf, holder: String },
+}
+
+impl std::fmt::Display for DiskUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiskUser::Mounted {
+                device,
+                mount_point,
+            } => write!(
+                f,
+                "{} is mounted on {}",
+                device.display(),
+                mount_point.display()
+            ),
+            DiskUser::Held { device, holder } => {
+                write!(f, "{} is used by {}", device.display(), holder)
+            }
+        }
+    }
+}
+
+/// Mounts and holders of the disk `path` belongs to, `path` may be the disk, a partition or its
+/// sg node.
+pub fn disk_users(path: &Path) -> crate::Result<Vec<DiskUser>> {
+    let block_path = disk_of(path)?;
+    let mountinfo = fs::read_to_string(MOUNTINFO)?;
+
+    Ok(disk_users_in(&block_path, &mountinfo))
+}
+
+/// Fails with [`crate::Error::InUse`] if any partition of the disk is mounted, held by another
+/// block device, or the disk is opened exclusively by another program.
+/// Check it before anything that destroys the data on the disk.
+pub fn check_not_in_use(path: &Path) -> crate::Result<()> {
+    claim_disk(path).map(drop)
+}
+
+/// Like `check_not_in_use`, but returns the disk node opened with O_EXCL,
+/// nobody can mount it or claim it exclusively while it's open.
+pub(crate) fn claim_disk(path: &Path) -> crate::Result<fs::File> {
+    use nix::libc;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let block_path = disk_of(path)?;
+    let mountinfo = fs::read_to_string(MOUNTINFO)?;
+    let users: Vec<_> = disk_users_in(&block_path, &mountinfo)
+        .iter()
+        .map(|user| user.to_string())
+        .collect();
+    if !users.is_empty() {
+        return Err(crate::Error::InUse(path.to_owned(), users.join(", ")));
+    }
+
+    let disk = Path::new(DEV_ROOT).join(block_path.file_name().unwrap_or_default());
+    fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_EXCL)
+        .open(&disk)
+        .map_err(|error| match error.raw_os_error() {
+            Some(libc::EBUSY) => crate::Error::InUse(
+                path.to_owned(),
+                format!(
+                    "{} is opened exclusively by another program",
+                    disk.display()
+                ),
+            ),
+            _ => error.into(),
+        })
+}
+
+fn disk_of(path: &Path) -> crate::Result<PathBuf> {
+    let (kind, major, minor) = device_number(path)?;
+
+    disk_block_path(Path::new(SYSFS_ROOT), kind, major, minor)
+        .ok_or_else(|| crate::Error::NotScsiDevice(path.to_owned()))
+}
+
+/// The kind of device node, "block" or "char", and its major and minor number.
+fn device_number(path: &Path) -> crate::Result<(&'static str, u32, u32)> {
+    use nix::libc::{major, minor};
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let metadata = fs::metadata(path)?;
+    let kind = match metadata.file_type() {
+        t if t.is_block_device() => "block",
+        t if t.is_char_device() => "char",
+        _ => return Err(crate::Error::NotBlockDevice(path.to_owned())),
+    };
+
+    let device = metadata.rdev();
+    Ok((kind, major(device), minor(device)))
+}
+
+fn disk_users_in(block_path: &Path, mountinfo: &str) -> Vec<DiskUser> {
+    let partitions = fs::read_dir(block_path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("partition").exists());
+    let mut block_paths: Vec<_> = std::iter::once(block_path.to_owned())
+        .chain(partitions)
+        .collect();
+    block_paths.sort();
+
+    let mut users = vec![];
+    for block_path in block_paths {
+        let device = Path::new(DEV_ROOT).join(block_path.file_name().unwrap_or_default());
+        let dev = read_attribute(&block_path.join("dev"));
+
+        for mount in mountinfo.lines().filter_map(Mount::parse) {
+            // btrfs reports an anonymous device number, but still names the source
+            if Some(mount.dev) == dev.as_deref() || Path::new(mount.source) == device {
+                users.push(DiskUser::Mounted {
+                    device: device.clone(),
+                    mount_point: PathBuf::from(unescape(mount.mount_point)),
+                });
+            }
+        }
+
+        let mut holders: Vec<_> = fs::read_dir(block_path.join("holders"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        holders.sort();
+        users.extend(holders.into_iter().map(|holder| DiskUser::Held {
+            device: device.clone(),
+            holder,
+        }));
+    }
+
+    users
+}
+
+/// The parts of a /proc/self/mountinfo line the guard needs.
+struct Mount<'a> {
+    /// major:minor
+    dev: &'a str,
+    mount_point: &'a str,
+    source: &'a str,
+}
+
+impl<'a> Mount<'a> {
+    fn parse(line: &'a str) -> Option<Mount<'a>> {
+        let fields: Vec<_> = line.split(' ').collect();
+        // Optional fields end with a lone dash, then come the file system type and the source
+        let separator = fields.iter().position(|field| *field == "-")?;
+
+        Some(Mount {
+            dev: fields.get(2)?,
+            mount_point: fields.get(4)?,
+            source: fields.get(separator + 2)?,
+        })
+    }
+}
+
+/// Spaces, tabs, newlines and backslashes in mountinfo are escaped as octal, e.g. \040.
+fn unescape(field: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let octal = tail
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match (byte, octal) {
+            (b'\\', Some(value)) => {
+                bytes.push(value);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn read_sg_path(device_path: &Path) -> Option<PathBuf> {
+    fs::read_dir(device_path.join("scsi_generic"))
+        .ok()?
+        .next()?
+        .ok()
+        .map(|entry| Path::new(DEV_ROOT).join(entry.file_name()))
+}
+
+fn read_bsg_path(device_path: &Path) -> Option<PathBuf> {
+    fs::read_dir(device_path.join("bsg"))
+        .ok()?
+        .next()?
+        .ok()
+        .map(|entry| Path::new(DEV_ROOT).join("bsg").join(entry.file_name()))
+}
+
+fn read_disk(block_path: &Path, name: &str) -> crate::Result<DiskInfo> {
+    let device_path = block_path.join("device").canonicalize()?;
+
+    let sg_path = read_sg_path(&device_path);
+
+    // The interface the driver binds to sits above the SCSI host, the USB device above that
+    let interface_path = device_path
+        .ancestors()
+        .find(|path| path.join("bInterfaceNumber").exists());
+    let usb_device_path = device_path
+        .ancestors()
+        .find(|path| path.join("idVendor").exists());
+
+    let driver = interface_path
+        .and_then(|path| fs::read_link(path.join("driver")).ok())
+        .and_then(|driver| {
+            driver
+                .file_name()
+                .map(|name| UsbDriver::from_name(&name.to_string_lossy()))
+        });
+
+    let usb_id = |file: &str| {
+        usb_device_path
+            .and_then(|path| read_attribute(&path.join(file)))
+            .and_then(|id| u16::from_str_radix(&id, 16).ok())
+    };
+
+    let serial = usb_device_path
+        .and_then(|path| read_attribute(&path.join("serial")))
+        .or_else(|| read_unit_serial_number(&device_path.join(UNIT_SERIAL_NUMBER_VPD_FILE)));
+
+    let mut partitions: Vec<_> = fs::read_dir(block_path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("partition").exists())
+        .map(|entry| Path::new(DEV_ROOT).join(entry.file_name()))
+        .collect();
+    // sdb10 sorts after sdb9
+    partitions.sort_by_key(|path| (path.as_os_str().len(), path.clone()));
+
+    Ok(DiskInfo {
+        path: Path::new(DEV_ROOT).join(name),
+        sg_path,
+        vendor: read_attribute(&device_path.join("vendor")).unwrap_or_default(),
+        model: read_attribute(&device_path.join("model")).unwrap_or_default(),
+        revision: read_attribute(&device_path.join("rev")).unwrap_or_default(),
+        serial,
+        usb_vendor_id: usb_id("idVendor"),
+        usb_product_id: usb_id("idProduct"),
+        driver,
+        partitions,
+    })
+}
+
+/// Reads a sysfs text attribute, None if it's missing or empty.
+fn read_attribute(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+
+    match value.is_empty() {
+        true => None,
+        false => Some(value.to_owned()),
+    }
+}
+
+fn read_unit_serial_number(path: &Path) -> Option<String> {
+    let page = fs::read(path).ok()?;
+    let serial = String::from_utf8_lossy(page.get(VPD_HEADER_LENGTH..)?);
+    let serial = serial.trim_matches([' ', '\0']);
+
+    match serial.is_empty() {
+        true => None,
+        false => Some(serial.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// Lays out a USB disk the way the kernel does, with the block entry linking into devices.
+    fn usb_disk(root: &Path, name: &str, minor: u32, sg: &str, port: &str, driver: &str) {
+        let usb_device = root.join("devices/pci0000:00/0000:00:14.0/usb2").join(port);
+        let interface = usb_device.join(format!("{}:1.0", port));
+        let scsi_device = interface.join("host6/target6:0:0/6:0:0:0");
+        let block = scsi_device.join("block").join(name);
+        fs::create_dir_all(root.join("dev/block")).unwrap();
+
+        write(&usb_device.join("idVendor"), "1058\n");
+        write(&usb_device.join("idProduct"), "25e2\n");
+        write(&usb_device.join("serial"), "575834314135\n");
+        write(&interface.join("bInterfaceNumber"), "00\n");
+        fs::create_dir_all(root.join("bus/usb/drivers").join(driver)).unwrap();
+        symlink(
+            root.join("bus/usb/drivers").join(driver),
+            interface.join("driver"),
+        )
+        .unwrap();
+
+        write(&scsi_device.join("vendor"), "WD      \n");
+        write(&scsi_device.join("model"), "My Passport 25E2\n");
+        write(&scsi_device.join("rev"), "4004\n");
+        let sg_minor = &sg["sg".len()..];
+        let sg_device = scsi_device.join("scsi_generic").join(sg);
+        let bsg_device = scsi_device.join("bsg/6:0:0:0");
+        fs::create_dir_all(root.join("dev/char")).unwrap();
+        for (class, device, major) in [("scsi_generic", &sg_device, 21), ("bsg", &bsg_device, 254)]
+        {
+            fs::create_dir_all(device).unwrap();
+            fs::create_dir_all(root.join("class").join(class)).unwrap();
+            symlink(&scsi_device, device.join("device")).unwrap();
+            symlink(root.join("class").join(class), device.join("subsystem")).unwrap();
+            symlink(
+                device,
+                root.join(format!("dev/char/{}:{}", major, sg_minor)),
+            )
+            .unwrap();
+        }
+
+        write(&block.join("dev"), &format!("8:{}\n", minor));
+        symlink(&scsi_device, block.join("device")).unwrap();
+        for partition in [1, 2, 10] {
+            let partition_path = block.join(format!("{}{}", name, partition));
+            write(
+                &partition_path.join("partition"),
+                &format!("{}\n", partition),
+            );
+            write(
+                &partition_path.join("dev"),
+                &format!("8:{}\n", minor + partition),
+            );
+            symlink(
+                block.join(format!("{}{}", name, partition)),
+                root.join(format!("dev/block/8:{}", minor + partition)),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(root.join("block")).unwrap();
+        symlink(&block, root.join("block").join(name)).unwrap();
+        symlink(&block, root.join(format!("dev/block/8:{}", minor))).unwrap();
+    }
+
+    #[test]
+    fn list_usb_disks() {
+        let root = std::env::temp_dir().join(format!("libscsi_sysfs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        usb_disk(&root, "sdc", 32, "sg3", "2-2", "usb-storage");
+        usb_disk(&root, "sdb", 16, "sg2", "2-1", "uas");
+        // not a SCSI disk
+        fs::create_dir_all(root.join("devices/virtual/block/loop0")).unwrap();
+        symlink(
+            root.join("devices/virtual/block/loop0"),
+            root.join("block/loop0"),
+        )
+        .unwrap();
+
+        let disks = list_disks_in(&root).unwrap();
+        let sg_paths = [16, 17, 26, 32, 48].map(|minor| sg_path_in(&root, 8, minor));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(disks.len(), 2);
+        let disk = &disks[0];
+        assert_eq!(disk.path, Path::new("/dev/sdb"));
+        assert_eq!(disk.sg_path.as_deref(), Some(Path::new("/dev/sg2")));
+        assert_eq!(disk.vendor, "WD");
+        assert_eq!(disk.model, "My Passport 25E2");
+        assert_eq!(disk.revision, "4004");
+        assert_eq!(disk.serial.as_deref(), Some("575834314135"));
+        assert_eq!(disk.usb_vendor_id, Some(0x1058));
+        assert_eq!(disk.usb_product_id, Some(0x25e2));
+        assert_eq!(disk.driver, Some(UsbDriver::Uas));
+        assert_eq!(
+            disk.partitions,
+            ["/dev/sdb1", "/dev/sdb2", "/dev/sdb10"].map(PathBuf::from)
+        );
+        assert_eq!(disks[1].path, Path::new("/dev/sdc"));
+        assert_eq!(disks[1].driver, Some(UsbDriver::UsbStorage));
+
+        // sdb, sdb1, sdb10, sdc and nothing
+        assert_eq!(
+            sg_paths,
+            [
+                Some("/dev/sg2"),
+                Some("/dev/sg2"),
+                Some("/dev/sg2"),
+                Some("/dev/sg3"),
+                None
+            ]
+            .map(|p| p.map(PathBuf::from))
+        );
+    }
+
+    #[test]
+    fn disk_in_use() {
+        let root = std::env::temp_dir().join(format!("libscsi_in_use_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        usb_disk(&root, "sdb", 16, "sg2", "2-1", "uas");
+        usb_disk(&root, "sdc", 32, "sg3", "2-2", "usb-storage");
+        let sdb = disk_block_path(&root, "char", 21, 2).unwrap();
+        fs::create_dir_all(sdb.join("sdb2/holders/dm-0")).unwrap();
+
+        let mountinfo = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+95 22 8:17 / /mnt/backup\\040job rw,nosuid shared:48 - ext4 /dev/sdb1 rw
+96 22 0:52 / /mnt/btrfs rw,relatime - btrfs /dev/sdb10 rw
+97 22 8:33 /home /srv rw,relatime shared:50 master:3 - xfs /dev/sdc1 rw
+";
+        let sdb_users = disk_users_in(&sdb, mountinfo);
+        let bsg_disk = disk_block_path(&root, "char", 254, 3);
+        let bsg = [(254, 2), (21, 2), (8, 16)].map(|(major, minor)| is_bsg_in(&root, major, minor));
+        let bsg_path = read_bsg_path(&sdb.join("device"));
+        let sdc = disk_block_path(&root, "block", 8, 34).unwrap();
+        let sdc_users = disk_users_in(&sdc, mountinfo);
+        let unused = disk_users_in(&sdc, "22 1 259:2 / / rw - ext4 /dev/nvme0n1p2 rw\n");
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(sdb.file_name().unwrap(), "sdb");
+        assert_eq!(bsg_disk.unwrap().file_name().unwrap(), "sdc");
+        assert_eq!(bsg, [true, false, false]);
+        assert_eq!(bsg_path.as_deref(), Some(Path::new("/dev/bsg/6:0:0:0")));
+        assert_eq!(
+            sdb_users,
+            [
+                DiskUser::Mounted {
+                    device: PathBuf::from("/dev/sdb1"),
+                    mount_point: PathBuf::from("/mnt/backup job"),
+                },
+                DiskUser::Mounted {
+                    device: PathBuf::from("/dev/sdb10"),
+                    mount_point: PathBuf::from("/mnt/btrfs"),
+                },
+                DiskUser::Held {
+                    device: PathBuf::from("/dev/sdb2"),
+                    holder: "dm-0".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(sdb_users[2].to_string(), "/dev/sdb2 is used by dm-0");
+        assert_eq!(sdc_users.len(), 1);
+        assert_eq!(sdc_users[0].to_string(), "/dev/sdc1 is mounted on /srv");
+        assert!(unused.is_empty());
+    }
+
+    #[test]
+    fn sysfs_mapping() {
+        let root = std::env::temp_dir().join(format!("libscsi_mapping_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        usb_disk(&root, "sdb", 16, "sg2", "2-1", "uas");
+        let name = |path: Option<PathBuf>| {
+            path.and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+        };
+        // the disk, a partition, its sg node, its bsg node and nothing
+        let disks = [
+            ("block", 8, 16),
+            ("block", 8, 26),
+            ("char", 21, 2),
+            ("char", 254, 2),
+            ("block", 8, 48),
+        ]
+        .map(|(kind, major, minor)| name(disk_block_path(&root, kind, major, minor)));
+        let sg_paths = [("block", 8, 17), ("char", 254, 2)]
+            .map(|(kind, major, minor)| disk_block_path(&root, kind, major, minor))
+            .map(|block_path| read_sg_path(&block_path.unwrap().join("device")));
+        let bsg_paths = [
+            ("block", 8, 16),
+            ("block", 8, 18),
+            ("char", 21, 2),
+            ("block", 8, 48),
+        ]
+        .map(|(kind, major, minor)| bsg_path_in(&root, kind, major, minor));
+        let bsg = [(254, 2), (21, 2), (8, 16), (254, 9)]
+            .map(|(major, minor)| is_bsg_in(&root, major, minor));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            disks,
+            [Some("sdb"), Some("sdb"), Some("sdb"), Some("sdb"), None]
+                .map(|n| n.map(str::to_owned))
+        );
+        assert_eq!(
+            sg_paths,
+            [Some("/dev/sg2"); 2].map(|p| p.map(PathBuf::from))
+        );
+        assert_eq!(
+            bsg_paths,
+            [
+                Some("/dev/bsg/6:0:0:0"),
+                Some("/dev/bsg/6:0:0:0"),
+                Some("/dev/bsg/6:0:0:0"),
+                None
+            ]
+            .map(|p| p.map(PathBuf::from))
+        );
+        assert_eq!(bsg, [true, false, false, false]);
+    }
+
+    #[test]
+    fn parse_mountinfo() {
+        let mount =
+            Mount::parse("97 22 8:33 /home /srv rw,relatime shared:50 master:3 - xfs /dev/sdc1 rw")
+                .unwrap();
+        assert_eq!(
+            (mount.dev, mount.mount_point, mount.source),
+            ("8:33", "/srv", "/dev/sdc1")
+        );
+
+        // no optional fields
+        let mount =
+            Mount::parse("96 22 0:52 / /mnt/btrfs rw,relatime - btrfs /dev/sdb10 rw").unwrap();
+        assert_eq!(
+            (mount.dev, mount.mount_point, mount.source),
+            ("0:52", "/mnt/btrfs", "/dev/sdb10")
+        );
+
+        assert!(Mount::parse("96 22 0:52 / /mnt rw,relatime btrfs /dev/sdb10 rw").is_none());
+        assert!(Mount::parse("96 22 0:52 / /mnt rw -").is_none());
+        assert!(Mount::parse("").is_none());
+
+        assert_eq!(unescape("/mnt/backup\\040job"), "/mnt/backup job");
+        assert_eq!(unescape("a\\011b\\012c\\134d"), "a\tb\nc\\d");
+        // not an escape
+        assert_eq!(unescape("a\\9zz\\04"), "a\\9zz\\04");
+    }
+}
@@ -1,17 +1,31 @@
-use std::{any::Any, io, path::PathBuf};
+use std::{io, path::PathBuf};
 
 use thiserror::Error;
 
+use crate::{command::AtaRegisters, SenseData};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
-pub enum Error<T = Box<dyn Any>> {
+pub enum Error {
     #[error("{0} is not a block device.")]
     NotBlockDevice(PathBuf),
     #[error("{0} is not an SCSI Generic device, or old SCSI Generic driver.")]
     NotScsiDevice(PathBuf),
-    #[error("Check condition: {0:?}")]
-    CheckCondition(T),
+    #[error("Check condition: {0}")]
+    CheckCondition(SenseData),
+    #[error("ATA command failed, {0}")]
+    Ata(AtaRegisters),
+    #[error("{0} is in use, {1}.")]
+    InUse(PathBuf, String),
+    #[error("Can't tell whether {0} is in use on this system.")]
+    InUseUnknown(PathBuf),
+    #[error("Erasing with {0} isn't supported by this device, writing zeros to every block has to be allowed.")]
+    EraseNotSupported(String),
+    #[error("{0} can't be changed on this device.")]
+    NotChangeable(String),
+    #[error("{0} can't be saved on this device.")]
+    NotSavable(String),
     #[error("{0:?}")]
     Other(String),
     #[error("{0}")]
@@ -36,6 +36,24 @@ impl FileDescriptor {
         Ok(file_type.is_block_device())
     }
 
+    #[cfg(target_os = "linux")]
+    pub fn is_char(&self) -> crate::Result<bool> {
+        use std::os::unix::prelude::FileTypeExt;
+
+        let file_type = self.file.metadata()?.file_type();
+        Ok(file_type.is_char_device())
+    }
+
+    /// (major, minor) of a device file
+    #[cfg(target_os = "linux")]
+    pub fn device_number(&self) -> crate::Result<(u32, u32)> {
+        use nix::libc::{major, minor};
+        use std::os::unix::fs::MetadataExt;
+
+        let device = self.file.metadata()?.rdev();
+        Ok((major(device), minor(device)))
+    }
+
     #[cfg(target_os = "windows")]
     pub fn is_block(&self) -> crate::Result<bool> {
         use std::io;
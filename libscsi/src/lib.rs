@@ -2,6 +2,8 @@ mod access_flag;
 mod auxiliary_info;
 pub mod command;
 mod data_direction;
+#[cfg(target_os = "linux")]
+pub mod discovery;
 mod driver_status;
 mod error;
 mod file_descriptor;
@@ -9,7 +11,10 @@ mod host_status;
 mod masked_status;
 mod os;
 mod result_data;
+mod retry;
 mod scsi;
+mod sense_data;
+mod transport;
 
 pub use access_flag::AccessFlags;
 pub use auxiliary_info::AuxiliaryInfo;
@@ -20,4 +25,12 @@ pub use error::{Error, Result};
 pub use host_status::HostStatus;
 pub use masked_status::MaskedStatus;
 pub use result_data::ResultData;
-pub use scsi::Scsi;
+pub use retry::{is_retryable, RetryPolicy};
+pub use scsi::{ExclusiveClaim, Scsi};
+pub use sense_data::{additional_sense_description, SenseData, SenseKey};
+pub use transport::{
+    NativeTransport, ReplayTransport, TraceRecord, Transport, TransportRequest, TransportResult,
+    VectoredRequest,
+};
+#[cfg(target_os = "linux")]
+pub use transport::{QueuedRequest, QueuedResponse, QueuedTransport, ResponseFuture};
@@ -1,5 +1,7 @@
 #[cfg(target_os = "linux")]
 pub mod sg_io_header;
+#[cfg(target_os = "linux")]
+pub mod sg_io_v4_header;
 
 #[cfg(target_os = "windows")]
 pub mod scsi_pass_through_header;
@@ -1,6 +1,10 @@
+use std::{ops::Deref, time::Duration};
+
 use nix::libc::{c_int, c_uchar, c_uint, c_ushort};
 
-use crate::{AccessFlags, AuxiliaryInfo, DriverStatus, MaskedStatus};
+use crate::{
+    AccessFlags, AuxiliaryInfo, DataDirection, DriverStatus, MaskedStatus, TransportResult,
+};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -51,6 +55,114 @@ pub struct SgIoHeader<'a, C, D, S> {
     pub info: AuxiliaryInfo,
 }
 
+impl<'a> SgIoHeader<'a, u8, u8, u8> {
+    /// A request with every output field cleared.
+    pub fn new(
+        direction: DataDirection,
+        command: &'a [u8],
+        data: &'a mut [u8],
+        sense: &'a mut [u8],
+        timeout: Duration,
+        pack_id: i32,
+    ) -> Self {
+        let data_length = data.len() as u32;
+
+        SgIoHeader::with_data(
+            direction,
+            command,
+            data.first_mut(),
+            0,
+            data_length,
+            sense,
+            timeout,
+            pack_id,
+        )
+    }
+}
+
+impl<'a, D: Deref<Target = [u8]>> SgIoHeader<'a, u8, D, u8> {
+    /// Scatter gather, the buffers in `iovecs` are sent or filled one after another.
+    /// `D` is `IoSlice` or `IoSliceMut`, both are laid out like sg_iovec_t.
+    /// The kernel takes at most UIO_MAXIOV (1024) of them.
+    pub fn with_iovecs(
+        direction: DataDirection,
+        command: &'a [u8],
+        iovecs: &'a mut [D],
+        sense: &'a mut [u8],
+        timeout: Duration,
+        pack_id: i32,
+    ) -> Self {
+        let iovec_count = iovecs.len() as c_ushort;
+        let data_length = iovecs.iter().map(|iovec| iovec.len() as u32).sum();
+
+        SgIoHeader::with_data(
+            direction,
+            command,
+            iovecs.first_mut(),
+            iovec_count,
+            data_length,
+            sense,
+            timeout,
+            pack_id,
+        )
+    }
+}
+
+impl<'a, C, D, S> SgIoHeader<'a, C, D, S> {
+    #[allow(clippy::too_many_arguments)]
+    fn with_data(
+        direction: DataDirection,
+        command: &'a [C],
+        data: Option<&'a mut D>,
+        iovec_count: c_ushort,
+        data_length: u32,
+        sense: &'a mut [S],
+        timeout: Duration,
+        pack_id: i32,
+    ) -> Self {
+        SgIoHeader {
+            interface_id: b'S' as i32,
+            data_direction: direction.into(),
+            command_length: command.len() as u8,
+            max_sense_buffer_length: sense.len() as u8,
+            iovec_count,
+            data_length,
+            data,
+            command: command.first(),
+            sense_buffer: sense.first_mut(),
+            timeout: timeout
+                .as_millis()
+                .clamp(u32::MIN as u128, u32::MAX as u128) as u32,
+            flags: AccessFlags::DEFAULT,
+            pack_id,
+            user_pointer: 0,
+            status: 0,
+            masked_status: MaskedStatus::GOOD,
+            message_status: 0,
+            sense_buffer_written: 0,
+            host_status: 0,
+            driver_status: DriverStatus::OK,
+            residual_count: 0,
+            duration: 0,
+            info: AuxiliaryInfo::OK,
+        }
+    }
+
+    /// `ioctl_result` is 0 or a negated os error code.
+    pub fn transport_result(&self, ioctl_result: i32) -> TransportResult {
+        TransportResult {
+            ioctl_result,
+            transfered_data_length: (self.data_length as usize)
+                .saturating_sub(self.residual_count.max(0) as usize),
+            transfered_sense_length: self.sense_buffer_written as usize,
+            masked_status: self.masked_status,
+            host_status: self.host_status.into(),
+            driver_status: self.driver_status,
+            duration: Duration::from_millis(self.duration as u64),
+        }
+    }
+}
+
 #[allow(deref_nullptr)]
 #[cfg(test)]
 mod tests {
@@ -530,4 +642,42 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn iovecs() {
+        use std::io::IoSliceMut;
+        use std::mem::{align_of, size_of};
+        use std::time::Duration;
+
+        use nix::libc::iovec;
+
+        use crate::DataDirection;
+
+        // sg_iovec_t is the same as struct iovec
+        assert_eq!(size_of::<IoSliceMut>(), size_of::<iovec>());
+        assert_eq!(align_of::<IoSliceMut>(), align_of::<iovec>());
+
+        let command = [0x88; 16];
+        let mut sense = [0; 32];
+        let mut first = [0; 512];
+        let mut second = [0; 1024];
+        let mut iovecs = [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)];
+        let iovecs_pointer = iovecs.as_ptr();
+
+        let header = SgIoHeaderGeneric::with_iovecs(
+            DataDirection::FromDevice,
+            &command,
+            &mut iovecs,
+            &mut sense,
+            Duration::from_secs(1),
+            0,
+        );
+        assert_eq!(header.iovec_count, 2);
+        assert_eq!(header.data_length, 1536);
+        assert_eq!(header.command_length, 16);
+        assert_eq!(
+            header.data.map(|data| data as *const _),
+            Some(iovecs_pointer)
+        );
+    }
 }
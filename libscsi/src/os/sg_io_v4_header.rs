@@ -0,0 +1,315 @@
+use std::{marker::PhantomData, time::Duration};
+
+use nix::libc::{c_int, c_uint};
+
+use crate::{DataDirection, DriverStatus, MaskedStatus, TransportResult};
+
+/// struct sg_io_v4 from linux/bsg.h, the only request SG_IO takes on bsg nodes (/dev/bsg/H:C:T:L).
+/// The data is always passed flat, bsg doesn't look at the iovec counts and would write
+/// the response over an iovec list.
+/// It has a buffer for each direction, so a command can send and receive data at once,
+/// but kernels since 5.1 refuse to use both and fail such requests with EOPNOTSUPP.
+/// Pointers are always 64 bit, `'a` keeps the buffers they point to borrowed.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SgIoV4Header<'a> {
+    /// \[i\] 'Q' to differentiate from v3
+    pub guard: c_int,
+    /// \[i\] 0 -> SCSI
+    pub protocol: c_uint,
+    /// \[i\] 0 -> SCSI command, 1 -> SCSI task management function
+    pub subprotocol: c_uint,
+    /// \[i\] in bytes
+    pub request_length: c_uint,
+    /// \[i\], \[*i\] {SCSI: cdb}
+    pub request: u64,
+    /// \[i\] {SCSI: task tag (only if flagged)}
+    pub request_tag: u64,
+    /// \[i\] {SCSI: task attribute}
+    pub request_attribute: c_uint,
+    /// \[i\] {SCSI: task priority}
+    pub request_priority: c_uint,
+    /// \[i\] {spare, for padding}
+    pub request_extra: c_uint,
+    /// \[i\] in bytes
+    pub max_response_length: c_uint,
+    /// \[i\], \[*o\] {SCSI: (auto)sense data}
+    pub response: u64,
+    /// \[i\] 0 -> "flat" dout transfer else dout_xfer points to array of iovec
+    pub data_out_iovec_count: c_uint,
+    /// \[i\] bytes to be transferred to device
+    pub data_out_length: c_uint,
+    /// \[i\] 0 -> "flat" din transfer
+    pub data_in_iovec_count: c_uint,
+    /// \[i\] bytes to be transferred from device
+    pub data_in_length: c_uint,
+    /// \[i\], \[*i\]
+    pub data_out: u64,
+    /// \[i\], \[*o\]
+    pub data_in: u64,
+    /// \[i\] units: millisecond
+    pub timeout: c_uint,
+    /// \[i\] bit mask
+    pub flags: c_uint,
+    /// \[i->o\] unused internally
+    pub user_pointer: u64,
+    /// \[i\]
+    pub spare_in: c_uint,
+    /// \[o\] 0 -> ok
+    pub driver_status: c_uint,
+    /// \[o\] 0 -> ok
+    pub transport_status: c_uint,
+    /// \[o\] {SCSI: command completion status}
+    pub device_status: c_uint,
+    /// \[o\] {SCSI: status auxiliary information}
+    pub retry_delay: c_uint,
+    /// \[o\] additional information
+    pub info: c_uint,
+    /// \[o\] time to complete, in milliseconds
+    pub duration: c_uint,
+    /// \[o\] bytes of response actually written
+    pub response_length: c_uint,
+    /// \[o\] din_xfer_len - actual_din_xfer_len
+    pub data_in_residual_count: c_int,
+    /// \[o\] dout_xfer_len - actual_dout_xfer_len
+    pub data_out_residual_count: c_int,
+    /// \[o\] {SCSI: transport generated task tag}
+    pub generated_tag: u64,
+    /// \[o\]
+    pub spare_out: c_uint,
+    pub padding: c_uint,
+    buffers: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> SgIoV4Header<'a> {
+    /// A request with every output field cleared.
+    /// `data` is sent or filled depending on `direction`.
+    pub fn new(
+        direction: DataDirection,
+        command: &'a [u8],
+        data: &'a mut [u8],
+        sense: &'a mut [u8],
+        timeout: Duration,
+    ) -> Self {
+        let data_length = data.len() as c_uint;
+        let data_pointer = data.as_mut_ptr() as usize as u64;
+        let (data_out, data_in) = match direction {
+            DataDirection::ToDevice => ((data_length, data_pointer), (0, 0)),
+            DataDirection::FromDevice | DataDirection::ToFromDevice => {
+                ((0, 0), (data_length, data_pointer))
+            }
+            _ => ((0, 0), (0, 0)),
+        };
+
+        Self::with_buffers(command, data_out, data_in, sense, timeout)
+    }
+
+    /// Like `new`, but sends `data_out` and fills `data_in` with the same command.
+    pub fn bidirectional(
+        command: &'a [u8],
+        data_out: &'a [u8],
+        data_in: &'a mut [u8],
+        sense: &'a mut [u8],
+        timeout: Duration,
+    ) -> Self {
+        Self::with_buffers(
+            command,
+            (data_out.len() as c_uint, data_out.as_ptr() as usize as u64),
+            (
+                data_in.len() as c_uint,
+                data_in.as_mut_ptr() as usize as u64,
+            ),
+            sense,
+            timeout,
+        )
+    }
+
+    /// `data_out` and `data_in` are (length, pointer) of flat buffers.
+    fn with_buffers(
+        command: &'a [u8],
+        (data_out_length, data_out_pointer): (c_uint, u64),
+        (data_in_length, data_in_pointer): (c_uint, u64),
+        sense: &'a mut [u8],
+        timeout: Duration,
+    ) -> Self {
+        SgIoV4Header {
+            guard: b'Q' as c_int,
+            protocol: 0,
+            subprotocol: 0,
+            request_length: command.len() as c_uint,
+            request: command.as_ptr() as usize as u64,
+            request_tag: 0,
+            request_attribute: 0,
+            request_priority: 0,
+            request_extra: 0,
+            max_response_length: sense.len() as c_uint,
+            response: sense.as_mut_ptr() as usize as u64,
+            data_out_iovec_count: 0,
+            data_out_length,
+            data_in_iovec_count: 0,
+            data_in_length,
+            data_out: data_out_pointer,
+            data_in: data_in_pointer,
+            timeout: timeout
+                .as_millis()
+                .clamp(u32::MIN as u128, u32::MAX as u128) as u32,
+            flags: 0,
+            user_pointer: 0,
+            spare_in: 0,
+            driver_status: 0,
+            transport_status: 0,
+            device_status: 0,
+            retry_delay: 0,
+            info: 0,
+            duration: 0,
+            response_length: 0,
+            data_in_residual_count: 0,
+            data_out_residual_count: 0,
+            generated_tag: 0,
+            spare_out: 0,
+            padding: 0,
+            buffers: PhantomData,
+        }
+    }
+
+    /// `ioctl_result` is 0 or a negated os error code.
+    /// The data length is the one received if the command has data in, the one sent otherwise.
+    pub fn transport_result(&self, ioctl_result: i32) -> TransportResult {
+        let (length, residual_count) = match self.data_in_length {
+            0 => (self.data_out_length, self.data_out_residual_count),
+            length => (length, self.data_in_residual_count),
+        };
+
+        TransportResult {
+            ioctl_result,
+            transfered_data_length: (length as usize)
+                .saturating_sub(residual_count.max(0) as usize),
+            transfered_sense_length: self.response_length as usize,
+            // v3 has the status shifted, v4 the full one
+            masked_status: MaskedStatus::from_bits_truncate(
+                ((self.device_status & 0x3e) >> 1) as u8,
+            ),
+            host_status: (self.transport_status as u16).into(),
+            driver_status: DriverStatus::from_bits_truncate(self.driver_status as u16),
+            duration: Duration::from_millis(self.duration as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{align_of, size_of, MaybeUninit};
+
+    use super::*;
+
+    macro_rules! assert_offset {
+        ($field:ident, $offset:expr) => {{
+            const UNINIT: MaybeUninit<SgIoV4Header> = MaybeUninit::uninit();
+            let ptr = UNINIT.as_ptr();
+            assert_eq!(
+                unsafe { std::ptr::addr_of!((*ptr).$field) as usize - ptr as usize },
+                $offset,
+                concat!("Offset of field: SgIoV4Header::", stringify!($field))
+            );
+        }};
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    fn layout() {
+        assert_eq!(size_of::<SgIoV4Header>(), 160);
+        assert_eq!(align_of::<SgIoV4Header>(), align_of::<u64>());
+        assert_offset!(request, 16);
+        assert_offset!(max_response_length, 44);
+        assert_offset!(response, 48);
+        assert_offset!(data_out_iovec_count, 56);
+        assert_offset!(data_in_length, 68);
+        assert_offset!(data_out, 72);
+        assert_offset!(data_in, 80);
+        assert_offset!(timeout, 88);
+        assert_offset!(user_pointer, 96);
+        assert_offset!(driver_status, 108);
+        assert_offset!(device_status, 116);
+        assert_offset!(response_length, 132);
+        assert_offset!(data_in_residual_count, 136);
+        assert_offset!(generated_tag, 144);
+        assert_offset!(padding, 156);
+    }
+
+    #[test]
+    fn flat_data() {
+        let command = [0x28_u8; 10];
+        let mut data = [0_u8; 4096];
+        let data_pointer = data.as_ptr() as usize as u64;
+        let mut sense = [0_u8; 32];
+
+        let mut sg_header = SgIoV4Header::new(
+            DataDirection::FromDevice,
+            &command,
+            &mut data,
+            &mut sense,
+            Duration::from_secs(3),
+        );
+        assert_eq!(sg_header.guard, b'Q' as c_int);
+        assert_eq!(sg_header.request_length, 10);
+        assert_eq!(sg_header.max_response_length, 32);
+        assert_eq!(sg_header.data_in_iovec_count, 0);
+        assert_eq!(sg_header.data_in, data_pointer);
+        assert_eq!(sg_header.data_in_length, 4096);
+        assert_eq!((sg_header.data_out, sg_header.data_out_length), (0, 0));
+        assert_eq!(sg_header.timeout, 3000);
+
+        sg_header.data_in_residual_count = 512;
+        sg_header.device_status = 0x02;
+        sg_header.response_length = 18;
+        let result = sg_header.transport_result(0);
+        assert_eq!(result.transfered_data_length, 3584);
+        assert_eq!(result.transfered_sense_length, 18);
+        assert_eq!(result.masked_status, MaskedStatus::CHECK_CONDITION);
+
+        let mut data = [0_u8; 24];
+        let sg_header = SgIoV4Header::new(
+            DataDirection::ToDevice,
+            &command,
+            &mut data,
+            &mut sense,
+            Duration::from_secs(3),
+        );
+        assert_eq!(sg_header.data_out_iovec_count, 0);
+        assert_eq!(sg_header.data_out_length, 24);
+        assert_eq!(sg_header.data_in_length, 0);
+    }
+
+    #[test]
+    fn bidirectional() {
+        let command = [0x7f_u8; 32];
+        let parameter_list = [1_u8; 24];
+        let mut data = [0_u8; 4096];
+        let data_pointer = data.as_ptr() as usize as u64;
+        let mut sense = [0_u8; 32];
+
+        let mut sg_header = SgIoV4Header::bidirectional(
+            &command,
+            &parameter_list,
+            &mut data,
+            &mut sense,
+            Duration::from_secs(3),
+        );
+        assert_eq!(sg_header.request_length, 32);
+        assert_eq!(
+            (
+                sg_header.data_out_iovec_count,
+                sg_header.data_in_iovec_count
+            ),
+            (0, 0)
+        );
+        assert_eq!(sg_header.data_out, parameter_list.as_ptr() as usize as u64);
+        assert_eq!(sg_header.data_out_length, 24);
+        assert_eq!(sg_header.data_in, data_pointer);
+        assert_eq!(sg_header.data_in_length, 4096);
+
+        // the received length counts
+        sg_header.data_in_residual_count = 96;
+        assert_eq!(sg_header.transport_result(0).transfered_data_length, 4000);
+    }
+}
@@ -1,8 +1,9 @@
-use std::io;
+use std::{io, time::Duration};
 
-use crate::{command::sense::Sense, error, DriverStatus, HostStatus, MaskedStatus};
+use crate::{command::sense::Sense, error, DriverStatus, HostStatus, MaskedStatus, SenseData};
 
-pub struct ResultData<'a, D, S> {
+pub struct ResultData<'a, D: ?Sized, S> {
+    /// 0 on success, otherwise a negated os error code
     pub ioctl_result: i32,
     pub transfered_data_length: usize,
     pub data: Option<&'a mut D>,
@@ -14,13 +15,30 @@ pub struct ResultData<'a, D, S> {
     pub host_status: HostStatus,
     /// Linux only
     pub driver_status: DriverStatus,
+    /// Time taken by the command, zero if the transport can't tell
+    pub duration: Duration,
 }
 
 impl<D, S> ResultData<'_, D, S>
 where
+    D: ?Sized,
     S: Sense,
 {
+    /// Decoded sense data, `None` if the device returned none or in an unknown format.
+    pub fn sense_data(&self) -> Option<SenseData> {
+        if self.transfered_sense_length == 0 {
+            return None;
+        }
+
+        let sense = self.sense_buffer.as_ref()?.as_byte_slice();
+        SenseData::parse(&sense[..usize::min(self.transfered_sense_length, sense.len())])
+    }
+
     pub fn check_common_error(&self) -> crate::Result<()> {
+        if let Some(sense_data) = self.sense_data() {
+            return Err(crate::Error::CheckCondition(sense_data));
+        }
+
         let mut result = String::new();
 
         if !self.masked_status.is_empty() {
@@ -50,11 +68,21 @@ where
     }
 }
 
-impl<D, S> ResultData<'_, D, S> {
+impl<D: ?Sized, S> ResultData<'_, D, S> {
     pub fn check_ioctl_error(&self) -> error::Result<()> {
         match self.ioctl_result {
             0 => Ok(()),
-            _ => Err(error::Error::IO(io::Error::last_os_error())),
+            n => Err(error::Error::IO(io::Error::from_raw_os_error(-n))),
+        }
+    }
+}
+
+impl<S> ResultData<'_, [u8], S> {
+    /// The part of a runtime sized data buffer the device filled in.
+    pub fn transfered_data(&self) -> &[u8] {
+        match &self.data {
+            Some(data) => &data[..usize::min(self.transfered_data_length, data.len())],
+            None => &[],
         }
     }
 }
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use crate::{DriverStatus, HostStatus, MaskedStatus, SenseData, TransportResult};
+
+/// How often and how patiently `Scsi::execute_command` resends a command that failed for a reason
+/// that goes away by itself, see [`is_retryable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Including the first one, 1 means no retries
+    pub max_attempts: u32,
+    /// Before the first retry, doubled before each following one
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Every result is handed to the command as it is.
+    pub const NEVER: RetryPolicy = RetryPolicy {
+        max_attempts: 1,
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    };
+
+    /// Delay before attempt number `attempt` (counted from 1), `None` if there are no attempts left.
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt < 2 || attempt > self.max_attempts {
+            return None;
+        }
+
+        let factor = 1u32.checked_shl(attempt - 2).unwrap_or(u32::MAX);
+        Some(
+            self.initial_delay
+                .saturating_mul(factor)
+                .min(self.max_delay),
+        )
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Rides out a drive spinning up after it was plugged in, about 5 seconds in total.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 6,
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Whether the command may succeed if it's sent again, anything else is fatal:
+/// - UNIT ATTENTION after a power on or reset, it's reported once
+/// - NOT READY while the logical unit is becoming ready
+/// - BUSY and TASK SET FULL statuses
+/// - bus busy and immediate retries of the host adapter
+/// - its time outs, soft errors and requeues, if the command is `retry_safe`,
+///   see `Command::is_retry_safe`
+/// - soft errors and retry suggestions of the driver
+pub fn is_retryable(result: &TransportResult, sense: Option<&SenseData>, retry_safe: bool) -> bool {
+    if result.ioctl_result != 0 {
+        return false;
+    }
+
+    if let Some(sense) = sense {
+        return sense.is_power_on_or_reset() || sense.is_becoming_ready();
+    }
+
+    if result.masked_status == MaskedStatus::BUSY
+        || result.masked_status == MaskedStatus::QUEUE_FULL
+    {
+        return true;
+    }
+
+    if matches!(
+        result.host_status,
+        HostStatus::BusBusy | HostStatus::ImmediateRetry
+    ) {
+        return true;
+    }
+
+    // the command may have reached the device before the host gave up on it
+    if matches!(
+        result.host_status,
+        HostStatus::TimeOut | HostStatus::SoftError | HostStatus::Requeue
+    ) {
+        return retry_safe;
+    }
+
+    let status = result.driver_status.bits() & DRIVER_STATUS_MASK;
+    let suggestion = result.driver_status.bits() & DRIVER_SUGGESTION_MASK;
+    status == DriverStatus::BUSY.bits()
+        || status == DriverStatus::SOFT.bits()
+        || suggestion == DriverStatus::RETRY.bits()
+}
+
+/// The low nibble of the driver status is a status, the high one a suggestion
+const DRIVER_STATUS_MASK: u16 = 0x0f;
+const DRIVER_SUGGESTION_MASK: u16 = 0xf0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataDirection, ReplayTransport, Scsi, TraceRecord};
+
+    fn sense(key: u8, asc: u8, ascq: u8) -> SenseData {
+        let mut bytes = [0u8; 18];
+        bytes[0] = 0x70;
+        bytes[2] = key;
+        bytes[7] = 10;
+        bytes[12] = asc;
+        bytes[13] = ascq;
+        SenseData::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn classify() {
+        let good = TransportResult::good(0);
+        assert!(!is_retryable(&good, None, false));
+        assert!(is_retryable(&good, Some(&sense(0x06, 0x29, 0x00)), false));
+        assert!(is_retryable(&good, Some(&sense(0x02, 0x04, 0x01)), false));
+        // parameters changed and a locked drive need the caller to step in
+        assert!(!is_retryable(&good, Some(&sense(0x06, 0x2a, 0x01)), false));
+        assert!(!is_retryable(&good, Some(&sense(0x07, 0x74, 0x71)), false));
+
+        let with = |f: fn(&mut TransportResult)| {
+            let mut result = TransportResult::good(0);
+            f(&mut result);
+            is_retryable(&result, None, false)
+        };
+        assert!(with(|r| r.masked_status = MaskedStatus::BUSY));
+        assert!(with(|r| r.host_status = HostStatus::BusBusy));
+        // writes, UNMAP and security commands may have been carried out already
+        assert!(!with(|r| r.host_status = HostStatus::TimeOut));
+        assert!(!with(|r| r.host_status = HostStatus::SoftError));
+        assert!(!with(|r| r.host_status = HostStatus::Requeue));
+        let mut timed_out = TransportResult::good(0);
+        timed_out.host_status = HostStatus::TimeOut;
+        assert!(is_retryable(&timed_out, None, true));
+        assert!(!with(|r| r.host_status = HostStatus::NoConnect));
+        assert!(with(|r| r.driver_status = DriverStatus::SOFT));
+        assert!(with(
+            |r| r.driver_status = DriverStatus::ERROR | DriverStatus::RETRY
+        ));
+        assert!(!with(|r| r.driver_status = DriverStatus::HARD));
+        assert!(!with(|r| r.ioctl_result = -5));
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<_> = (1..=7).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [
+                None,
+                Some(200),
+                Some(400),
+                Some(800),
+                Some(1600),
+                Some(2000),
+                None
+            ]
+            .map(|delay| delay.map(Duration::from_millis))
+        );
+        assert_eq!(RetryPolicy::NEVER.delay(2), None);
+        assert!(RetryPolicy {
+            max_attempts: u32::MAX,
+            ..policy
+        }
+        .delay(100)
+        .is_some());
+    }
+
+    #[test]
+    fn timed_out_writes_are_not_resent() {
+        let record = |command: [u8; 16], direction, host_status| TraceRecord {
+            command: command.to_vec(),
+            direction,
+            data_out: vec![],
+            data_in: vec![],
+            sense: vec![],
+            masked_status: MaskedStatus::empty(),
+            host_status,
+            driver_status: DriverStatus::empty(),
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        };
+        let write = [0x8a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0];
+        let read = [0x88, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0];
+        let mut device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                record(write, DataDirection::ToDevice, HostStatus::TimeOut),
+                record(read, DataDirection::FromDevice, HostStatus::TimeOut),
+                TraceRecord {
+                    data_in: vec![0x55; 512],
+                    ..record(read, DataDirection::FromDevice, HostStatus::Ok)
+                },
+            ]),
+        );
+        device.set_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+
+        assert!(device.write16(0, &[0xaa; 512], 512).is_err());
+        assert_eq!(device.transport().remaining(), 2);
+        let mut buffer = [0; 512];
+        device.read16(0, &mut buffer, 512).unwrap();
+        assert_eq!(buffer, [0x55; 512]);
+        assert_eq!(device.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn unit_attention_is_retried() {
+        let test_unit_ready = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let record =
+            |sense| TraceRecord::check_condition(&test_unit_ready, DataDirection::None, sense);
+        let mut device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                record((0x06, 0x29, 0x01)),
+                record((0x06, 0x29, 0x01)),
+                TraceRecord::good(&test_unit_ready, DataDirection::None, &[]),
+                record((0x07, 0x74, 0x71)),
+            ]),
+        );
+
+        let error = device.test_unit_ready();
+        assert!(
+            matches!(error, Err(crate::Error::CheckCondition(sense)) if sense.is_power_on_or_reset())
+        );
+
+        device.set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+        device.test_unit_ready().unwrap();
+
+        // a locked drive won't unlock itself
+        device.set_retry_policy(RetryPolicy::default());
+        let error = device.test_unit_ready();
+        assert!(matches!(error, Err(crate::Error::CheckCondition(sense)) if sense.is_locked()));
+        assert_eq!(device.transport().remaining(), 0);
+    }
+}
@@ -2,226 +2,369 @@
 
 use std::{
     borrow::BorrowMut,
-    fs::OpenOptions,
-    io,
-    mem::size_of_val,
+    fs::File,
+    io::{self, IoSlice, IoSliceMut, Write},
+    mem::{size_of, size_of_val},
     path::{Path, PathBuf},
+    slice,
+    sync::{Arc, Mutex, Weak},
+    thread,
     time::Duration,
 };
 
-use crate::{file_descriptor::FileDescriptor, Command};
+use crate::{
+    command::sense::{BytesSenseBuffer, Sense},
+    is_retryable,
+    result_data::ResultData,
+    transport::Recorder,
+    Command, DataDirection, NativeTransport, RetryPolicy, SenseData, TraceRecord, Transport,
+    TransportRequest, TransportResult, VectoredRequest,
+};
+
+#[cfg(target_os = "linux")]
+use crate::{QueuedRequest, QueuedTransport};
 
 #[derive(Debug)]
-pub struct Scsi {
+pub struct Scsi<T = NativeTransport> {
     path: PathBuf,
-    file_descriptor: FileDescriptor,
+    transport: T,
     timeout: Duration,
+    timeout_override: Option<Duration>,
+    recorder: Option<Recorder>,
+    retry_policy: RetryPolicy,
+    /// The claim currently held, if any
+    claim: Mutex<Weak<Option<File>>>,
 }
 
+/// The disk stays claimed until every clone of it is dropped, see [`Scsi::claim_exclusive`].
+#[derive(Debug, Clone)]
+pub struct ExclusiveClaim(Arc<Option<File>>);
+
 impl Scsi {
     pub fn new<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<Scsi> {
-        let mut options = OpenOptions::new();
-        options.read(true).write(true);
-        let file_descriptor = FileDescriptor::open(&path, options)?;
+        let transport = NativeTransport::open(path)?;
 
-        if !file_descriptor.is_block()? {
-            return Err(crate::Error::NotBlockDevice(path.as_ref().to_owned()));
-        }
+        Ok(Scsi::with_transport(path, transport))
+    }
 
-        if !Self::is_scsi_device(&file_descriptor)? {
-            return Err(crate::Error::NotScsiDevice(path.as_ref().to_owned()));
-        }
+    /// Like `new`, but talks to the SCSI Generic node (/dev/sgN) of a block device if it has one.
+    /// Hosts may reject vendor specific opcodes sent to /dev/sdX with EPERM without
+    /// CAP_SYS_RAWIO, sg lets them through.
+    /// Falls back to `path` itself if the sg driver isn't loaded.
+    #[cfg(target_os = "linux")]
+    pub fn new_generic<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<Scsi> {
+        let transport = match crate::discovery::sg_path(path.as_ref())? {
+            Some(sg_path) => {
+                NativeTransport::open(&sg_path).or_else(|_| NativeTransport::open(path))?
+            }
+            None => NativeTransport::open(path)?,
+        };
 
-        Ok(Scsi {
-            path: path.as_ref().to_owned(),
-            file_descriptor,
-            timeout: Duration::from_millis(SG_DEFAULT_TIMEOUT),
-        })
+        Ok(Scsi::with_transport(path, transport))
     }
 
-    #[cfg(target_os = "linux")]
-    pub fn execute_command<T: Command>(&self, command: &T) -> T::ReturnType {
-        use nix::libc;
+    /// Same as `new`, there's no SCSI Generic driver outside of Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new_generic<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<Scsi> {
+        Scsi::new(path)
+    }
+}
 
-        use crate::{
-            os::sg_io_header::SgIoHeader, result_data::ResultData, AccessFlags, AuxiliaryInfo,
-            DriverStatus, MaskedStatus,
-        };
+#[cfg(target_os = "linux")]
+impl Scsi<QueuedTransport> {
+    /// Opens the SCSI Generic node of `path` for queued commands, see [`QueuedTransport`].
+    pub fn new_queued<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<Scsi<QueuedTransport>> {
+        let transport = QueuedTransport::open(path)?;
 
-        const SG_IO: u32 = 0x2285;
+        Ok(Scsi::with_transport(path, transport))
+    }
 
+    /// Like `execute_command`, but waits for the response without blocking the thread,
+    /// so several commands can be in flight at once.
+    /// Dropping the future only stops waiting, it doesn't cancel anything:
+    /// the device still carries out the command.
+    /// Failed commands aren't retried, there's no timer to back off with.
+    pub async fn execute_command_async<C: Command>(&self, command: &C) -> C::ReturnType {
         let command_buffer = command.get_command();
         let mut data_buffer = command.get_data();
         let mut sense_buffer = command.get_sense_buffer();
 
-        let size_of_command_buffer = size_of_val(&command_buffer) as u8;
-        let size_of_data_buffer = command.get_data_size();
-        let size_of_sense_buffer = size_of_val(&sense_buffer) as u8;
-
-        let pointer_of_command_buffer = Some(&command_buffer);
-
-        let pointer_of_data_buffer = if size_of_data_buffer == 0 {
-            None
-        } else {
-            Some(data_buffer.borrow_mut())
-        };
+        let size_of_data_buffer = usize::min(
+            command.get_data_size() as usize,
+            size_of_val(data_buffer.borrow_mut()),
+        );
+
+        let direction = command.get_direction();
+        let command_bytes = unsafe { as_byte_slice(&command_buffer) };
+        let data =
+            &mut unsafe { as_mut_byte_slice(data_buffer.borrow_mut()) }[..size_of_data_buffer];
+        let data_out = self.data_out(direction, data);
+
+        let response = self
+            .transport
+            .submit(QueuedRequest {
+                direction,
+                command: command_bytes.to_vec(),
+                data: data.to_vec(),
+                sense: vec![0; size_of::<C::SenseBuffer>()],
+                timeout: self.command_timeout(command),
+            })
+            .await;
+
+        data.copy_from_slice(&response.data);
+        let sense = unsafe { as_mut_byte_slice(&mut sense_buffer) };
+        sense.copy_from_slice(&response.sense);
+        self.record(
+            direction,
+            command_bytes,
+            &data_out,
+            data,
+            sense,
+            &response.result,
+        );
+
+        self.finish_command(
+            command,
+            &mut data_buffer,
+            size_of_data_buffer,
+            &mut sense_buffer,
+            response.result,
+        )
+    }
+}
 
-        let pointer_of_sense_buffer = if size_of_sense_buffer == 0 {
-            None
-        } else {
-            Some(&mut sense_buffer)
-        };
+impl<T: Transport> Scsi<T> {
+    /// `path` is only used for display purpose.
+    pub fn with_transport<P: AsRef<Path> + ?Sized>(path: &P, transport: T) -> Scsi<T> {
+        Scsi {
+            path: path.as_ref().to_owned(),
+            transport,
+            timeout: Duration::from_millis(SG_DEFAULT_TIMEOUT),
+            timeout_override: None,
+            recorder: None,
+            retry_policy: RetryPolicy::NEVER,
+            claim: Mutex::new(Weak::new()),
+        }
+    }
 
-        let mut sg_header = SgIoHeader {
-            interface_id: b'S' as i32,
-            data_direction: command.get_direction().into(),
-            command_length: size_of_command_buffer,
-            max_sense_buffer_length: size_of_sense_buffer,
-            iovec_count: 0,
-            data_length: size_of_data_buffer,
-            data: pointer_of_data_buffer,
-            command: pointer_of_command_buffer,
-            sense_buffer: pointer_of_sense_buffer,
-            timeout: self
-                .timeout
-                .as_millis()
-                .clamp(u32::MIN as u128, u32::MAX as u128) as u32,
-            flags: AccessFlags::DEFAULT,
-            pack_id: 0,
-            user_pointer: 0,
-            status: 0,
-            masked_status: MaskedStatus::GOOD,
-            message_status: 0,
-            sense_buffer_written: 0,
-            host_status: 0,
-            driver_status: DriverStatus::OK,
-            residual_count: 0,
-            duration: 0,
-            info: AuxiliaryInfo::OK,
-        };
+    pub fn execute_command<C: Command>(&self, command: &C) -> C::ReturnType {
+        let command_buffer = command.get_command();
+        let mut data_buffer = command.get_data();
+        let mut sense_buffer = command.get_sense_buffer();
 
-        let ioctl_result = unsafe {
-            libc::ioctl(
-                self.file_descriptor.raw(),
-                SG_IO.try_into().unwrap(),
-                &mut sg_header,
-            )
-        };
+        let size_of_data_buffer = usize::min(
+            command.get_data_size() as usize,
+            size_of_val(data_buffer.borrow_mut()),
+        );
+
+        let direction = command.get_direction();
+        let command_bytes = unsafe { as_byte_slice(&command_buffer) };
+        let data =
+            &mut unsafe { as_mut_byte_slice(data_buffer.borrow_mut()) }[..size_of_data_buffer];
+        let data_out = self.data_out(direction, data);
+
+        let sense = unsafe { as_mut_byte_slice(&mut sense_buffer) };
+
+        let mut attempt = 1;
+        let transport_result = loop {
+            let transport_result = self.transport.transport(TransportRequest {
+                direction,
+                command: command_bytes,
+                data: &mut *data,
+                sense: &mut *sense,
+                timeout: self.command_timeout(command),
+            });
+            self.record(
+                direction,
+                command_bytes,
+                &data_out,
+                data,
+                sense,
+                &transport_result,
+            );
 
-        let result_data = ResultData {
-            ioctl_result,
-            transfered_data_length: sg_header.data_length as usize
-                - sg_header.residual_count as usize,
-            data: sg_header.data,
-            transfered_sense_length: sg_header.sense_buffer_written as usize,
-            sense_buffer: sg_header.sense_buffer,
-            masked_status: sg_header.masked_status,
-            host_status: sg_header.host_status.into(),
-            driver_status: sg_header.driver_status,
+            attempt += 1;
+            match self.retry_delay(attempt, &transport_result, sense, command.is_retry_safe()) {
+                Some(delay) => thread::sleep(delay),
+                None => break transport_result,
+            }
         };
 
-        command.process_result(&result_data)
+        self.finish_command(
+            command,
+            &mut data_buffer,
+            size_of_data_buffer,
+            &mut sense_buffer,
+            transport_result,
+        )
     }
 
-    #[cfg(target_os = "windows")]
-    pub fn execute_command<T: Command>(&self, command: &T) -> T::ReturnType {
-        use std::slice;
-
-        use windows::Win32::{
-            Foundation::HANDLE,
-            Storage::IscsiDisc::{
-                IOCTL_SCSI_PASS_THROUGH_DIRECT, SCSI_IOCTL_DATA_BIDIRECTIONAL, SCSI_IOCTL_DATA_IN,
-                SCSI_IOCTL_DATA_OUT, SCSI_IOCTL_DATA_UNSPECIFIED,
-            },
-            System::IO::DeviceIoControl,
+    /// Sends `command` with its data spread over several buffers, passed to the kernel as they
+    /// are by block devices and sg nodes on Linux, see `Transport::transport_vectored`.
+    /// With buffers in both `data_out` and `data_in` it's bidirectional, that only works on Linux
+    /// through the bsg node of the disk, and on kernels before 5.1.
+    /// Retried and recorded like `execute_command`, `timeout` is the one of the command.
+    /// Only reads count as `Command::is_retry_safe`.
+    /// Returns the number of bytes transfered, the received ones if there's `data_in`.
+    pub fn execute_vectored(
+        &self,
+        command: &[u8],
+        data_out: &[IoSlice],
+        data_in: &mut [IoSliceMut],
+        timeout: Option<Duration>,
+    ) -> crate::Result<usize> {
+        let timeout = self.timeout_override.or(timeout).unwrap_or(self.timeout);
+        let mut sense_buffer = BytesSenseBuffer::default();
+        let data_out_bytes: Vec<u8> = match &self.recorder {
+            Some(_) => data_out.iter().flat_map(|buffer| buffer.to_vec()).collect(),
+            None => vec![],
         };
 
-        use crate::{
-            os::scsi_pass_through_header::ScsiPassThroughDirectWrapper, result_data::ResultData,
-            DriverStatus, MaskedStatus,
+        let mut attempt = 1;
+        let transport_result = loop {
+            let request = VectoredRequest {
+                command,
+                data_out,
+                data_in: &mut *data_in,
+                sense: &mut sense_buffer,
+                timeout,
+            };
+            let direction = request.direction();
+            // like the default of `Command::is_retry_safe`
+            let retry_safe = direction == DataDirection::FromDevice;
+            let transport_result = self.transport.transport_vectored(request);
+            if self.recorder.is_some() {
+                let data: Vec<u8> = data_in.iter().flat_map(|buffer| buffer.to_vec()).collect();
+                self.record(
+                    direction,
+                    command,
+                    &data_out_bytes,
+                    &data,
+                    &sense_buffer,
+                    &transport_result,
+                );
+            }
+
+            attempt += 1;
+            match self.retry_delay(attempt, &transport_result, &sense_buffer, retry_safe) {
+                Some(delay) => thread::sleep(delay),
+                None => break transport_result,
+            }
         };
 
-        const MAX_COMMAND_LENGTH: u8 = 16;
-
-        let command_buffer = command.get_command();
-        let mut data_buffer = command.get_data();
+        let result_data: ResultData<[u8], BytesSenseBuffer> = ResultData {
+            ioctl_result: transport_result.ioctl_result,
+            transfered_data_length: transport_result.transfered_data_length,
+            data: None,
+            transfered_sense_length: transport_result.transfered_sense_length,
+            sense_buffer: Some(&mut sense_buffer),
+            masked_status: transport_result.masked_status,
+            host_status: transport_result.host_status,
+            driver_status: transport_result.driver_status,
+            duration: transport_result.duration,
+        };
+        result_data.check_ioctl_error()?;
+        result_data.check_common_error()?;
 
-        let size_of_command_buffer = size_of_val(&command_buffer) as u8;
-        let size_of_data_buffer = command.get_data_size();
+        Ok(transport_result.transfered_data_length)
+    }
 
-        if size_of_command_buffer > MAX_COMMAND_LENGTH {
-            panic!(
-                "Current command length is {}, max command length is {}",
-                size_of_command_buffer, MAX_COMMAND_LENGTH
-            );
-        }
-        let command_pointer = &command_buffer as *const _ as *const u8;
-        let command_slice =
-            unsafe { slice::from_raw_parts(command_pointer, size_of_command_buffer as usize) };
-
-        let mut header = ScsiPassThroughDirectWrapper::<T::SenseBuffer>::default();
-        let address_of_header = std::ptr::addr_of!(header) as usize;
-        let mut spt = &mut header.scsi_pass_through;
-        spt.Length = size_of_val(spt) as u16;
-        spt.CdbLength = size_of_command_buffer;
-        spt.SenseInfoLength = size_of_val(&header.sense) as u8;
-        spt.DataIn = match command.get_direction() {
-            crate::DataDirection::None => SCSI_IOCTL_DATA_UNSPECIFIED,
-            crate::DataDirection::ToDevice => SCSI_IOCTL_DATA_OUT,
-            crate::DataDirection::FromDevice => SCSI_IOCTL_DATA_IN,
-            crate::DataDirection::ToFromDevice => SCSI_IOCTL_DATA_BIDIRECTIONAL,
-            crate::DataDirection::Unknown => SCSI_IOCTL_DATA_UNSPECIFIED,
-        } as u8;
-
-        spt.DataTransferLength = size_of_data_buffer;
-
-        spt.TimeOutValue = match self
-            .timeout
-            .as_secs()
-            .clamp(u32::MIN as u64, u32::MAX as u64)
-        {
-            0 => 1,
-            n => n as u32,
+    /// How long to wait before attempt number `attempt`, `None` to give up.
+    fn retry_delay(
+        &self,
+        attempt: u32,
+        transport_result: &TransportResult,
+        sense: &[u8],
+        retry_safe: bool,
+    ) -> Option<Duration> {
+        let sense_data = match transport_result.transfered_sense_length {
+            0 => None,
+            length => SenseData::parse(&sense[..usize::min(length, sense.len())]),
         };
 
-        spt.DataBuffer = data_buffer.borrow_mut() as *mut _ as _;
-
-        spt.SenseInfoOffset =
-            (std::ptr::addr_of!(header.sense) as usize - address_of_header) as u32;
+        self.retry_policy
+            .delay(attempt)
+            .filter(|_| is_retryable(transport_result, sense_data.as_ref(), retry_safe))
+    }
 
-        spt.Cdb[..command_slice.len()].copy_from_slice(command_slice);
+    fn command_timeout<C: Command>(&self, command: &C) -> Duration {
+        self.timeout_override
+            .or_else(|| command.get_timeout())
+            .unwrap_or(self.timeout)
+    }
 
-        let mut bytes_returned = 0;
+    /// Data sent to the device, kept for the trace.
+    fn data_out(&self, direction: DataDirection, data: &[u8]) -> Vec<u8> {
+        match (&self.recorder, direction) {
+            (Some(_), DataDirection::ToDevice | DataDirection::ToFromDevice) => data.to_vec(),
+            _ => vec![],
+        }
+    }
 
-        let success = unsafe {
-            DeviceIoControl(
-                HANDLE(self.file_descriptor.raw() as isize),
-                IOCTL_SCSI_PASS_THROUGH_DIRECT,
-                Some(&header as *const _ as _),
-                size_of_val(&header) as u32,
-                Some(&mut header as *mut _ as _),
-                size_of_val(&header) as u32,
-                Some(&mut bytes_returned),
-                None,
-            )
+    fn record(
+        &self,
+        direction: DataDirection,
+        command: &[u8],
+        data_out: &[u8],
+        data: &[u8],
+        sense: &[u8],
+        transport_result: &TransportResult,
+    ) {
+        let Some(recorder) = &self.recorder else {
+            return;
         };
 
-        let ioctl_result = match success.as_bool() {
-            true => 0,
-            false => -1,
+        let data_in = match direction {
+            DataDirection::FromDevice | DataDirection::ToFromDevice => {
+                data[..usize::min(transport_result.transfered_data_length, data.len())].to_vec()
+            }
+            _ => vec![],
         };
 
+        recorder.record(&TraceRecord {
+            command: command.to_vec(),
+            direction,
+            data_out: data_out.to_vec(),
+            data_in,
+            sense: sense[..usize::min(transport_result.transfered_sense_length, sense.len())]
+                .to_vec(),
+            masked_status: transport_result.masked_status,
+            host_status: transport_result.host_status,
+            driver_status: transport_result.driver_status,
+            duration: transport_result.duration,
+            ioctl_result: transport_result.ioctl_result,
+        });
+    }
+
+    /// Hands the response to `process_result`.
+    fn finish_command<C: Command>(
+        &self,
+        command: &C,
+        data_buffer: &mut C::DataBufferWrapper,
+        size_of_data_buffer: usize,
+        sense_buffer: &mut C::SenseBuffer,
+        transport_result: TransportResult,
+    ) -> C::ReturnType {
+        let size_of_sense_buffer = size_of::<C::SenseBuffer>();
+
         let result_data = ResultData {
-            ioctl_result,
-            transfered_data_length: header.scsi_pass_through.DataTransferLength as usize,
-            data: Some(data_buffer.borrow_mut()),
-            transfered_sense_length: header.scsi_pass_through.SenseInfoLength as usize,
-            sense_buffer: Some(&mut header.sense),
-            masked_status: MaskedStatus::from_bits_truncate(
-                header.scsi_pass_through.ScsiStatus >> 1,
-            ),
-            host_status: crate::HostStatus::Ok,
-            driver_status: DriverStatus::OK,
+            ioctl_result: transport_result.ioctl_result,
+            transfered_data_length: transport_result.transfered_data_length,
+            data: if size_of_data_buffer == 0 {
+                None
+            } else {
+                Some(data_buffer.borrow_mut())
+            },
+            transfered_sense_length: transport_result.transfered_sense_length,
+            sense_buffer: if size_of_sense_buffer == 0 {
+                None
+            } else {
+                Some(sense_buffer)
+            },
+            masked_status: transport_result.masked_status,
+            host_status: transport_result.host_status,
+            driver_status: transport_result.driver_status,
+            duration: transport_result.duration,
         };
 
         command.process_result(&result_data)
@@ -231,6 +374,11 @@ impl Scsi {
         &self.path
     }
 
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// For commands that don't declare a timeout of their own, see `Command::get_timeout`.
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = timeout;
     }
@@ -239,65 +387,193 @@ impl Scsi {
         self.timeout
     }
 
-    #[cfg(target_os = "linux")]
-    fn is_scsi_device(file: &FileDescriptor) -> crate::Result<bool> {
-        use nix::libc;
-
-        const SG_GET_VERSION_NUM: u32 = 0x2282;
-
-        let mut version = 0_i32;
-        let result = unsafe {
-            libc::ioctl(
-                file.raw(),
-                SG_GET_VERSION_NUM.try_into().unwrap(),
-                &mut version,
-            )
-        };
+    /// Used for every command while set, even the ones that declare their own timeout.
+    pub fn set_timeout_override(&mut self, timeout: Option<Duration>) {
+        self.timeout_override = timeout;
+    }
 
-        if result != 0 {
-            Err(io::Error::last_os_error())?;
-        }
+    pub fn timeout_override(&self) -> Option<Duration> {
+        self.timeout_override
+    }
 
-        if version < 30000 {
-            Ok(false)
-        } else {
-            Ok(true)
-        }
+    /// No command is retried unless a policy is set.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
     }
 
-    #[cfg(target_os = "windows")]
-    fn is_scsi_device(file: &FileDescriptor) -> crate::Result<bool> {
-        use std::mem::size_of;
-
-        use windows::Win32::Foundation::HANDLE;
-        use windows::Win32::Storage::IscsiDisc::{IOCTL_SCSI_GET_ADDRESS, SCSI_ADDRESS};
-        use windows::Win32::System::IO::DeviceIoControl;
-
-        let mut scsi_address = SCSI_ADDRESS::default();
-        let mut bytes_returned = 0;
-        let success = unsafe {
-            DeviceIoControl(
-                HANDLE(file.raw() as isize),
-                IOCTL_SCSI_GET_ADDRESS,
-                None,
-                0,
-                Some(&mut scsi_address as *mut _ as _),
-                size_of::<SCSI_ADDRESS>() as u32,
-                Some(&mut bytes_returned),
-                None,
-            )
-        };
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Write every following command and its response to `writer`, see [`TraceRecord`] for the format.
+    /// Commands don't fail because of the trace, recording just stops at the first failed write.
+    pub fn start_recording<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.recorder = Some(Recorder::new(writer));
+    }
+
+    /// Returns the error that stopped the recording early, if any.
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
 
-        if success == false {
-            Err(io::Error::last_os_error())?;
+    /// Fails with [`crate::Error::InUse`] if the disk is mounted or used by another program,
+    /// otherwise keeps it that way until the claim is dropped.
+    /// Commands that destroy data take one while they run, hold one to cover several of them.
+    /// Claims taken while one is held share it.
+    /// Where that can't be checked, the claim is taken unchecked, see `claim_exclusive_strict`.
+    pub fn claim_exclusive(&self) -> crate::Result<ExclusiveClaim> {
+        match self.claim_exclusive_strict() {
+            Err(crate::Error::InUseUnknown(_)) => Ok(self.claim_unchecked()),
+            result => result,
         }
+    }
 
-        if bytes_returned == 0 {
-            Ok(false)
-        } else {
-            Ok(true)
+    /// Like `claim_exclusive`, but also fails with [`crate::Error::InUseUnknown`] where it can't
+    /// be checked whether the disk is in use, so the user can be asked first.
+    pub fn claim_exclusive_strict(&self) -> crate::Result<ExclusiveClaim> {
+        let mut current = self.claim.lock().unwrap();
+        if let Some(disk) = current.upgrade() {
+            return Ok(ExclusiveClaim(disk));
         }
+
+        let disk = Arc::new(self.transport.claim_disk()?);
+        *current = Arc::downgrade(&disk);
+        Ok(ExclusiveClaim(disk))
+    }
+
+    /// Like `claim_exclusive`, but checks and claims nothing, for when the user insists.
+    /// Commands that destroy data don't check either while it's held.
+    pub fn claim_unchecked(&self) -> ExclusiveClaim {
+        let mut current = self.claim.lock().unwrap();
+        let disk = current.upgrade().unwrap_or_else(|| Arc::new(None));
+        *current = Arc::downgrade(&disk);
+        ExclusiveClaim(disk)
     }
 }
 
+/// Command buffers are plain bitfields or byte arrays, so they are sent as they are laid out in memory.
+unsafe fn as_byte_slice<B: ?Sized>(buffer: &B) -> &[u8] {
+    slice::from_raw_parts(buffer as *const B as *const u8, size_of_val(buffer))
+}
+
+unsafe fn as_mut_byte_slice<B: ?Sized>(buffer: &mut B) -> &mut [u8] {
+    slice::from_raw_parts_mut(buffer as *mut B as *mut u8, size_of_val(buffer))
+}
+
 const SG_DEFAULT_TIMEOUT: u64 = 60_000;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct ClaimCounter {
+        claims: Cell<usize>,
+        in_use: bool,
+        unknown: bool,
+    }
+
+    impl Transport for ClaimCounter {
+        fn transport(&self, _request: TransportRequest) -> TransportResult {
+            TransportResult::good(0)
+        }
+
+        fn claim_disk(&self) -> crate::Result<Option<File>> {
+            self.claims.set(self.claims.get() + 1);
+            match (self.in_use, self.unknown) {
+                (true, _) => Err(crate::Error::InUse("disk".into(), "mounted".to_owned())),
+                (_, true) => Err(crate::Error::InUseUnknown("disk".into())),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    /// Keeps the commands it was sent and answers them with `response`
+    #[derive(Default)]
+    struct Canned {
+        response: Vec<u8>,
+        requests: RefCell<Vec<(Vec<u8>, DataDirection, Duration)>>,
+    }
+
+    impl Transport for Canned {
+        fn transport(&self, request: TransportRequest) -> TransportResult {
+            self.requests.borrow_mut().push((
+                request.command.to_vec(),
+                request.direction,
+                request.timeout,
+            ));
+            let length = usize::min(self.response.len(), request.data.len());
+            request.data[..length].copy_from_slice(&self.response[..length]);
+
+            TransportResult::good(length)
+        }
+    }
+
+    #[test]
+    fn custom_transport() {
+        let mut response = vec![0; 32];
+        response[..8].copy_from_slice(&0x0fffu64.to_be_bytes());
+        response[8..12].copy_from_slice(&4096u32.to_be_bytes());
+        let device = Scsi::with_transport(
+            "canned",
+            Canned {
+                response,
+                ..Default::default()
+            },
+        );
+        assert_eq!(device.path(), Path::new("canned"));
+
+        let capacity = device.read_capacity16().unwrap();
+        assert_eq!(capacity.logical_block_count, 0x1000);
+        assert_eq!(capacity.logical_block_length_in_bytes, 4096);
+
+        let requests = device.transport().requests.borrow();
+        let (command, direction, timeout) = &requests[0];
+        assert_eq!(command[..2], [0x9e, 0x10]);
+        assert_eq!(*direction, DataDirection::FromDevice);
+        assert_eq!(*timeout, device.timeout());
+    }
+
+    #[test]
+    fn exclusive_claims() {
+        let device = Scsi::with_transport("disk", ClaimCounter::default());
+        let claim = device.claim_exclusive().unwrap();
+        let nested = device.claim_exclusive().unwrap();
+        assert_eq!(device.transport().claims.get(), 1);
+        drop(claim);
+        drop(nested);
+        let _claim = device.claim_exclusive().unwrap();
+        assert_eq!(device.transport().claims.get(), 2);
+
+        let in_use = ClaimCounter {
+            in_use: true,
+            ..Default::default()
+        };
+        let device = Scsi::with_transport("disk", in_use);
+        assert!(matches!(
+            device.claim_exclusive(),
+            Err(crate::Error::InUse(_, _))
+        ));
+        let _forced = device.claim_unchecked();
+        assert!(device.claim_exclusive().is_ok());
+        assert_eq!(device.transport().claims.get(), 1);
+
+        let unknown = ClaimCounter {
+            unknown: true,
+            ..Default::default()
+        };
+        let device = Scsi::with_transport("disk", unknown);
+        assert!(matches!(
+            device.claim_exclusive_strict(),
+            Err(crate::Error::InUseUnknown(_))
+        ));
+        let _claim = device.claim_exclusive().unwrap();
+        assert!(device.claim_exclusive_strict().is_ok());
+        assert_eq!(device.transport().claims.get(), 2);
+    }
+}
@@ -0,0 +1,412 @@
+use std::fmt;
+
+const FIXED_CURRENT: u8 = 0x70;
+const FIXED_DEFERRED: u8 = 0x71;
+const DESCRIPTOR_CURRENT: u8 = 0x72;
+const DESCRIPTOR_DEFERRED: u8 = 0x73;
+
+const INFORMATION_DESCRIPTOR: u8 = 0x00;
+const SENSE_KEY_SPECIFIC_DESCRIPTOR: u8 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SenseKey {
+    NoSense,
+    RecoveredError,
+    NotReady,
+    MediumError,
+    HardwareError,
+    IllegalRequest,
+    UnitAttention,
+    DataProtect,
+    BlankCheck,
+    VendorSpecific,
+    CopyAborted,
+    AbortedCommand,
+    Reserved,
+    VolumeOverflow,
+    Miscompare,
+    Completed,
+}
+
+impl From<u8> for SenseKey {
+    fn from(value: u8) -> Self {
+        match value & 0x0f {
+            0x00 => SenseKey::NoSense,
+            0x01 => SenseKey::RecoveredError,
+            0x02 => SenseKey::NotReady,
+            0x03 => SenseKey::MediumError,
+            0x04 => SenseKey::HardwareError,
+            0x05 => SenseKey::IllegalRequest,
+            0x06 => SenseKey::UnitAttention,
+            0x07 => SenseKey::DataProtect,
+            0x08 => SenseKey::BlankCheck,
+            0x09 => SenseKey::VendorSpecific,
+            0x0a => SenseKey::CopyAborted,
+            0x0b => SenseKey::AbortedCommand,
+            0x0c => SenseKey::Reserved,
+            0x0d => SenseKey::VolumeOverflow,
+            0x0e => SenseKey::Miscompare,
+            _ => SenseKey::Completed,
+        }
+    }
+}
+
+impl From<SenseKey> for u8 {
+    fn from(value: SenseKey) -> Self {
+        match value {
+            SenseKey::NoSense => 0x00,
+            SenseKey::RecoveredError => 0x01,
+            SenseKey::NotReady => 0x02,
+            SenseKey::MediumError => 0x03,
+            SenseKey::HardwareError => 0x04,
+            SenseKey::IllegalRequest => 0x05,
+            SenseKey::UnitAttention => 0x06,
+            SenseKey::DataProtect => 0x07,
+            SenseKey::BlankCheck => 0x08,
+            SenseKey::VendorSpecific => 0x09,
+            SenseKey::CopyAborted => 0x0a,
+            SenseKey::AbortedCommand => 0x0b,
+            SenseKey::Reserved => 0x0c,
+            SenseKey::VolumeOverflow => 0x0d,
+            SenseKey::Miscompare => 0x0e,
+            SenseKey::Completed => 0x0f,
+        }
+    }
+}
+
+/// Decoded fixed or descriptor format sense data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenseData {
+    pub response_code: u8,
+    pub sense_key: SenseKey,
+    pub additional_sense_code: u8,
+    pub additional_sense_code_qualifier: u8,
+    /// Only set if the device marked it as valid
+    pub information: Option<u64>,
+    /// Only set if SKSV is set
+    pub sense_key_specific: Option<[u8; 3]>,
+    pub filemark: bool,
+    pub eom: bool,
+    pub ili: bool,
+    /// Raw sense data descriptors, descriptor format only
+    pub descriptors: Vec<u8>,
+}
+
+impl SenseData {
+    pub fn parse(bytes: &[u8]) -> Option<SenseData> {
+        let response_code = *bytes.first()? & 0x7f;
+        match response_code {
+            FIXED_CURRENT | FIXED_DEFERRED => Self::parse_fixed(bytes),
+            DESCRIPTOR_CURRENT | DESCRIPTOR_DEFERRED => Self::parse_descriptor(bytes),
+            _ => None,
+        }
+    }
+
+    fn parse_fixed(bytes: &[u8]) -> Option<SenseData> {
+        let flags = *bytes.get(2)?;
+        let additional_sense_length = bytes.get(7).copied().unwrap_or_default() as usize;
+        let end = usize::min(bytes.len(), 8 + additional_sense_length);
+        let byte = |index: usize| match index < end {
+            true => bytes[index],
+            false => 0,
+        };
+
+        let information = match bytes[0] & 0x80 {
+            0 => None,
+            _ => Some(u32::from_be_bytes([byte(3), byte(4), byte(5), byte(6)]) as u64),
+        };
+
+        let sense_key_specific = match byte(15) & 0x80 {
+            0 => None,
+            _ => Some([byte(15), byte(16), byte(17)]),
+        };
+
+        Some(SenseData {
+            response_code: bytes[0] & 0x7f,
+            sense_key: flags.into(),
+            additional_sense_code: byte(12),
+            additional_sense_code_qualifier: byte(13),
+            information,
+            sense_key_specific,
+            filemark: flags & 0x80 != 0,
+            eom: flags & 0x40 != 0,
+            ili: flags & 0x20 != 0,
+            descriptors: vec![],
+        })
+    }
+
+    fn parse_descriptor(bytes: &[u8]) -> Option<SenseData> {
+        if bytes.len() < 4 {
+            return None;
+        }
+
+        let additional_sense_length = bytes.get(7).copied().unwrap_or_default() as usize;
+        let end = usize::min(bytes.len(), 8 + additional_sense_length);
+        let descriptors = bytes.get(8..end).unwrap_or_default().to_vec();
+
+        let mut sense_data = SenseData {
+            response_code: bytes[0] & 0x7f,
+            sense_key: bytes[1].into(),
+            additional_sense_code: bytes[2],
+            additional_sense_code_qualifier: bytes[3],
+            information: None,
+            sense_key_specific: None,
+            filemark: false,
+            eom: false,
+            ili: false,
+            descriptors,
+        };
+
+        if let Some(descriptor) = sense_data.descriptor(INFORMATION_DESCRIPTOR) {
+            if descriptor.len() >= 12 && descriptor[2] & 0x80 != 0 {
+                sense_data.information =
+                    Some(u64::from_be_bytes(descriptor[4..12].try_into().unwrap()));
+            }
+        }
+
+        if let Some(descriptor) = sense_data.descriptor(SENSE_KEY_SPECIFIC_DESCRIPTOR) {
+            if descriptor.len() >= 7 && descriptor[4] & 0x80 != 0 {
+                sense_data.sense_key_specific = Some(descriptor[4..7].try_into().unwrap());
+            }
+        }
+
+        Some(sense_data)
+    }
+
+    pub fn is_descriptor_format(&self) -> bool {
+        matches!(self.response_code, DESCRIPTOR_CURRENT | DESCRIPTOR_DEFERRED)
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        matches!(self.response_code, FIXED_DEFERRED | DESCRIPTOR_DEFERRED)
+    }
+
+    /// Returns the whole descriptor including its 2 byte header.
+    pub fn descriptor(&self, descriptor_type: u8) -> Option<&[u8]> {
+        let mut rest = self.descriptors.as_slice();
+        while rest.len() >= 2 {
+            let length = usize::min(rest.len(), 2 + rest[1] as usize);
+            let (descriptor, next) = rest.split_at(length);
+            if descriptor[0] == descriptor_type {
+                return Some(descriptor);
+            }
+            rest = next;
+        }
+
+        None
+    }
+
+    /// Progress of a long running operation, in 1/65536 units.
+    /// Reported with NO SENSE or NOT READY while a format, self-test, sanitize etc. is in progress.
+    pub fn progress_indication(&self) -> Option<u16> {
+        match self.sense_key {
+            SenseKey::NoSense | SenseKey::NotReady => self
+                .sense_key_specific
+                .map(|s| u16::from_be_bytes([s[1], s[2]])),
+            _ => None,
+        }
+    }
+
+    /// NOT READY, LOGICAL UNIT IS IN PROCESS OF BECOMING READY or OPERATION IN PROGRESS.
+    pub fn is_becoming_ready(&self) -> bool {
+        self.sense_key == SenseKey::NotReady
+            && self.additional_sense_code == 0x04
+            && matches!(self.additional_sense_code_qualifier, 0x01 | 0x07)
+    }
+
+    /// ILLEGAL REQUEST, INVALID COMMAND OPERATION CODE, e.g. a bridge that doesn't pass a command through.
+    pub fn is_unsupported_command(&self) -> bool {
+        self.sense_key == SenseKey::IllegalRequest
+            && self.additional_sense_code == 0x20
+            && self.additional_sense_code_qualifier == 0x00
+    }
+
+    pub fn is_unit_attention(&self) -> bool {
+        self.sense_key == SenseKey::UnitAttention
+    }
+
+    /// UNIT ATTENTION, POWER ON, RESET, OR BUS DEVICE RESET OCCURRED, reported once after plugging in.
+    pub fn is_power_on_or_reset(&self) -> bool {
+        self.is_unit_attention() && self.additional_sense_code == 0x29
+    }
+
+    /// The medium is there but can't be accessed until it's unlocked, e.g. an encrypted drive before
+    /// `unlock_encryption`.
+    pub fn is_locked(&self) -> bool {
+        matches!(
+            (
+                self.sense_key,
+                self.additional_sense_code,
+                self.additional_sense_code_qualifier
+            ),
+            (SenseKey::DataProtect | SenseKey::NotReady, 0x74, 0x71)
+                | (SenseKey::DataProtect, 0x20, 0x02)
+        )
+    }
+
+    pub fn description(&self) -> &'static str {
+        additional_sense_description(
+            self.additional_sense_code,
+            self.additional_sense_code_qualifier,
+        )
+    }
+}
+
+impl fmt::Display for SenseData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}, {} (asc/ascq: {:02X}/{:02X})",
+            self.sense_key,
+            self.description(),
+            self.additional_sense_code,
+            self.additional_sense_code_qualifier
+        )?;
+
+        if let Some(progress) = self.progress_indication() {
+            write!(f, ", progress: {}%", progress as u32 * 100 / 65536)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn additional_sense_description(
+    additional_sense_code: u8,
+    additional_sense_code_qualifier: u8,
+) -> &'static str {
+    ADDITIONAL_SENSE_DESCRIPTIONS
+        .iter()
+        .find(|(asc, ascq, _)| {
+            *asc == additional_sense_code && *ascq == additional_sense_code_qualifier
+        })
+        .map(|(_, _, description)| *description)
+        .unwrap_or("Unknown additional sense code")
+}
+
+const ADDITIONAL_SENSE_DESCRIPTIONS: &[(u8, u8, &str)] = &[
+    (0x00, 0x00, "No additional sense information"),
+    (0x00, 0x16, "Operation in progress"),
+    (0x00, 0x1d, "ATA pass through information available"),
+    (0x04, 0x00, "Logical unit not ready, cause not reportable"),
+    (0x04, 0x01, "Logical unit is in process of becoming ready"),
+    (
+        0x04,
+        0x02,
+        "Logical unit not ready, initializing command required",
+    ),
+    (
+        0x04,
+        0x03,
+        "Logical unit not ready, manual intervention required",
+    ),
+    (0x04, 0x04, "Logical unit not ready, format in progress"),
+    (0x04, 0x07, "Logical unit not ready, operation in progress"),
+    (0x04, 0x09, "Logical unit not ready, self-test in progress"),
+    (
+        0x04,
+        0x11,
+        "Logical unit not ready, notify (enable spinup) required",
+    ),
+    (0x04, 0x1b, "Logical unit not ready, sanitize in progress"),
+    (0x04, 0x22, "Logical unit not ready, power cycle required"),
+    (0x05, 0x00, "Logical unit does not respond to selection"),
+    (0x08, 0x00, "Logical unit communication failure"),
+    (0x08, 0x01, "Logical unit communication time-out"),
+    (0x0b, 0x01, "Warning - specified temperature exceeded"),
+    (0x0c, 0x00, "Write error"),
+    (0x0c, 0x02, "Write error - auto reallocation failed"),
+    (0x10, 0x00, "Id CRC or ECC error"),
+    (0x11, 0x00, "Unrecovered read error"),
+    (
+        0x11,
+        0x04,
+        "Unrecovered read error - auto reallocate failed",
+    ),
+    (0x14, 0x01, "Record not found"),
+    (0x15, 0x00, "Random positioning error"),
+    (0x1a, 0x00, "Parameter list length error"),
+    (0x1d, 0x00, "Miscompare during verify operation"),
+    (0x20, 0x00, "Invalid command operation code"),
+    (0x20, 0x02, "Access denied - no access rights"),
+    (0x21, 0x00, "Logical block address out of range"),
+    (0x24, 0x00, "Invalid field in cdb"),
+    (0x25, 0x00, "Logical unit not supported"),
+    (0x26, 0x00, "Invalid field in parameter list"),
+    (0x26, 0x01, "Parameter not supported"),
+    (0x26, 0x02, "Parameter value invalid"),
+    (0x27, 0x00, "Write protected"),
+    (
+        0x28,
+        0x00,
+        "Not ready to ready change, medium may have changed",
+    ),
+    (0x29, 0x00, "Power on, reset, or bus device reset occurred"),
+    (0x29, 0x01, "Power on occurred"),
+    (0x29, 0x02, "SCSI bus reset occurred"),
+    (0x29, 0x03, "Bus device reset function occurred"),
+    (0x29, 0x04, "Device internal reset"),
+    (0x2a, 0x01, "Mode parameters changed"),
+    (0x2c, 0x00, "Command sequence error"),
+    (0x2f, 0x00, "Commands cleared by another initiator"),
+    (0x31, 0x00, "Medium format corrupted"),
+    (0x32, 0x00, "No defect spare location available"),
+    (0x35, 0x00, "Enclosure failure"),
+    (0x39, 0x00, "Saving parameters not supported"),
+    (0x3a, 0x00, "Medium not present"),
+    (0x3e, 0x03, "Logical unit failed self-test"),
+    (0x40, 0x80, "Diagnostic failure"),
+    (0x44, 0x00, "Internal target failure"),
+    (0x47, 0x00, "SCSI parity error"),
+    (0x4e, 0x00, "Overlapped commands attempted"),
+    (0x53, 0x02, "Medium removal prevented"),
+    (0x55, 0x03, "Insufficient resources"),
+    (0x5d, 0x00, "Failure prediction threshold exceeded"),
+    (
+        0x5d,
+        0x10,
+        "Hardware impending failure general hard drive failure",
+    ),
+    (0x5d, 0xff, "Failure prediction threshold exceeded (false)"),
+    (0x5e, 0x00, "Low power condition on"),
+    (0x5e, 0x01, "Idle condition activated by timer"),
+    (0x5e, 0x02, "Standby condition activated by timer"),
+    (0x5e, 0x03, "Idle condition activated by command"),
+    (0x5e, 0x04, "Standby condition activated by command"),
+    (0x74, 0x40, "Authentication failed"),
+    (0x74, 0x71, "Logical unit access not authorized"),
+    (0x74, 0x80, "Unlock attempts exceeded (vendor specific)"),
+    (0x74, 0x81, "Already unlocked (vendor specific)"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fixed() {
+        let bytes = [
+            0xf0, 0x00, 0x05, 0x00, 0x00, 0x10, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x74, 0x40,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let sense = SenseData::parse(&bytes).unwrap();
+        assert_eq!(sense.sense_key, SenseKey::IllegalRequest);
+        assert_eq!(sense.additional_sense_code, 0x74);
+        assert_eq!(sense.additional_sense_code_qualifier, 0x40);
+        assert_eq!(sense.information, Some(0x1000));
+        assert_eq!(sense.description(), "Authentication failed");
+    }
+
+    #[test]
+    fn parse_descriptor() {
+        let bytes = [
+            0x72, 0x02, 0x04, 0x09, 0x00, 0x00, 0x00, 0x14, 0x00, 0x0a, 0x80, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x02, 0x06, 0x00, 0x00, 0x80, 0x40, 0x00, 0x00,
+        ];
+        let sense = SenseData::parse(&bytes).unwrap();
+        assert!(sense.is_descriptor_format());
+        assert_eq!(sense.sense_key, SenseKey::NotReady);
+        assert_eq!(sense.information, Some(0x1234));
+        assert_eq!(sense.progress_indication(), Some(0x4000));
+    }
+}
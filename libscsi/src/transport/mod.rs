@@ -0,0 +1,231 @@
+mod native;
+#[cfg(target_os = "linux")]
+mod queued;
+mod replay;
+mod trace;
+
+use std::{
+    fs::File,
+    io::{IoSlice, IoSliceMut},
+    time::Duration,
+};
+
+use crate::{DataDirection, DriverStatus, HostStatus, MaskedStatus};
+
+pub use native::NativeTransport;
+#[cfg(target_os = "linux")]
+pub use queued::{QueuedRequest, QueuedResponse, QueuedTransport, ResponseFuture};
+pub use replay::ReplayTransport;
+pub use trace::TraceRecord;
+
+pub(crate) use trace::Recorder;
+
+/// Something that can deliver a raw SCSI command to a device and bring back its response.
+pub trait Transport {
+    fn transport(&self, request: TransportRequest) -> TransportResult;
+
+    /// Sends or fills several buffers with one command.
+    /// The default copies them through a single buffer, so every transport can do it.
+    /// It can't send and receive at once though, such commands fail with EOPNOTSUPP
+    /// unless the transport has a way to, like bsg nodes on Linux.
+    fn transport_vectored(&self, request: VectoredRequest) -> TransportResult {
+        transport_buffered(self, request)
+    }
+
+    /// Opens the disk behind the transport so nobody else can mount or claim it while the
+    /// returned file is open, fails with [`crate::Error::InUse`] if someone already does.
+    /// `None` if there's no disk to claim, like for replays and simulators.
+    fn claim_disk(&self) -> crate::Result<Option<File>> {
+        Ok(None)
+    }
+}
+
+/// `request` copied through a single buffer, for transports that can't take a list of them.
+pub(crate) fn transport_buffered<T: Transport + ?Sized>(
+    transport: &T,
+    request: VectoredRequest,
+) -> TransportResult {
+    let direction = request.direction();
+    let mut data = match direction {
+        DataDirection::ToFromDevice => return TransportResult::failed(NOT_SUPPORTED),
+        DataDirection::ToDevice => gather(request.data_out),
+        _ => vec![0; request.data_in.iter().map(|buffer| buffer.len()).sum()],
+    };
+
+    let result = transport.transport(TransportRequest {
+        direction,
+        command: request.command,
+        data: &mut data,
+        sense: request.sense,
+        timeout: request.timeout,
+    });
+
+    scatter(&data, request.data_in);
+
+    result
+}
+
+/// The buffers one after another in a single one.
+pub(crate) fn gather(buffers: &[IoSlice]) -> Vec<u8> {
+    buffers
+        .iter()
+        .flat_map(|buffer| buffer.iter().copied())
+        .collect()
+}
+
+/// Fills the buffers one after another from `data`, which is as long as all of them.
+pub(crate) fn scatter(data: &[u8], buffers: &mut [IoSliceMut]) {
+    let mut rest = data;
+    for buffer in buffers.iter_mut() {
+        let (head, tail) = rest.split_at(buffer.len());
+        buffer.copy_from_slice(head);
+        rest = tail;
+    }
+}
+
+pub struct TransportRequest<'a> {
+    pub direction: DataDirection,
+    pub command: &'a [u8],
+    /// written to the device or filled by the device depending on `direction`
+    pub data: &'a mut [u8],
+    pub sense: &'a mut [u8],
+    pub timeout: Duration,
+}
+
+/// A command with its data spread over several buffers, e.g. a read into a list of pages.
+/// Having both data out and data in makes it bidirectional, see `Transport::transport_vectored`.
+pub struct VectoredRequest<'a, 'b> {
+    pub command: &'a [u8],
+    /// Sent to the device one after another
+    pub data_out: &'a [IoSlice<'a>],
+    /// Filled by the device one after another
+    pub data_in: &'a mut [IoSliceMut<'b>],
+    pub sense: &'a mut [u8],
+    pub timeout: Duration,
+}
+
+impl VectoredRequest<'_, '_> {
+    pub fn direction(&self) -> DataDirection {
+        match (self.data_out.is_empty(), self.data_in.is_empty()) {
+            (true, true) => DataDirection::None,
+            (false, true) => DataDirection::ToDevice,
+            (true, false) => DataDirection::FromDevice,
+            (false, false) => DataDirection::ToFromDevice,
+        }
+    }
+}
+
+/// Negated os error code of a request the transport can't carry out.
+#[cfg(target_os = "linux")]
+pub(crate) const NOT_SUPPORTED: i32 = -nix::libc::EOPNOTSUPP;
+
+#[cfg(target_os = "windows")]
+pub(crate) const NOT_SUPPORTED: i32 = -(windows::Win32::Foundation::ERROR_NOT_SUPPORTED.0 as i32);
+
+#[derive(Debug)]
+pub struct TransportResult {
+    /// 0 on success, otherwise a negated os error code
+    pub ioctl_result: i32,
+    pub transfered_data_length: usize,
+    pub transfered_sense_length: usize,
+    pub masked_status: MaskedStatus,
+    pub host_status: HostStatus,
+    pub driver_status: DriverStatus,
+    /// time taken by the command, zero if the transport can't tell
+    pub duration: Duration,
+}
+
+impl TransportResult {
+    /// A successfully completed command that transfered `transfered_data_length` bytes.
+    pub fn good(transfered_data_length: usize) -> Self {
+        TransportResult {
+            ioctl_result: 0,
+            transfered_data_length,
+            transfered_sense_length: 0,
+            masked_status: MaskedStatus::GOOD,
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::OK,
+            duration: Duration::ZERO,
+        }
+    }
+
+    /// A command that never reached the device, `ioctl_result` is a negated os error code.
+    pub fn failed(ioctl_result: i32) -> Self {
+        TransportResult {
+            ioctl_result,
+            ..TransportResult::good(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// Keeps what it was sent, fills data in with 0, 1, 2...
+    #[derive(Default)]
+    struct Recording {
+        requests: RefCell<Vec<(DataDirection, Vec<u8>)>>,
+    }
+
+    impl Transport for Recording {
+        fn transport(&self, request: TransportRequest) -> TransportResult {
+            self.requests
+                .borrow_mut()
+                .push((request.direction, request.data.to_vec()));
+            if request.direction == DataDirection::FromDevice {
+                for (index, byte) in request.data.iter_mut().enumerate() {
+                    *byte = index as u8;
+                }
+            }
+
+            TransportResult::good(request.data.len())
+        }
+    }
+
+    #[test]
+    fn bounce_buffer() {
+        let transport = Recording::default();
+        let (mut first, mut second) = ([0xff; 3], [0xff; 5]);
+        let result = transport.transport_vectored(VectoredRequest {
+            command: &[0x28],
+            data_out: &[],
+            data_in: &mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)],
+            sense: &mut [0; 32],
+            timeout: Duration::from_secs(1),
+        });
+        assert_eq!(result.transfered_data_length, 8);
+        assert_eq!(first, [0, 1, 2]);
+        assert_eq!(second, [3, 4, 5, 6, 7]);
+
+        let result = transport.transport_vectored(VectoredRequest {
+            command: &[0x2a],
+            data_out: &[IoSlice::new(&[1, 2]), IoSlice::new(&[]), IoSlice::new(&[3])],
+            data_in: &mut [],
+            sense: &mut [0; 32],
+            timeout: Duration::from_secs(1),
+        });
+        assert_eq!(result.transfered_data_length, 3);
+        assert_eq!(
+            transport.requests.borrow()[1],
+            (DataDirection::ToDevice, vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn bidirectional_not_supported() {
+        let transport = Recording::default();
+        let mut data_in = [0; 4];
+        let result = transport.transport_vectored(VectoredRequest {
+            command: &[0x53],
+            data_out: &[IoSlice::new(&[1, 2])],
+            data_in: &mut [IoSliceMut::new(&mut data_in)],
+            sense: &mut [0; 32],
+            timeout: Duration::from_secs(1),
+        });
+        assert_eq!(result.ioctl_result, NOT_SUPPORTED);
+        assert!(transport.requests.borrow().is_empty());
+    }
+}
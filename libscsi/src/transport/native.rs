@@ -0,0 +1,501 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use crate::file_descriptor::FileDescriptor;
+
+use super::{Transport, TransportRequest, TransportResult};
+
+/// SG_IO on Linux, SCSI_PASS_THROUGH_DIRECT on Windows.
+/// On Linux it takes block devices, sg nodes and bsg nodes, the latter get sg v4 requests.
+#[derive(Debug)]
+pub struct NativeTransport {
+    file_descriptor: FileDescriptor,
+    #[cfg(target_os = "linux")]
+    bsg: bool,
+}
+
+impl NativeTransport {
+    pub fn open<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<NativeTransport> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true);
+
+        Self::open_with(path, options)
+    }
+
+    fn open_with<P: AsRef<Path> + ?Sized>(
+        path: &P,
+        options: OpenOptions,
+    ) -> crate::Result<NativeTransport> {
+        let file_descriptor = FileDescriptor::open(&path, options)?;
+
+        if !Self::is_device(&file_descriptor)? {
+            return Err(crate::Error::NotBlockDevice(path.as_ref().to_owned()));
+        }
+
+        if !Self::is_scsi_device(&file_descriptor)? {
+            return Err(crate::Error::NotScsiDevice(path.as_ref().to_owned()));
+        }
+
+        Ok(NativeTransport {
+            #[cfg(target_os = "linux")]
+            bsg: Self::is_bsg(&file_descriptor)?,
+            file_descriptor,
+        })
+    }
+
+    /// Returns 0 or a negated os error code, `header` is a v3 or v4 sg header.
+    #[cfg(target_os = "linux")]
+    fn sg_io<H>(&self, header: &mut H) -> i32 {
+        use nix::libc;
+
+        const SG_IO: u32 = 0x2285;
+
+        let ioctl_result =
+            unsafe { libc::ioctl(self.file_descriptor.raw(), SG_IO.into(), header as *mut H) };
+
+        match ioctl_result {
+            0 => 0,
+            _ => -io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or(libc::EIO),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn is_bsg(file: &FileDescriptor) -> crate::Result<bool> {
+        if !file.is_char()? {
+            return Ok(false);
+        }
+
+        let (major, minor) = file.device_number()?;
+        Ok(crate::discovery::is_bsg(major, minor))
+    }
+
+    /// SCSI Generic nodes (/dev/sgN) are character devices, `is_scsi_device` tells them apart
+    /// from other ones.
+    #[cfg(target_os = "linux")]
+    fn is_device(file: &FileDescriptor) -> crate::Result<bool> {
+        Ok(file.is_block()? || file.is_char()?)
+    }
+
+    #[cfg(target_os = "windows")]
+    fn is_device(file: &FileDescriptor) -> crate::Result<bool> {
+        file.is_block()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(super) fn is_scsi_device(file: &FileDescriptor) -> crate::Result<bool> {
+        use nix::libc;
+
+        const SG_GET_VERSION_NUM: u32 = 0x2282;
+
+        let mut version = 0_i32;
+        let result = unsafe { libc::ioctl(file.raw(), SG_GET_VERSION_NUM.into(), &mut version) };
+
+        if result != 0 {
+            Err(io::Error::last_os_error())?;
+        }
+
+        if version < 30000 {
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn is_scsi_device(file: &FileDescriptor) -> crate::Result<bool> {
+        use std::mem::size_of;
+
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::Storage::IscsiDisc::{IOCTL_SCSI_GET_ADDRESS, SCSI_ADDRESS};
+        use windows::Win32::System::IO::DeviceIoControl;
+
+        let mut scsi_address = SCSI_ADDRESS::default();
+        let mut bytes_returned = 0;
+        let success = unsafe {
+            DeviceIoControl(
+                HANDLE(file.raw() as isize),
+                IOCTL_SCSI_GET_ADDRESS,
+                None,
+                0,
+                Some(&mut scsi_address as *mut _ as _),
+                size_of::<SCSI_ADDRESS>() as u32,
+                Some(&mut bytes_returned),
+                None,
+            )
+        };
+
+        if success == false {
+            Err(io::Error::last_os_error())?;
+        }
+
+        if bytes_returned == 0 {
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+impl Transport for NativeTransport {
+    #[cfg(target_os = "linux")]
+    fn claim_disk(&self) -> crate::Result<Option<File>> {
+        crate::discovery::claim_disk(Path::new(self.file_descriptor.path())).map(Some)
+    }
+
+    /// Mounted volumes aren't looked up here, so it can't be told whether the disk is in use.
+    #[cfg(not(target_os = "linux"))]
+    fn claim_disk(&self) -> crate::Result<Option<File>> {
+        Err(crate::Error::InUseUnknown(
+            self.file_descriptor.path().into(),
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn transport(&self, request: TransportRequest) -> TransportResult {
+        use crate::os::sg_io_header::SgIoHeader;
+
+        if self.bsg {
+            let mut sg_header = bsg_header(request);
+            let ioctl_result = self.sg_io(&mut sg_header);
+            return sg_header.transport_result(ioctl_result);
+        }
+
+        let mut sg_header = SgIoHeader::new(
+            request.direction,
+            request.command,
+            request.data,
+            request.sense,
+            request.timeout,
+            0,
+        );
+
+        let ioctl_result = self.sg_io(&mut sg_header);
+        sg_header.transport_result(ioctl_result)
+    }
+
+    /// Block devices and sg nodes take the buffers as an iovec list.
+    /// bsg ignores iovec lists, so the buffers are copied through a single one there.
+    /// Bidirectional commands go through bsg as well, sg v3 has a single buffer.
+    #[cfg(target_os = "linux")]
+    fn transport_vectored(&self, request: super::VectoredRequest) -> TransportResult {
+        use nix::libc;
+
+        use crate::{os::sg_io_header::SgIoHeader, DataDirection};
+
+        /// UIO_MAXIOV, the most buffers the kernel takes in one iovec list
+        const MAX_IOVEC_COUNT: usize = 1024;
+
+        let direction = request.direction();
+        if direction == DataDirection::ToFromDevice {
+            return self.transport_bidirectional(request);
+        }
+
+        if self.bsg {
+            return super::transport_buffered(self, request);
+        }
+
+        if request.data_out.len() > MAX_IOVEC_COUNT || request.data_in.len() > MAX_IOVEC_COUNT {
+            return TransportResult::failed(-libc::EINVAL);
+        }
+
+        match direction {
+            DataDirection::ToDevice => {
+                // The kernel only reads the list and the buffers, it's the same as a mutable one
+                let mut iovecs = request.data_out.to_vec();
+                let mut sg_header = SgIoHeader::with_iovecs(
+                    direction,
+                    request.command,
+                    &mut iovecs,
+                    request.sense,
+                    request.timeout,
+                    0,
+                );
+
+                let ioctl_result = self.sg_io(&mut sg_header);
+                sg_header.transport_result(ioctl_result)
+            }
+            _ => {
+                let mut sg_header = SgIoHeader::with_iovecs(
+                    direction,
+                    request.command,
+                    request.data_in,
+                    request.sense,
+                    request.timeout,
+                    0,
+                );
+
+                let ioctl_result = self.sg_io(&mut sg_header);
+                sg_header.transport_result(ioctl_result)
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn transport(&self, request: TransportRequest) -> TransportResult {
+        use std::{mem::size_of_val, time::Instant};
+
+        use windows::Win32::{
+            Foundation::{ERROR_GEN_FAILURE, HANDLE},
+            Storage::IscsiDisc::{
+                IOCTL_SCSI_PASS_THROUGH_DIRECT, SCSI_IOCTL_DATA_BIDIRECTIONAL, SCSI_IOCTL_DATA_IN,
+                SCSI_IOCTL_DATA_OUT, SCSI_IOCTL_DATA_UNSPECIFIED,
+            },
+            System::IO::DeviceIoControl,
+        };
+
+        use crate::{
+            command::sense::{BytesSenseBuffer, Sense},
+            os::scsi_pass_through_header::ScsiPassThroughDirectWrapper,
+            DriverStatus, MaskedStatus,
+        };
+
+        const MAX_COMMAND_LENGTH: usize = 16;
+
+        if request.command.len() > MAX_COMMAND_LENGTH {
+            panic!(
+                "Current command length is {}, max command length is {}",
+                request.command.len(),
+                MAX_COMMAND_LENGTH
+            );
+        }
+
+        let mut header = ScsiPassThroughDirectWrapper::<BytesSenseBuffer>::default();
+        let address_of_header = std::ptr::addr_of!(header) as usize;
+        let sense_length = usize::min(request.sense.len(), header.sense.len());
+        let mut spt = &mut header.scsi_pass_through;
+        spt.Length = size_of_val(spt) as u16;
+        spt.CdbLength = request.command.len() as u8;
+        spt.SenseInfoLength = sense_length as u8;
+        spt.DataIn = match request.direction {
+            crate::DataDirection::None => SCSI_IOCTL_DATA_UNSPECIFIED,
+            crate::DataDirection::ToDevice => SCSI_IOCTL_DATA_OUT,
+            crate::DataDirection::FromDevice => SCSI_IOCTL_DATA_IN,
+            crate::DataDirection::ToFromDevice => SCSI_IOCTL_DATA_BIDIRECTIONAL,
+            crate::DataDirection::Unknown => SCSI_IOCTL_DATA_UNSPECIFIED,
+        } as u8;
+
+        spt.DataTransferLength = request.data.len() as u32;
+
+        spt.TimeOutValue = match request
+            .timeout
+            .as_secs()
+            .clamp(u32::MIN as u64, u32::MAX as u64)
+        {
+            0 => 1,
+            n => n as u32,
+        };
+
+        spt.DataBuffer = request.data.as_mut_ptr() as _;
+
+        spt.SenseInfoOffset =
+            (std::ptr::addr_of!(header.sense) as usize - address_of_header) as u32;
+
+        spt.Cdb[..request.command.len()].copy_from_slice(request.command);
+
+        let mut bytes_returned = 0;
+
+        let start = Instant::now();
+        let success = unsafe {
+            DeviceIoControl(
+                HANDLE(self.file_descriptor.raw() as isize),
+                IOCTL_SCSI_PASS_THROUGH_DIRECT,
+                Some(&header as *const _ as _),
+                size_of_val(&header) as u32,
+                Some(&mut header as *mut _ as _),
+                size_of_val(&header) as u32,
+                Some(&mut bytes_returned),
+                None,
+            )
+        };
+
+        let duration = start.elapsed();
+
+        let ioctl_result = match success.as_bool() {
+            true => 0,
+            false => -io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or(ERROR_GEN_FAILURE.0 as i32),
+        };
+
+        let transfered_sense_length = usize::min(
+            header.scsi_pass_through.SenseInfoLength as usize,
+            sense_length,
+        );
+        request.sense[..transfered_sense_length]
+            .copy_from_slice(&header.sense.as_byte_slice()[..transfered_sense_length]);
+
+        TransportResult {
+            ioctl_result,
+            transfered_data_length: header.scsi_pass_through.DataTransferLength as usize,
+            transfered_sense_length,
+            masked_status: MaskedStatus::from_bits_truncate(
+                header.scsi_pass_through.ScsiStatus >> 1,
+            ),
+            host_status: crate::HostStatus::Ok,
+            driver_status: DriverStatus::OK,
+            duration,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl NativeTransport {
+    /// Sends and receives through the bsg node of the disk, opened for the command if it isn't
+    /// one already. Kernels since 5.1 fail it with EOPNOTSUPP.
+    fn transport_bidirectional(&self, request: super::VectoredRequest) -> TransportResult {
+        use crate::os::sg_io_v4_header::SgIoV4Header;
+
+        let opened;
+        let bsg = match self.bsg {
+            true => self,
+            false => match self.open_bsg() {
+                Ok(transport) => {
+                    opened = transport;
+                    &opened
+                }
+                Err(ioctl_result) => return TransportResult::failed(ioctl_result),
+            },
+        };
+
+        let data_out = super::gather(request.data_out);
+        let mut data_in = vec![0; request.data_in.iter().map(|buffer| buffer.len()).sum()];
+        let mut sg_header = SgIoV4Header::bidirectional(
+            request.command,
+            &data_out,
+            &mut data_in,
+            request.sense,
+            request.timeout,
+        );
+        let ioctl_result = bsg.sg_io(&mut sg_header);
+        let result = sg_header.transport_result(ioctl_result);
+
+        super::scatter(&data_in, request.data_in);
+        result
+    }
+
+    /// Returns a negated os error code on failure.
+    fn open_bsg(&self) -> Result<NativeTransport, i32> {
+        let path = crate::discovery::bsg_path(Path::new(self.file_descriptor.path()));
+        match path {
+            Ok(Some(path)) => NativeTransport::open(&path),
+            Ok(None) => return Err(super::NOT_SUPPORTED),
+            Err(error) => Err(error),
+        }
+        .map_err(|error| match error {
+            crate::Error::IO(error) => -error.raw_os_error().unwrap_or(nix::libc::EIO),
+            _ => super::NOT_SUPPORTED,
+        })
+    }
+}
+
+/// Always a flat transfer, bsg treats the data pointer as one buffer.
+#[cfg(target_os = "linux")]
+fn bsg_header(request: TransportRequest) -> crate::os::sg_io_v4_header::SgIoV4Header {
+    crate::os::sg_io_v4_header::SgIoV4Header::new(
+        request.direction,
+        request.command,
+        request.data,
+        request.sense,
+        request.timeout,
+    )
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{
+        cell::RefCell,
+        io::{IoSlice, IoSliceMut},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{transport::transport_buffered, DataDirection, VectoredRequest};
+
+    /// (iovec count, pointer, length)
+    type DataFields = (u32, u64, u32);
+
+    /// Builds the header a bsg node would get and keeps its data out and data in fields.
+    #[derive(Default)]
+    struct BsgHeaders(RefCell<Vec<(DataFields, DataFields)>>);
+
+    impl Transport for BsgHeaders {
+        fn transport(&self, request: TransportRequest) -> TransportResult {
+            let header = bsg_header(request);
+            self.0.borrow_mut().push((
+                (
+                    header.data_out_iovec_count,
+                    header.data_out,
+                    header.data_out_length,
+                ),
+                (
+                    header.data_in_iovec_count,
+                    header.data_in,
+                    header.data_in_length,
+                ),
+            ));
+            TransportResult::good(header.data_in_length as usize)
+        }
+    }
+
+    #[test]
+    fn bsg_never_gets_iovecs() {
+        let transport = BsgHeaders::default();
+        let command = [0x88; 16];
+        let mut sense = [0; 32];
+        let mut first = [0; 512];
+        let mut second = [0; 1024];
+        let mut data_in = [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)];
+        let data_in_list = data_in.as_ptr() as usize as u64;
+
+        transport_buffered(
+            &transport,
+            VectoredRequest {
+                command: &command,
+                data_out: &[],
+                data_in: &mut data_in,
+                sense: &mut sense,
+                timeout: Duration::from_secs(1),
+            },
+        );
+
+        let data_out = [IoSlice::new(&[1; 8]), IoSlice::new(&[2; 16])];
+        let data_out_list = data_out.as_ptr() as usize as u64;
+        transport_buffered(
+            &transport,
+            VectoredRequest {
+                command: &command,
+                data_out: &data_out,
+                data_in: &mut [],
+                sense: &mut sense,
+                timeout: Duration::from_secs(1),
+            },
+        );
+
+        let headers = transport.0.borrow().clone();
+        let ((out_count, _, out_length), (in_count, in_pointer, in_length)) = headers[0];
+        assert_eq!(
+            (out_count, out_length, in_count, in_length),
+            (0, 0, 0, 1536)
+        );
+        assert_ne!(in_pointer, data_in_list);
+        let ((out_count, out_pointer, out_length), (in_count, _, in_length)) = headers[1];
+        assert_eq!((out_count, out_length, in_count, in_length), (0, 24, 0, 0));
+        assert_ne!(out_pointer, data_out_list);
+
+        // the same header for a plain command
+        let mut data = [0; 36];
+        transport.transport(TransportRequest {
+            direction: DataDirection::FromDevice,
+            command: &command[..6],
+            data: &mut data,
+            sense: &mut sense,
+            timeout: Duration::from_secs(1),
+        });
+        assert_eq!(transport.0.borrow()[2].1 .1, data.as_ptr() as usize as u64);
+    }
+}
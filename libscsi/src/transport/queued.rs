@@ -0,0 +1,436 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    future::Future,
+    io,
+    mem::size_of_val,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    thread,
+    time::Duration,
+};
+
+use nix::libc;
+
+use crate::{file_descriptor::FileDescriptor, os::sg_io_header::SgIoHeader, DataDirection};
+
+use super::{NativeTransport, Transport, TransportRequest, TransportResult};
+
+/// A command with buffers of its own, the kernel fills them when the response is read,
+/// which may be after the future waiting for it was dropped.
+#[derive(Debug, Clone)]
+pub struct QueuedRequest {
+    pub direction: DataDirection,
+    pub command: Vec<u8>,
+    /// Sent to the device, or filled by it depending on `direction`
+    pub data: Vec<u8>,
+    /// Its length is the maximum sense length
+    pub sense: Vec<u8>,
+    pub timeout: Duration,
+}
+
+#[derive(Debug)]
+pub struct QueuedResponse {
+    pub data: Vec<u8>,
+    pub sense: Vec<u8>,
+    pub result: TransportResult,
+}
+
+#[derive(Debug)]
+struct Slot {
+    request: QueuedRequest,
+    result: Option<TransportResult>,
+    waker: Option<Waker>,
+    /// Nobody waits for it anymore, but the command is still in flight.
+    /// The buffers are kept until the kernel is done with them
+    abandoned: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    slots: HashMap<i32, Slot>,
+    next_pack_id: i32,
+    reader_running: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    file_descriptor: FileDescriptor,
+    state: Mutex<State>,
+}
+
+/// The write()/read() interface of the SCSI Generic driver.
+/// Commands are queued with write() and tagged with a pack id, a reader thread collects
+/// the responses with read() while any command is outstanding.
+///
+/// Unlike SG_IO, several commands can be in flight on one device,
+/// see `submit` and `Scsi::execute_command_async`.
+/// Queued commands can't be cancelled, the sg driver has no way to abort a single one.
+/// Long operations are stopped with commands of their own, like
+/// [`crate::command::SelfTestCode::AbortBackground`], or split into shorter commands
+/// that are no longer sent.
+#[derive(Debug)]
+pub struct QueuedTransport {
+    shared: Arc<Shared>,
+}
+
+impl QueuedTransport {
+    /// Only sg nodes (/dev/sgN) support queued commands, a block device is mapped to its sg node.
+    pub fn open<P: AsRef<Path> + ?Sized>(path: &P) -> crate::Result<QueuedTransport> {
+        let path = path.as_ref();
+        let sg_path = crate::discovery::sg_path(path)?.unwrap_or_else(|| path.to_owned());
+
+        let mut options = OpenOptions::new();
+        options.read(true).write(true);
+        let file_descriptor = FileDescriptor::open(&sg_path, options)?;
+
+        if !file_descriptor.is_char()? || !NativeTransport::is_scsi_device(&file_descriptor)? {
+            return Err(crate::Error::NotScsiDevice(path.to_owned()));
+        }
+
+        Ok(QueuedTransport::with_file_descriptor(file_descriptor))
+    }
+
+    fn with_file_descriptor(file_descriptor: FileDescriptor) -> QueuedTransport {
+        QueuedTransport {
+            shared: Arc::new(Shared {
+                file_descriptor,
+                state: Mutex::new(State::default()),
+            }),
+        }
+    }
+
+    /// Queues `request` and returns a future of its response.
+    /// There's no way to cancel a queued command, dropping the future only stops waiting
+    /// for it, the device still carries it out and its response is thrown away.
+    pub fn submit(&self, mut request: QueuedRequest) -> ResponseFuture {
+        let mut state = self.shared.state.lock().unwrap();
+        let pack_id = state.next_pack_id;
+        // Negative pack ids mean any response on read()
+        state.next_pack_id = pack_id.wrapping_add(1) & i32::MAX;
+
+        // Written with the lock held, so the reader can't see the response before its slot
+        if let Err(ioctl_result) = self.shared.write(pack_id, &mut request) {
+            return ResponseFuture {
+                shared: self.shared.clone(),
+                pack_id,
+                failed: Some(QueuedResponse {
+                    data: request.data,
+                    sense: request.sense,
+                    result: TransportResult::failed(ioctl_result),
+                }),
+                done: true,
+            };
+        }
+
+        state.slots.insert(
+            pack_id,
+            Slot {
+                request,
+                result: None,
+                waker: None,
+                abandoned: false,
+            },
+        );
+
+        if !state.reader_running {
+            state.reader_running = true;
+            let shared = self.shared.clone();
+            thread::spawn(move || shared.read_responses());
+        }
+
+        ResponseFuture {
+            shared: self.shared.clone(),
+            pack_id,
+            failed: None,
+            done: false,
+        }
+    }
+}
+
+impl Shared {
+    /// Returns a negated os error code on failure.
+    fn write(&self, pack_id: i32, request: &mut QueuedRequest) -> Result<(), i32> {
+        let header = SgIoHeader::new(
+            request.direction,
+            &request.command,
+            &mut request.data,
+            &mut request.sense,
+            request.timeout,
+            pack_id,
+        );
+
+        let written = unsafe {
+            libc::write(
+                self.file_descriptor.raw(),
+                &header as *const _ as *const libc::c_void,
+                size_of_val(&header),
+            )
+        };
+
+        match written {
+            n if n < 0 => Err(last_os_error()),
+            n if n as usize != size_of_val(&header) => Err(-libc::EIO),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_responses(self: Arc<Self>) {
+        loop {
+            // Stop while nothing is outstanding, read() would block forever
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.slots.values().all(|slot| slot.result.is_some()) {
+                    state.reader_running = false;
+                    return;
+                }
+            }
+
+            let mut header = SgIoHeader::new(
+                DataDirection::None,
+                &[],
+                &mut [],
+                &mut [],
+                Duration::ZERO,
+                -1,
+            );
+            let read = unsafe {
+                libc::read(
+                    self.file_descriptor.raw(),
+                    &mut header as *mut _ as *mut libc::c_void,
+                    size_of_val(&header),
+                )
+            };
+
+            let error = match read {
+                n if n < 0 => last_os_error(),
+                n if n as usize != size_of_val(&header) => -libc::EIO,
+                _ => {
+                    let mut state = self.state.lock().unwrap();
+                    state.complete(header.pack_id, header.transport_result(0));
+                    continue;
+                }
+            };
+
+            if error == -libc::EINTR {
+                continue;
+            }
+
+            // The device is gone, none of the outstanding commands will be answered
+            let mut state = self.state.lock().unwrap();
+            state.fail_outstanding(error);
+            state.reader_running = false;
+            return;
+        }
+    }
+}
+
+impl State {
+    /// Responses that were already read are kept, their futures just weren't polled yet.
+    fn fail_outstanding(&mut self, ioctl_result: i32) {
+        let pack_ids: Vec<_> = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.result.is_none())
+            .map(|(pack_id, _)| *pack_id)
+            .collect();
+        for pack_id in pack_ids {
+            self.complete(pack_id, TransportResult::failed(ioctl_result));
+        }
+    }
+
+    fn complete(&mut self, pack_id: i32, result: TransportResult) {
+        let Some(slot) = self.slots.get_mut(&pack_id) else {
+            return;
+        };
+
+        if slot.abandoned {
+            self.slots.remove(&pack_id);
+            return;
+        }
+
+        slot.result = Some(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn last_os_error() -> i32 {
+    -io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO)
+}
+
+/// Resolves once the response of a queued command was read, works with any executor.
+/// Dropping it doesn't cancel the command, see [`QueuedTransport`].
+#[derive(Debug)]
+pub struct ResponseFuture {
+    shared: Arc<Shared>,
+    pack_id: i32,
+    failed: Option<QueuedResponse>,
+    done: bool,
+}
+
+impl Future for ResponseFuture {
+    type Output = QueuedResponse;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(response) = this.failed.take() {
+            return Poll::Ready(response);
+        }
+
+        let mut state = this.shared.state.lock().unwrap();
+        let slot = state
+            .slots
+            .get_mut(&this.pack_id)
+            .expect("ResponseFuture polled after completion");
+
+        if slot.result.is_none() {
+            slot.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let slot = state.slots.remove(&this.pack_id).unwrap();
+        this.done = true;
+        Poll::Ready(QueuedResponse {
+            data: slot.request.data,
+            sense: slot.request.sense,
+            result: slot.result.unwrap(),
+        })
+    }
+}
+
+impl Drop for ResponseFuture {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        match state.slots.get_mut(&self.pack_id) {
+            Some(slot) if slot.result.is_some() => {
+                state.slots.remove(&self.pack_id);
+            }
+            Some(slot) => {
+                slot.abandoned = true;
+                slot.waker = None;
+            }
+            None => (),
+        }
+    }
+}
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on(mut future: ResponseFuture) -> QueuedResponse {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(response) = Pin::new(&mut future).poll(&mut context) {
+            return response;
+        }
+        thread::park();
+    }
+}
+
+impl Transport for QueuedTransport {
+    fn claim_disk(&self) -> crate::Result<Option<File>> {
+        let path = Path::new(self.shared.file_descriptor.path());
+        crate::discovery::claim_disk(path).map(Some)
+    }
+
+    fn transport(&self, request: TransportRequest) -> TransportResult {
+        let response = block_on(self.submit(QueuedRequest {
+            direction: request.direction,
+            command: request.command.to_vec(),
+            data: request.data.to_vec(),
+            sense: vec![0; request.sense.len()],
+            timeout: request.timeout,
+        }));
+
+        request.data.copy_from_slice(&response.data);
+        request.sense.copy_from_slice(&response.sense);
+        response.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> QueuedRequest {
+        QueuedRequest {
+            direction: DataDirection::FromDevice,
+            command: vec![0x12, 0, 0, 0, 36, 0],
+            data: vec![0xaa; 36],
+            sense: vec![0; 32],
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn device_gone() {
+        // write() to /dev/null succeeds and read() returns nothing, like a vanished device
+        let mut options = OpenOptions::new();
+        options.read(true).write(true);
+        let transport = QueuedTransport::with_file_descriptor(
+            FileDescriptor::open("/dev/null", options).unwrap(),
+        );
+
+        let responses: Vec<_> = (0..3)
+            .map(|_| transport.submit(request()))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(block_on)
+            .collect();
+        for response in responses {
+            assert_eq!(response.result.ioctl_result, -libc::EIO);
+            assert_eq!(response.data, vec![0xaa; 36]);
+        }
+
+        // dropped before completion
+        drop(transport.submit(request()));
+
+        while transport.shared.state.lock().unwrap().reader_running {
+            thread::yield_now();
+        }
+        assert!(transport.shared.state.lock().unwrap().slots.is_empty());
+        assert_eq!(transport.shared.state.lock().unwrap().next_pack_id, 4);
+    }
+
+    #[test]
+    fn read_error_keeps_arrived_responses() {
+        let slot = |result, abandoned| Slot {
+            request: request(),
+            result,
+            waker: None,
+            abandoned,
+        };
+        let mut state = State::default();
+        state
+            .slots
+            .insert(0, slot(Some(TransportResult::good(36)), false));
+        state.slots.insert(1, slot(None, false));
+        state.slots.insert(2, slot(None, true));
+
+        state.fail_outstanding(-libc::ENODEV);
+
+        assert_eq!(state.slots.len(), 2);
+        let arrived = state.slots[&0].result.as_ref().unwrap();
+        assert_eq!(arrived.ioctl_result, 0);
+        assert_eq!(arrived.transfered_data_length, 36);
+        let failed = state.slots[&1].result.as_ref().unwrap();
+        assert_eq!(failed.ioctl_result, -libc::ENODEV);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::Mutex,
+};
+
+use super::{TraceRecord, Transport, TransportRequest, TransportResult};
+
+/// Serves the responses of a recorded trace, in the order they were recorded.
+///
+/// Panics if a command doesn't match the next recorded one, a diverged replay is a broken fixture.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    records: Mutex<VecDeque<TraceRecord>>,
+}
+
+impl ReplayTransport {
+    pub fn open<P: AsRef<Path> + ?Sized>(path: &P) -> io::Result<ReplayTransport> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<ReplayTransport> {
+        Ok(Self::from_records(TraceRecord::read_all(reader)?))
+    }
+
+    pub fn from_records(records: Vec<TraceRecord>) -> ReplayTransport {
+        ReplayTransport {
+            records: Mutex::new(records.into()),
+        }
+    }
+
+    /// Number of recorded commands that haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn transport(&self, request: TransportRequest) -> TransportResult {
+        let record = match self.records.lock().unwrap().pop_front() {
+            Some(record) => record,
+            None => panic!(
+                "Trace exhausted, unexpected command {:02x?}",
+                request.command
+            ),
+        };
+
+        if record.command != request.command || record.direction != request.direction {
+            panic!(
+                "Replay diverged, expected command {:02x?} ({:?}), got {:02x?} ({:?})",
+                record.command, record.direction, request.command, request.direction
+            );
+        }
+
+        let transfered_data_length = usize::min(record.data_in.len(), request.data.len());
+        request.data[..transfered_data_length]
+            .copy_from_slice(&record.data_in[..transfered_data_length]);
+
+        let transfered_sense_length = usize::min(record.sense.len(), request.sense.len());
+        request.sense[..transfered_sense_length]
+            .copy_from_slice(&record.sense[..transfered_sense_length]);
+
+        TransportResult {
+            ioctl_result: record.ioctl_result,
+            transfered_data_length: if record.data_in.is_empty() {
+                record.data_out.len()
+            } else {
+                transfered_data_length
+            },
+            transfered_sense_length,
+            masked_status: record.masked_status,
+            host_status: record.host_status,
+            driver_status: record.driver_status,
+            duration: record.duration,
+        }
+    }
+}
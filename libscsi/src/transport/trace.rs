@@ -0,0 +1,347 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, BufRead, Write},
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{DataDirection, DriverStatus, HostStatus, MaskedStatus};
+
+/// One command and the device's response to it, as stored in a trace file.
+///
+/// A trace file is plain text, records are separated by an empty line:
+/// ```text
+/// command: c0 45 00 00 00 00 00 00 30 00
+/// direction: FromDevice
+/// data_out:
+/// data_in: 45 00 00 02 28 00 00 20 ...
+/// sense:
+/// masked_status: 00
+/// host_status: 00
+/// driver_status: 00
+/// duration: 2
+/// ioctl_result: 0
+/// ```
+#[derive(Debug, Clone)]
+pub struct TraceRecord {
+    pub command: Vec<u8>,
+    pub direction: DataDirection,
+    pub data_out: Vec<u8>,
+    pub data_in: Vec<u8>,
+    pub sense: Vec<u8>,
+    pub masked_status: MaskedStatus,
+    pub host_status: HostStatus,
+    pub driver_status: DriverStatus,
+    pub duration: Duration,
+    pub ioctl_result: i32,
+}
+
+impl TraceRecord {
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "command: {}", to_hex(&self.command))?;
+        writeln!(writer, "direction: {:?}", self.direction)?;
+        writeln!(writer, "data_out: {}", to_hex(&self.data_out))?;
+        writeln!(writer, "data_in: {}", to_hex(&self.data_in))?;
+        writeln!(writer, "sense: {}", to_hex(&self.sense))?;
+        writeln!(writer, "masked_status: {:02x}", self.masked_status.bits())?;
+        writeln!(writer, "host_status: {:02x}", u16::from(self.host_status))?;
+        writeln!(writer, "driver_status: {:02x}", self.driver_status.bits())?;
+        writeln!(writer, "duration: {}", self.duration.as_millis())?;
+        writeln!(writer, "ioctl_result: {}", self.ioctl_result)?;
+        writeln!(writer)
+    }
+
+    pub fn read_all<R: BufRead>(reader: R) -> io::Result<Vec<TraceRecord>> {
+        let mut records = vec![];
+        let mut fields = vec![];
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+
+            if line.is_empty() {
+                if !fields.is_empty() {
+                    records.push(Self::parse(&fields)?);
+                    fields.clear();
+                }
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid_data(format!("Invalid trace line: {}", line)))?;
+            fields.push((key.trim().to_owned(), value.trim().to_owned()));
+        }
+
+        if !fields.is_empty() {
+            records.push(Self::parse(&fields)?);
+        }
+
+        Ok(records)
+    }
+
+    fn parse(fields: &[(String, String)]) -> io::Result<TraceRecord> {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| invalid_data(format!("Missing trace field: {}", name)))
+        };
+
+        let direction = match field("direction")? {
+            "None" => DataDirection::None,
+            "ToDevice" => DataDirection::ToDevice,
+            "FromDevice" => DataDirection::FromDevice,
+            "ToFromDevice" => DataDirection::ToFromDevice,
+            "Unknown" => DataDirection::Unknown,
+            other => return Err(invalid_data(format!("Invalid direction: {}", other))),
+        };
+
+        Ok(TraceRecord {
+            command: from_hex(field("command")?)?,
+            direction,
+            data_out: from_hex(field("data_out")?)?,
+            data_in: from_hex(field("data_in")?)?,
+            sense: from_hex(field("sense")?)?,
+            masked_status: MaskedStatus::from_bits_truncate(
+                parse_hex(field("masked_status")?)? as u8
+            ),
+            host_status: parse_hex(field("host_status")?)?.into(),
+            driver_status: DriverStatus::from_bits_truncate(parse_hex(field("driver_status")?)?),
+            duration: Duration::from_millis(
+                field("duration")?
+                    .parse()
+                    .map_err(|e| invalid_data(format!("Invalid duration: {}", e)))?,
+            ),
+            ioctl_result: field("ioctl_result")?
+                .parse()
+                .map_err(|e| invalid_data(format!("Invalid ioctl result: {}", e)))?,
+        })
+    }
+}
+
+#[cfg(test)]
+impl TraceRecord {
+    /// A command the device completed with GOOD status, for replay tests.
+    pub(crate) fn good(command: &[u8], direction: DataDirection, data_in: &[u8]) -> TraceRecord {
+        TraceRecord {
+            command: command.to_vec(),
+            direction,
+            data_out: vec![],
+            data_in: data_in.to_vec(),
+            sense: vec![],
+            masked_status: MaskedStatus::GOOD,
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::OK,
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        }
+    }
+
+    /// A command the device rejected with fixed format sense data.
+    pub(crate) fn check_condition(
+        command: &[u8],
+        direction: DataDirection,
+        (sense_key, asc, ascq): (u8, u8, u8),
+    ) -> TraceRecord {
+        TraceRecord {
+            sense: vec![
+                0x70, 0x00, sense_key, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, asc,
+                ascq, 0x00, 0x00, 0x00, 0x00,
+            ],
+            masked_status: MaskedStatus::CHECK_CONDITION,
+            driver_status: DriverStatus::SENSE,
+            ..TraceRecord::good(command, direction, &[])
+        }
+    }
+}
+
+/// Vendor specific security commands (unlock, change passphrase, reset key),
+/// their data out carries the password blob, which unlocks the drive all by itself.
+const SECURITY_OPERATION_CODE: u8 = 0xc1;
+
+/// Appends every executed command to a trace writer.
+/// Traces get attached to bug reports, so security payloads are zeroed, keeping their length.
+/// Stops at the first failed write, the error is kept for [`Recorder::finish`].
+pub(crate) struct Recorder(Mutex<Result<Box<dyn Write + Send>, io::Error>>);
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Recorder {
+        Recorder(Mutex::new(Ok(Box::new(writer))))
+    }
+
+    pub fn record(&self, record: &TraceRecord) {
+        let record = redacted(record);
+        let mut state = self.0.lock().unwrap();
+        let Ok(writer) = &mut *state else {
+            return;
+        };
+
+        if let Err(e) = record.write_to(&mut **writer).and_then(|_| writer.flush()) {
+            *state = Err(e);
+        }
+    }
+
+    /// The error that stopped the recording, if any.
+    pub fn finish(self) -> io::Result<()> {
+        self.0.into_inner().unwrap().map(|_| ())
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Recorder")
+    }
+}
+
+fn redacted(record: &TraceRecord) -> Cow<'_, TraceRecord> {
+    if record.command.first() != Some(&SECURITY_OPERATION_CODE) {
+        return Cow::Borrowed(record);
+    }
+
+    let mut record = record.clone();
+    record.data_out.fill(0);
+    Cow::Owned(record)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn from_hex(text: &str) -> io::Result<Vec<u8>> {
+    text.split_whitespace()
+        .map(|b| {
+            u8::from_str_radix(b, 16).map_err(|_| invalid_data(format!("Invalid hex byte: {}", b)))
+        })
+        .collect()
+}
+
+fn parse_hex(text: &str) -> io::Result<u16> {
+    u16::from_str_radix(text, 16).map_err(|_| invalid_data(format!("Invalid hex number: {}", text)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let record = TraceRecord {
+            command: vec![0x12, 0x00, 0x00, 0x00, 0x20, 0x00],
+            direction: DataDirection::FromDevice,
+            data_out: vec![],
+            data_in: vec![0x00, 0x00, 0x06, 0x02],
+            sense: vec![0x70, 0x00, 0x05],
+            masked_status: MaskedStatus::CHECK_CONDITION,
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::SENSE,
+            duration: Duration::from_millis(3),
+            ioctl_result: 0,
+        };
+
+        let mut text = vec![];
+        record.write_to(&mut text).unwrap();
+        record.write_to(&mut text).unwrap();
+
+        let records = TraceRecord::read_all(text.as_slice()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].command, record.command);
+        assert_eq!(records[1].direction, record.direction);
+        assert_eq!(records[1].data_in, record.data_in);
+        assert_eq!(records[1].sense, record.sense);
+        assert_eq!(records[1].masked_status, record.masked_status);
+        assert_eq!(records[1].driver_status, record.driver_status);
+        assert_eq!(records[1].duration, record.duration);
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn security_payloads_are_redacted() {
+        let key: Vec<u8> = (1..=32).collect();
+        let mut unlock_data = vec![0x45, 0, 0, 0, 0, 0, 0, 0x20];
+        unlock_data.extend_from_slice(&key);
+        let unlock = TraceRecord {
+            command: vec![0xc1, 0xe1, 0, 0, 0, 0, 0, 0, 0x28, 0],
+            direction: DataDirection::ToDevice,
+            data_out: unlock_data,
+            data_in: vec![],
+            sense: vec![],
+            masked_status: MaskedStatus::GOOD,
+            host_status: HostStatus::Ok,
+            driver_status: DriverStatus::OK,
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        };
+        let write = TraceRecord {
+            command: vec![0x2a, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+            data_out: key.clone(),
+            ..unlock.clone()
+        };
+
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(buffer.clone());
+        recorder.record(&unlock);
+        let unlock_text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(!unlock_text.contains(&to_hex(&key[..4])));
+        recorder.record(&write);
+
+        let text = buffer.0.lock().unwrap().clone();
+        let records = TraceRecord::read_all(text.as_slice()).unwrap();
+        assert_eq!(records[0].command, unlock.command);
+        assert_eq!(records[0].data_out, vec![0; 40]);
+        // other commands are kept as they are
+        assert_eq!(records[1].data_out, key);
+    }
+    struct FailingWriter(usize);
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            self.0 += 1;
+            match self.0 {
+                1 => Err(io::Error::new(io::ErrorKind::StorageFull, "disk full")),
+                _ => panic!("Written after a failure"),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_write_stops_recording() {
+        let record = TraceRecord::good(&[0x00; 6], DataDirection::None, &[]);
+        let recorder = Recorder::new(FailingWriter(0));
+        recorder.record(&record);
+        recorder.record(&record);
+
+        let error = recorder.finish().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+        assert!(Recorder::new(SharedBuffer::default()).finish().is_ok());
+    }
+}
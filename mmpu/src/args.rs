@@ -7,10 +7,14 @@ use wd_vsc::Cipher;
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
-    /// My passport device
+    /// My passport device, a disk (/dev/sdX) or its SCSI Generic node (/dev/sgN)
     #[arg(short, long, value_hint = ValueHint::FilePath)]
     pub device: Option<PathBuf>,
 
+    /// List SCSI disks with their USB ids, driver, sg node and partitions
+    #[arg(short, long)]
+    pub list: bool,
+
     /// Show device info
     #[arg(short, long, requires = "device")]
     pub info: bool,
@@ -100,10 +104,19 @@ pub struct Cli {
     #[arg(short, long, requires = "erase")]
     pub preserve_data: bool,
 
+    /// Write zeros to every block if the drive can't erase them any faster, this takes hours
+    #[arg(long, requires = "erase", conflicts_with = "preserve_data")]
+    pub zero_fill: bool,
+
     /// Set new encryption cipher
     #[arg(short, long, requires = "eg")]
     pub cipher: Option<Cipher>,
 
+    /// Erase or remove the password even if the drive is mounted or in use,
+    /// or it can't be checked on this system
+    #[arg(long, requires = "device")]
+    pub force: bool,
+
     /// Force it to do some dangerous things
     #[arg(long)]
     pub i_know_what_i_am_doing: bool,
@@ -147,9 +160,31 @@ pub struct Cli {
     #[arg(long, requires = "device")]
     pub sleep_timer: Option<Option<u32>>,
 
-    /// Very minimum self diagnostic
+    /// Keep the virtual cd, led brightness and sleep timer settings across power cycles,
+    /// fails if the device can't save them
+    #[arg(long, requires = "device")]
+    pub save: bool,
+
+    /// Run a self-test. Without a value it's a very minimum self diagnostic.
+    /// Short and extended tests run in the background and are polled until they finish.
+    #[arg(long, requires = "device")]
+    pub self_test: Option<Option<SelfTestMode>>,
+
+    /// Show temperature, start-stop and load-unload counters, self-test results and informational exceptions
     #[arg(long, requires = "device")]
-    pub self_test: bool,
+    pub logs: bool,
+
+    /// Show the SMART health verdict and attributes of the disk inside the enclosure
+    #[arg(long, requires = "device")]
+    pub smart: bool,
+
+    /// Spin down and park the disk now, e.g. before unplugging it
+    #[arg(long, requires = "device")]
+    pub spin_down: bool,
+
+    /// Record every command sent to the device and its response to a trace file
+    #[arg(long, requires = "device", value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub record: Option<PathBuf>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -157,3 +192,11 @@ pub enum Switch {
     On,
     Off,
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SelfTestMode {
+    Short,
+    Extended,
+    /// Abort the running short or extended self-test
+    Abort,
+}
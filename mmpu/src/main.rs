@@ -1,26 +1,49 @@
 use std::{
-    fs::OpenOptions,
+    fs::{File, OpenOptions},
     io::{stdin, stdout, Read, Write},
     path::PathBuf,
+    thread,
+    time::Duration,
 };
 
 use clap::Parser;
-use libscsi::command::TestResult;
+use libscsi::{
+    additional_sense_description,
+    command::{
+        EraseMethod, IdentifyDevice, RotationRate, SelfTestCode, SelfTestProgress, SmartHealth,
+        TestResult,
+    },
+    ExclusiveClaim, RetryPolicy, Scsi,
+};
 use wd_vsc::{
     device_configuration_page, operations_page, password_utility::*, power_condition_mode_page,
-    security_block::*, Error, SecurityStatus, WdVsc, DEFAULT_ITERATION_COUNT, DEFAULT_SALT,
+    security_block::*, Error, ModeSetting, SecurityStatus, WdVsc, DEFAULT_ITERATION_COUNT,
+    DEFAULT_SALT,
 };
 mod args;
 use args::*;
 
+const SELF_TEST_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const TEMPERATURE_PAGE_CODE: u8 = 0x0d;
+const START_STOP_CYCLE_COUNTER_PAGE_CODE: u8 = 0x0e;
+const SELF_TEST_RESULTS_PAGE_CODE: u8 = 0x10;
+const INFORMATIONAL_EXCEPTIONS_PAGE_CODE: u8 = 0x2f;
+
 fn main() -> wd_vsc::Result<()> {
     let cli = Cli::parse();
 
+    if cli.list {
+        list_disks()?;
+        if cli.device.is_none() {
+            return Ok(());
+        }
+    }
+
     let salt = create_salt_blob(cli.salt)?;
     let new_salt = create_salt_blob(cli.new_salt)?;
     let old_salt = create_salt_blob(cli.old_salt)?;
 
-    let (device, status) = match cli.device {
+    let (device, status, inquiry) = match cli.device {
         Some(path) => {
             let path = if cfg!(target_os = "windows") {
                 // A small QOL for Windows users
@@ -39,22 +62,34 @@ fn main() -> wd_vsc::Result<()> {
                 path
             };
 
-            let device = libscsi::Scsi::new(&path)?;
+            let mut device = libscsi::Scsi::new_generic(&path)?;
+            // Right after plugging in the first command often fails with a unit attention
+            device.set_retry_policy(RetryPolicy::default());
+            if let Some(trace_path) = &cli.record {
+                device.start_recording(File::create(trace_path)?);
+            }
 
-            let product_name = device.inquiry_product_identification()?;
-            if !product_name.to_lowercase().contains("my passport") && !cli.i_know_what_i_am_doing {
-                return Err(wd_vsc::Error::Other(
-                    "This device doesn't seem like a my passport device. \
-                    Use --i-know-what-i-am-doing flag if you wish to continue."
-                        .to_owned(),
-                ));
+            let inquiry = device.inquiry_standard()?;
+            let is_my_passport = inquiry.vendor_identification == "WD"
+                && inquiry
+                    .product_identification
+                    .to_lowercase()
+                    .contains("my passport");
+            if !is_my_passport && !cli.i_know_what_i_am_doing {
+                return Err(wd_vsc::Error::Other(format!(
+                    "This device ({} {} {}) doesn't seem like a my passport device. \
+                    Use --i-know-what-i-am-doing flag if you wish to continue.",
+                    inquiry.vendor_identification,
+                    inquiry.product_identification,
+                    inquiry.product_revision_level
+                )));
             }
 
             let status = device.encryption_status()?;
 
-            (Some(device), Some(status))
+            (Some(device), Some(status), Some(inquiry))
         }
-        None => (None, None),
+        None => (None, None, None),
     };
 
     if let Some(password) = cli.generate_password_blob {
@@ -92,9 +127,27 @@ fn main() -> wd_vsc::Result<()> {
         }
     }
 
-    let (device, status) = (device.unwrap(), status.unwrap());
+    let (mut device, status, inquiry) = (device.unwrap(), status.unwrap(), inquiry.unwrap());
 
     if cli.info {
+        println!(
+            "Model: {} {}",
+            inquiry.vendor_identification, inquiry.product_identification
+        );
+        println!(
+            "Bridge firmware revision: {}",
+            inquiry.product_revision_level
+        );
+        if let Ok(serial_number) = device.inquiry_unit_serial_number() {
+            println!("Serial number: {}", serial_number);
+        }
+        if let Ok(Some(world_wide_name)) = device.inquiry_world_wide_name() {
+            println!("WWN: {}", world_wide_name);
+        }
+        // Not every bridge passes ATA commands through
+        if let Ok(disk) = device.ata_identify_device() {
+            print_disk_info(&disk);
+        }
         println!("Device status: {:?}", status.security_status);
         println!("Current cipher: {:?}", status.current_cipher);
         println!("Supported ciphers: {:?}", status.supported_ciphers);
@@ -178,6 +231,7 @@ fn main() -> wd_vsc::Result<()> {
     }
 
     if cli.remove_password.is_some() {
+        let _claim = claim_exclusive(&device, cli.force)?;
         change_password(
             &device,
             &status,
@@ -198,6 +252,9 @@ fn main() -> wd_vsc::Result<()> {
             }
         }
 
+        // Held until the erase is done as well
+        let _claim = claim_exclusive(&device, cli.force)?;
+
         // For getting a most recent key_reset_enabler
         let status = device.encryption_status()?;
 
@@ -207,11 +264,24 @@ fn main() -> wd_vsc::Result<()> {
         )?;
 
         if !cli.preserve_data {
-            let support_unmap = device.inquiry_unmap_support()?;
-            let max_unmap_block = device.inquiry_unmap_block_limit()?;
-            if support_unmap && max_unmap_block > 0 {
-                let cap = device.read_capacity16()?;
-                device.unmap(0, cap.logical_block_count, max_unmap_block)?;
+            let method = device
+                .erase(cli.zero_fill, |done, total| {
+                    print!("\rErasing: {}%", done * 100 / total.max(1));
+                    let _ = stdout().flush();
+                })
+                .map_err(|error| match error {
+                    // the key is gone already, so is the data as far as anyone can read it
+                    libscsi::Error::EraseNotSupported(_) => {
+                        Error::Other(format!("{} Use --zero-fill to do it anyway.", error))
+                    }
+                    error => error.into(),
+                })?;
+            println!();
+            match method {
+                EraseMethod::Unmap => println!("Erased with UNMAP"),
+                EraseMethod::WriteSameUnmap => println!("Erased with WRITE SAME and UNMAP"),
+                EraseMethod::WriteSame => println!("Erased with WRITE SAME"),
+                EraseMethod::ZeroFill => println!("Erased by writing zeros"),
             }
         }
     }
@@ -245,15 +315,20 @@ fn main() -> wd_vsc::Result<()> {
                     Switch::Off => false,
                 };
 
-                device_configuration_page::set_virtual_cd_status(&device, enable_cd)?;
+                device_configuration_page::set_virtual_cd_status(&device, enable_cd, cli.save)?;
             }
             None => {
-                let status = device_configuration_page::get_virtual_cd_status(&device)?;
-                print!("virtual cd status: ");
-                match status {
-                    true => println!("On"),
-                    false => println!("Off"),
-                }
+                let setting = device_configuration_page::get_virtual_cd_setting(&device)?;
+                let on_off = |status| match status {
+                    true => "On",
+                    false => "Off",
+                };
+                println!(
+                    "virtual cd status: {} (default: {}{})",
+                    on_off(setting.current),
+                    on_off(setting.default),
+                    not_changeable(&setting),
+                );
             }
         }
     }
@@ -266,12 +341,15 @@ fn main() -> wd_vsc::Result<()> {
                     "Device need to be unlocked in order to change led brightness",
                 )?;
 
-                operations_page::set_led_brightness(&device, led_brightness)?;
+                operations_page::set_led_brightness(&device, led_brightness, cli.save)?;
             }
             None => {
+                let setting = operations_page::get_led_brightness_setting(&device)?;
                 println!(
-                    "led brightness: {}",
-                    operations_page::get_led_brightness(&device)?
+                    "led brightness: {} (default: {}{})",
+                    setting.current,
+                    setting.default,
+                    not_changeable(&setting),
                 );
             }
         }
@@ -284,30 +362,335 @@ fn main() -> wd_vsc::Result<()> {
                     &status.security_status,
                     "Device need to be unlocked in order to change sleep timer",
                 )?;
-                power_condition_mode_page::set_sleep_timer(&device, sleep_timer)?;
+                power_condition_mode_page::set_sleep_timer(&device, sleep_timer, cli.save)?;
             }
             None => {
-                let sleep_timer = power_condition_mode_page::get_sleep_timer(&device)?;
-                print!("sleep timer: ");
-                match sleep_timer {
-                    0 => println!("0 (disabled)"),
-                    1 => println!("1 second"),
-                    timer => println!("{} seconds", timer),
+                let setting = power_condition_mode_page::get_sleep_timer_setting(&device)?;
+                let describe = |sleep_timer| match sleep_timer {
+                    0 => "0 (disabled)".to_owned(),
+                    1 => "1 second".to_owned(),
+                    timer => format!("{} seconds", timer),
+                };
+                println!(
+                    "sleep timer: {} (default: {}{})",
+                    describe(setting.current),
+                    describe(setting.default),
+                    not_changeable(&setting),
+                );
+            }
+        }
+    }
+
+    if let Some(self_test) = cli.self_test {
+        match self_test {
+            None => match device.send_diagnostic()? {
+                TestResult::Ok => println!("Ok"),
+                TestResult::HardwareError => println!("Hardware Error!"),
+            },
+            Some(SelfTestMode::Abort) => {
+                device.start_self_test(SelfTestCode::AbortBackground)?;
+                println!("Self-test aborted");
+            }
+            Some(mode) => {
+                let self_test_code = match mode {
+                    SelfTestMode::Short => SelfTestCode::BackgroundShort,
+                    _ => SelfTestCode::BackgroundExtended,
+                };
+                run_self_test(&device, self_test_code)?;
+            }
+        }
+    }
+
+    if cli.logs {
+        print_log_pages(&device)?;
+    }
+
+    if cli.smart {
+        print_smart(&device)?;
+    }
+
+    if cli.spin_down {
+        device.spin_down()?;
+    }
+
+    // The commands went through even if the trace couldn't be written
+    device.stop_recording()?;
+
+    Ok(())
+}
+
+fn run_self_test(device: &Scsi, self_test_code: SelfTestCode) -> wd_vsc::Result<()> {
+    if let TestResult::HardwareError = device.start_self_test(self_test_code)? {
+        println!("Hardware Error!");
+        return Ok(());
+    }
+
+    let result = loop {
+        match device.self_test_progress()? {
+            SelfTestProgress::InProgress(progress) => {
+                match progress {
+                    Some(progress) => print!(
+                        "\rSelf-test in progress: {}%",
+                        progress as u32 * 100 / 65536
+                    ),
+                    None => print!("\rSelf-test in progress"),
                 }
+                stdout().flush()?;
+                thread::sleep(SELF_TEST_POLL_INTERVAL);
+            }
+            SelfTestProgress::Finished(result) => break result,
+            SelfTestProgress::NeverRun => {
+                return Err(wd_vsc::Error::Other(
+                    "The device didn't log the self-test".to_owned(),
+                ))
             }
         }
+    };
+
+    println!();
+    println!("Self-test result: {:?}", result.status);
+    if let Some(lba) = result.address_of_first_failure {
+        println!("First failure at LBA {}", lba);
+    }
+    if result.status.is_failure() {
+        println!(
+            "Sense: {:?}, {}",
+            result.sense_key,
+            additional_sense_description(
+                result.additional_sense_code,
+                result.additional_sense_code_qualifier
+            )
+        );
     }
 
-    if cli.self_test {
-        match device.send_diagnostic()? {
-            TestResult::Ok => println!("Ok"),
-            TestResult::HardwareError => println!("Hardware Error!"),
+    Ok(())
+}
+
+fn print_log_pages(device: &Scsi) -> wd_vsc::Result<()> {
+    let supported_pages = device.log_sense_supported_pages()?;
+    println!("Supported log pages: {:02X?}", supported_pages);
+
+    if supported_pages.contains(&TEMPERATURE_PAGE_CODE) {
+        let temperature = device.log_sense_temperature()?;
+        match temperature.current {
+            Some(t) => println!("Temperature: {} C", t),
+            None => println!("Temperature: unknown"),
+        }
+        if let Some(t) = temperature.reference {
+            println!("Reference temperature: {} C", t);
         }
     }
 
+    if supported_pages.contains(&START_STOP_CYCLE_COUNTER_PAGE_CODE) {
+        let counter = device.log_sense_start_stop_cycle_counter()?;
+        if let Some((year, week)) = counter.date_of_manufacture {
+            println!("Date of manufacture: {} week {}", year, week);
+        }
+        if let Some(cycles) = counter.accumulated_start_stop_cycles {
+            print!("Start-stop cycles: {}", cycles);
+            match counter.specified_start_stop_cycles {
+                Some(specified) => println!(" (of {} specified)", specified),
+                None => println!(),
+            }
+        }
+        if let Some(cycles) = counter.accumulated_load_unload_cycles {
+            print!("Load-unload cycles: {}", cycles);
+            match counter.specified_load_unload_cycles {
+                Some(specified) => println!(" (of {} specified)", specified),
+                None => println!(),
+            }
+        }
+    }
+
+    if supported_pages.contains(&SELF_TEST_RESULTS_PAGE_CODE) {
+        let results = device.log_sense_self_test_results()?;
+        if results.is_empty() {
+            println!("Self-test results: none");
+        }
+        for result in results {
+            print!(
+                "Self-test #{} (code {}): {:?} at {} hours",
+                result.self_test_number,
+                result.self_test_code,
+                result.status,
+                result.accumulated_power_on_hours
+            );
+            match result.address_of_first_failure {
+                Some(lba) => println!(", first failure at LBA {}", lba),
+                None => println!(),
+            }
+        }
+    }
+
+    if supported_pages.contains(&INFORMATIONAL_EXCEPTIONS_PAGE_CODE) {
+        if let Some(exceptions) = device.log_sense_informational_exceptions()? {
+            match exceptions.additional_sense_code {
+                0 => println!("Informational exceptions: none"),
+                _ => println!("Informational exceptions: {}", exceptions.description()),
+            }
+            if exceptions.failure_predicted() {
+                println!("Warning: the drive predicts it's going to fail!");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Tab separated so scripts can pick the columns they need.
+#[cfg(target_os = "linux")]
+fn list_disks() -> wd_vsc::Result<()> {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+
+    println!("DEVICE\tSG\tVENDOR\tMODEL\tSERIAL\tUSB ID\tDRIVER\tPARTITIONS");
+    for disk in libscsi::discovery::list_disks()? {
+        let usb_id = match (disk.usb_vendor_id, disk.usb_product_id) {
+            (Some(vendor_id), Some(product_id)) => {
+                Some(format!("{:04x}:{:04x}", vendor_id, product_id))
+            }
+            _ => None,
+        };
+        let partitions: Vec<_> = disk
+            .partitions
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            disk.path.display(),
+            or_dash(disk.sg_path.map(|p| p.display().to_string())),
+            disk.vendor,
+            disk.model,
+            or_dash(disk.serial),
+            or_dash(usb_id),
+            or_dash(disk.driver.map(|d| d.to_string())),
+            partitions.join(","),
+        );
+    }
+
     Ok(())
 }
 
+#[cfg(not(target_os = "linux"))]
+fn list_disks() -> wd_vsc::Result<()> {
+    Err(Error::Other(
+        "Listing disks is only supported on Linux".to_owned(),
+    ))
+}
+
+/// Mounted file systems would be corrupted, or keep serving stale data, after their keys are gone.
+fn claim_exclusive(device: &Scsi, force: bool) -> wd_vsc::Result<ExclusiveClaim> {
+    if force {
+        return Ok(device.claim_unchecked());
+    }
+
+    device
+        .claim_exclusive_strict()
+        .map_err(|error| match error {
+            libscsi::Error::InUse(..) | libscsi::Error::InUseUnknown(_) => {
+                Error::Other(format!("{} Use --force to do it anyway.", error))
+            }
+            error => error.into(),
+        })
+}
+
+fn print_disk_info(disk: &IdentifyDevice) {
+    println!("Disk model: {}", disk.model_number);
+    println!("Disk serial number: {}", disk.serial_number);
+    println!("Disk firmware revision: {}", disk.firmware_revision);
+    println!(
+        "Disk capacity: {} sectors, {} bytes ({:.1} GB)",
+        disk.user_addressable_sectors,
+        disk.capacity(),
+        disk.capacity() as f64 / 1e9
+    );
+    println!(
+        "Sector size: {} bytes logical, {} bytes physical",
+        disk.logical_sector_size, disk.physical_sector_size
+    );
+    match disk.rotation_rate {
+        RotationRate::NotReported => println!("Rotation rate: unknown"),
+        RotationRate::NonRotating => println!("Rotation rate: solid state device"),
+        RotationRate::Rpm(rpm) => println!("Rotation rate: {} rpm", rpm),
+    }
+
+    let mut features = vec![];
+    if disk.lba48_supported {
+        features.push("48-bit LBA".to_owned());
+    }
+    if disk.smart_supported {
+        features.push("SMART".to_owned());
+    }
+    if disk.trim_supported {
+        features.push("TRIM".to_owned());
+    }
+    if disk.security_supported {
+        let states = [
+            (disk.security_enabled, "enabled"),
+            (disk.security_locked, "locked"),
+            (disk.security_frozen, "frozen"),
+        ];
+        let states: Vec<_> = states.iter().filter(|(on, _)| *on).map(|s| s.1).collect();
+        match states.is_empty() {
+            true => features.push("Security".to_owned()),
+            false => features.push(format!("Security ({})", states.join(", "))),
+        }
+    }
+    if disk.apm_supported {
+        match disk.apm_level {
+            Some(level) => features.push(format!("APM (level {})", level)),
+            None => features.push("APM (disabled)".to_owned()),
+        }
+    }
+    if disk.sct_supported {
+        features.push("SCT".to_owned());
+    }
+    println!("Disk features: {}", features.join(", "));
+}
+
+fn print_smart(device: &Scsi) -> wd_vsc::Result<()> {
+    let report = device.smart()?;
+
+    match report.health {
+        SmartHealth::Passed => println!("SMART overall-health: PASSED"),
+        SmartHealth::Failing => println!("SMART overall-health: FAILING"),
+    }
+
+    println!(
+        "{:>3} {:<24} {:>5} {:>5} {:>6} {:<9} Raw value",
+        "ID", "Attribute", "Value", "Worst", "Thresh", "Type"
+    );
+    for attribute in report.attributes {
+        print!(
+            "{:>3} {:<24} {:>5} {:>5} {:>6} {:<9} {}",
+            attribute.id,
+            attribute.name,
+            attribute.current,
+            attribute.worst,
+            attribute.threshold,
+            match attribute.is_prefailure() {
+                true => "Pre-fail",
+                false => "Old_age",
+            },
+            attribute.raw_value
+        );
+        match attribute.is_failing() {
+            true => println!(" (FAILING NOW)"),
+            false => println!(),
+        }
+    }
+
+    Ok(())
+}
+
+fn not_changeable<T>(setting: &ModeSetting<T>) -> &'static str {
+    match setting.changeable {
+        true => "",
+        false => ", not changeable",
+    }
+}
+
 fn check_device_unlocked(status: &SecurityStatus, error_message: &str) -> wd_vsc::Result<()> {
     match status {
         SecurityStatus::Locked | SecurityStatus::UnlockAttemptExceeded => {
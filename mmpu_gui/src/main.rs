@@ -5,20 +5,38 @@
 
 mod gui_error;
 
-use std::{collections::HashMap, io, path::PathBuf, sync::Mutex, vec};
+use std::{path::PathBuf, sync::Mutex, vec};
+
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
 
 use serde::Serialize;
-use tauri::{Manager, State};
+use tauri::{Manager, State, Window};
 
 use gui_error::Result;
-use libscsi::{command::TestResult, Scsi};
+use libscsi::{
+    command::{SelfTestCode, SelfTestProgress, SmartHealth, TestResult},
+    ExclusiveClaim, RetryPolicy, Scsi,
+};
+
+#[cfg(target_os = "linux")]
+use libscsi::QueuedTransport;
 use wd_vsc::{
     device_configuration_page, operations_page, password_utility, power_condition_mode_page,
     security_block::read_security_block, WdVsc,
 };
 
+#[cfg(target_os = "linux")]
+const WD_USB_VENDOR_ID: u16 = 0x1058;
+/// `erase_device` unlocks the device this many times
+const ERASE_CHUNK_COUNT: u64 = 100;
+
 struct Storage {
     device: Mutex<Option<Scsi>>,
+    /// Self-tests go through here if it could be opened, so they don't hold the device lock or
+    /// block a thread
+    #[cfg(target_os = "linux")]
+    queued_device: Mutex<Option<Arc<Scsi<QueuedTransport>>>>,
 }
 
 #[derive(Serialize)]
@@ -27,61 +45,27 @@ struct PathAndName {
     pub name: String,
 }
 
-#[cfg(target_os = "linux")]
-fn get_root_with_file(path: &std::path::Path, filename: &str) -> Result<PathBuf> {
-    let mut path = path.canonicalize()?;
-    loop {
-        if path.join(filename).exists() {
-            break;
-        } else {
-            path = match path.parent() {
-                Some(parent) => parent.to_owned(),
-                None => Err(io::Error::new(io::ErrorKind::NotFound, ""))?,
-            };
-        }
-    }
-
-    Ok(path)
-}
-
+/// Only WD drives on USB, internal disks are never offered for erasing.
 #[cfg(target_os = "linux")]
 #[tauri::command]
 fn list_drives() -> Result<Vec<PathAndName>> {
-    let mut result = HashMap::new();
-    let paths = std::fs::read_dir("/sys/dev/block/")?;
-    for path in paths {
-        // a naive way to determine if it's a partition or a drive.
-        let path = &path?.path();
-        let block_path = match get_root_with_file(path, "removable") {
-            Ok(p) => p,
-            Err(_) => continue,
-        };
-        let product_path = get_root_with_file(path, "model");
-        let filename = block_path.file_name().unwrap();
-        if filename.to_string_lossy().starts_with("sd") {
-            let product_name = match product_path {
-                Ok(path) => std::fs::read_to_string(path.join("model"))?,
-                Err(_) => "".to_owned(),
-            };
-            result.insert(PathBuf::from("/dev/").join(filename), product_name);
-        }
-    }
-
-    let mut result: Vec<_> = result
-        .iter()
-        .map(|r| PathAndName {
-            path: r.0.to_owned(),
-            name: r.1.to_owned(),
+    Ok(libscsi::discovery::list_disks()?
+        .into_iter()
+        .filter(|disk| {
+            disk.driver.is_some()
+                && (disk.usb_vendor_id == Some(WD_USB_VENDOR_ID) || disk.vendor == "WD")
         })
-        .collect();
-
-    result.sort_by(|a, b| a.path.cmp(&b.path));
-    Ok(result)
+        .map(|disk| PathAndName {
+            path: disk.path,
+            name: disk.model,
+        })
+        .collect())
 }
 
 #[cfg(target_os = "windows")]
 #[tauri::command]
 fn list_drives() -> Result<Vec<PathAndName>> {
+    use std::{collections::HashMap, io};
     use windows::core::PCWSTR;
     use windows::Win32::Devices::DeviceAndDriverInstallation::{
         SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW, SetupDiGetDeviceInterfaceDetailW,
@@ -242,7 +226,8 @@ fn list_drives() -> Result<Vec<PathAndName>> {
 
 #[tauri::command]
 fn open_device(path: String, storage: State<Storage>) -> crate::Result<()> {
-    let device = Scsi::new(&path)?;
+    let mut device = Scsi::new_generic(&path)?;
+    device.set_retry_policy(RetryPolicy::default());
     let name = device.inquiry_product_identification()?;
     if !name.to_lowercase().contains("my passport") {
         Err(wd_vsc::Error::Other(
@@ -250,6 +235,11 @@ fn open_device(path: String, storage: State<Storage>) -> crate::Result<()> {
         ))?
     }
 
+    // there are no sg nodes without the sg module, self-tests then go through `device`
+    #[cfg(target_os = "linux")]
+    {
+        *storage.queued_device.lock().unwrap() = Scsi::new_queued(&path).ok().map(Arc::new);
+    }
     let _ = storage.device.lock().unwrap().insert(device);
 
     Ok(())
@@ -321,9 +311,10 @@ fn set_password(password: String, hint: String, storage: State<Storage>) -> Resu
 }
 
 #[tauri::command]
-fn remove_password(password: String, storage: State<Storage>) -> Result<()> {
+fn remove_password(password: String, force: bool, storage: State<Storage>) -> Result<()> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
+    let _claim = claim_exclusive(device, force)?;
     let status = device.encryption_status()?;
     password_utility::change_password(
         device,
@@ -365,16 +356,181 @@ fn change_password(
     Ok(())
 }
 
+/// The default self-test takes up to a few minutes.
+#[cfg(target_os = "linux")]
 #[tauri::command]
+async fn basic_diagnose(storage: State<'_, Storage>) -> crate::Result<String> {
+    match queued_device(&storage) {
+        Some(device) => diagnose_result(device.send_diagnostic_async().await?),
+        None => diagnose(&storage),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[tauri::command(async)]
 fn basic_diagnose(storage: State<Storage>) -> crate::Result<String> {
+    diagnose(&storage)
+}
+
+fn diagnose(storage: &Storage) -> crate::Result<String> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
-    match device.send_diagnostic()? {
+    diagnose_result(device.send_diagnostic()?)
+}
+
+fn diagnose_result(result: TestResult) -> crate::Result<String> {
+    match result {
         TestResult::Ok => Ok("Everything is okay.".to_owned()),
         TestResult::HardwareError => Ok("Hardware error!".to_owned()),
     }
 }
 
+/// Cloned out of the lock, it isn't held while waiting for the device.
+/// `None` without sg nodes, the commands then block like on other systems.
+#[cfg(target_os = "linux")]
+fn queued_device(storage: &Storage) -> Option<Arc<Scsi<QueuedTransport>>> {
+    storage.queued_device.lock().unwrap().clone()
+}
+
+#[derive(Serialize)]
+struct SmartAttributeRow {
+    pub id: u8,
+    pub name: String,
+    pub current: u8,
+    pub worst: u8,
+    pub threshold: u8,
+    pub raw_value: u64,
+    pub prefailure: bool,
+    pub failing: bool,
+}
+
+#[derive(Serialize)]
+struct SmartState {
+    pub passed: bool,
+    pub attributes: Vec<SmartAttributeRow>,
+}
+
+#[tauri::command]
+fn get_smart(storage: State<Storage>) -> Result<SmartState> {
+    let device = storage.device.lock().unwrap();
+    let device = device.as_ref().unwrap();
+    let report = device.smart()?;
+
+    Ok(SmartState {
+        passed: report.health == SmartHealth::Passed,
+        attributes: report
+            .attributes
+            .into_iter()
+            .map(|a| SmartAttributeRow {
+                id: a.id,
+                name: a.name.to_owned(),
+                current: a.current,
+                worst: a.worst,
+                threshold: a.threshold,
+                raw_value: a.raw_value,
+                prefailure: a.is_prefailure(),
+                failing: a.is_failing(),
+            })
+            .collect(),
+    })
+}
+
+#[derive(Serialize)]
+struct SelfTestState {
+    pub running: bool,
+    /// In percent
+    pub progress: Option<u32>,
+    pub result: Option<String>,
+    pub failure_lba: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+#[tauri::command]
+async fn start_self_test(extended: bool, storage: State<'_, Storage>) -> Result<()> {
+    let code = background_self_test_code(extended);
+    match queued_device(&storage) {
+        Some(device) => self_test_started(device.start_self_test_async(code).await?),
+        None => run_self_test(&storage, code),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[tauri::command]
+fn start_self_test(extended: bool, storage: State<Storage>) -> Result<()> {
+    run_self_test(&storage, background_self_test_code(extended))
+}
+
+fn background_self_test_code(extended: bool) -> SelfTestCode {
+    match extended {
+        true => SelfTestCode::BackgroundExtended,
+        false => SelfTestCode::BackgroundShort,
+    }
+}
+
+fn run_self_test(storage: &Storage, code: SelfTestCode) -> Result<()> {
+    let device = storage.device.lock().unwrap();
+    let device = device.as_ref().unwrap();
+    self_test_started(device.start_self_test(code)?)
+}
+
+fn self_test_started(result: TestResult) -> Result<()> {
+    if let TestResult::HardwareError = result {
+        Err(wd_vsc::Error::Other("Hardware error!".to_owned()))?
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tauri::command]
+async fn abort_self_test(storage: State<'_, Storage>) -> Result<()> {
+    match queued_device(&storage) {
+        Some(device) => {
+            device
+                .start_self_test_async(SelfTestCode::AbortBackground)
+                .await?;
+        }
+        None => {
+            run_self_test(&storage, SelfTestCode::AbortBackground)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+#[tauri::command]
+fn abort_self_test(storage: State<Storage>) -> Result<()> {
+    run_self_test(&storage, SelfTestCode::AbortBackground)
+}
+
+#[tauri::command]
+fn get_self_test_state(storage: State<Storage>) -> Result<SelfTestState> {
+    let device = storage.device.lock().unwrap();
+    let device = device.as_ref().unwrap();
+
+    Ok(match device.self_test_progress()? {
+        SelfTestProgress::InProgress(progress) => SelfTestState {
+            running: true,
+            progress: progress.map(|p| p as u32 * 100 / 65536),
+            result: None,
+            failure_lba: None,
+        },
+        SelfTestProgress::Finished(result) => SelfTestState {
+            running: false,
+            progress: None,
+            result: Some(format!("{:?}", result.status)),
+            failure_lba: result.address_of_first_failure,
+        },
+        SelfTestProgress::NeverRun => SelfTestState {
+            running: false,
+            progress: None,
+            result: None,
+            failure_lba: None,
+        },
+    })
+}
+
 #[tauri::command]
 fn get_sleep_timer(storage: State<Storage>) -> Result<u32> {
     let device = storage.device.lock().unwrap();
@@ -384,10 +540,10 @@ fn get_sleep_timer(storage: State<Storage>) -> Result<u32> {
 }
 
 #[tauri::command]
-fn set_sleep_timer(timer: u32, storage: State<Storage>) -> Result<()> {
+fn set_sleep_timer(timer: u32, save: bool, storage: State<Storage>) -> Result<()> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
-    power_condition_mode_page::set_sleep_timer(device, timer)?;
+    power_condition_mode_page::set_sleep_timer(device, timer, save)?;
 
     Ok(())
 }
@@ -401,12 +557,12 @@ fn get_led_state(storage: State<Storage>) -> Result<bool> {
 }
 
 #[tauri::command]
-fn set_led_state(on: bool, storage: State<Storage>) -> Result<()> {
+fn set_led_state(on: bool, save: bool, storage: State<Storage>) -> Result<()> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
     match on {
-        true => operations_page::set_led_brightness(device, 255)?,
-        false => operations_page::set_led_brightness(device, 0)?,
+        true => operations_page::set_led_brightness(device, 255, save)?,
+        false => operations_page::set_led_brightness(device, 0, save)?,
     }
 
     Ok(())
@@ -421,35 +577,97 @@ fn get_vcd_state(storage: State<Storage>) -> Result<bool> {
 }
 
 #[tauri::command]
-fn set_vcd_state(on: bool, storage: State<Storage>) -> Result<()> {
+fn set_vcd_state(on: bool, save: bool, storage: State<Storage>) -> Result<()> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
-    device_configuration_page::set_virtual_cd_status(device, on)?;
+    device_configuration_page::set_virtual_cd_status(device, on, save)?;
+
+    Ok(())
+}
+
+/// Runs off the main thread, so the window can show the erase-progress events.
+/// The device is erased in chunks and unlocked in between, so other commands, like reading the
+/// temperature, still get through.
+/// `zero_fill` allows writing zeros to every block if the drive can't erase them any faster,
+/// the window should warn that this takes hours.
+#[tauri::command(async)]
+fn erase_device(
+    window: Window,
+    force: bool,
+    zero_fill: bool,
+    storage: State<Storage>,
+) -> Result<()> {
+    let (path, capacity, mut plan, _claim) = {
+        let device = storage.device.lock().unwrap();
+        let device = device.as_ref().unwrap();
+        let claim = claim_exclusive(device, force)?;
+        let status = device.encryption_status()?;
+        device.reset_data_encryption_key(status.current_cipher, status.key_reset_enabler)?;
+        let capacity = device.read_capacity16()?;
+        let plan = device.erase_plan(capacity.logical_block_length_in_bytes, zero_fill);
+
+        (device.path().clone(), capacity, plan, claim)
+    };
+
+    let total = capacity.logical_block_count;
+    let chunk_length = total.div_ceil(ERASE_CHUNK_COUNT).max(1);
+    let mut last_percent = None;
+    let mut lba = 0;
+    while lba < total {
+        let blocks = u64::min(chunk_length, total - lba);
+        let device = storage.device.lock().unwrap();
+        let device = device.as_ref().unwrap();
+        // another drive may have been opened in the meantime
+        if device.path() != &path {
+            Err(wd_vsc::Error::Other(
+                "The drive was changed while erasing it.".to_owned(),
+            ))?
+        }
+
+        device.erase_range(lba, blocks, &mut plan, |done, _| {
+            let percent = (lba + done) * 100 / total;
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                let _ = window.emit("erase-progress", percent);
+            }
+        })?;
+        lba += blocks;
+    }
 
     Ok(())
 }
 
+/// `force` skips the check, the window should only offer it after warning that the drive may be in
+/// use, see `disk_in_use`.
+fn claim_exclusive(device: &Scsi, force: bool) -> Result<ExclusiveClaim> {
+    match force {
+        true => Ok(device.claim_unchecked()),
+        false => Ok(device.claim_exclusive_strict()?),
+    }
+}
+
+/// Why the drive may be in use, e.g. mounted or where that can't be checked, `None` if it isn't.
+/// The window shows it before asking whether to remove the password or erase it anyway.
 #[tauri::command]
-fn erase_device(storage: State<Storage>) -> Result<()> {
+fn disk_in_use(storage: State<Storage>) -> Result<Option<String>> {
     let device = storage.device.lock().unwrap();
     let device = device.as_ref().unwrap();
-    let status = device.encryption_status()?;
-    device.reset_data_encryption_key(status.current_cipher, status.key_reset_enabler)?;
 
-    let support_unmap = device.inquiry_unmap_support()?;
-    let max_unmap_block = device.inquiry_unmap_block_limit()?;
-    if support_unmap && max_unmap_block > 0 {
-        let cap = device.read_capacity16()?;
-        device.unmap(0, cap.logical_block_count, max_unmap_block)?;
+    match device.claim_exclusive_strict() {
+        Ok(_) => Ok(None),
+        Err(error @ (libscsi::Error::InUse(..) | libscsi::Error::InUseUnknown(_))) => {
+            Ok(Some(error.to_string()))
+        }
+        Err(error) => Err(error.into()),
     }
-
-    Ok(())
 }
 
 fn main() {
     tauri::Builder::default()
         .manage(Storage {
             device: Mutex::new(None),
+            #[cfg(target_os = "linux")]
+            queued_device: Mutex::new(None),
         })
         .invoke_handler(tauri::generate_handler![
             list_drives,
@@ -462,12 +680,17 @@ fn main() {
             remove_password,
             change_password,
             basic_diagnose,
+            get_smart,
+            start_self_test,
+            abort_self_test,
+            get_self_test_state,
             get_sleep_timer,
             set_sleep_timer,
             get_led_state,
             set_led_state,
             get_vcd_state,
             set_vcd_state,
+            disk_in_use,
             erase_device
         ])
         .setup(|app| {
@@ -1,6 +1,7 @@
 const { invoke } = window.__TAURI__.tauri;
-const { message } = window.__TAURI__.dialog;
+const { message, ask } = window.__TAURI__.dialog;
 const { appWindow } = window.__TAURI__.window;
+const { listen } = window.__TAURI__.event;
 
 const SecurityStatus = {
   NoUserPassword: 'NoUserPassword',
@@ -38,8 +39,8 @@ async function set_password(password, hint) {
   await invoke("set_password", { password: password, hint: hint });
 }
 
-async function remove_password(password) {
-  await invoke("remove_password", { password: password });
+async function remove_password(password, force) {
+  await invoke("remove_password", { password: password, force: force });
 }
 
 async function change_password(current_password, new_password, hint) {
@@ -51,12 +52,28 @@ async function basic_diagnose() {
   await message(m);
 }
 
+async function get_smart() {
+  return await invoke("get_smart");
+}
+
+async function start_self_test(extended) {
+  await invoke("start_self_test", { extended: extended });
+}
+
+async function abort_self_test() {
+  await invoke("abort_self_test");
+}
+
+async function get_self_test_state() {
+  return await invoke("get_self_test_state");
+}
+
 async function get_sleep_timer() {
   return parseInt(await invoke("get_sleep_timer"), 10);
 }
 
-async function set_sleep_timer(timer) {
-  await invoke("set_sleep_timer", { timer: parseInt(timer, 10) });
+async function set_sleep_timer(timer, save) {
+  await invoke("set_sleep_timer", { timer: parseInt(timer, 10), save: save });
   await message("Success!");
 }
 
@@ -64,8 +81,8 @@ async function get_led_state() {
   return await invoke("get_led_state");
 }
 
-async function set_led_state(state) {
-  await invoke("set_led_state", { on: state });
+async function set_led_state(state, save) {
+  await invoke("set_led_state", { on: state, save: save });
   await message("Success!");
 }
 
@@ -73,13 +90,32 @@ async function get_vcd_state() {
   return await invoke("get_vcd_state");
 }
 
-async function set_vcd_state(state) {
-  await invoke("set_vcd_state", { on: state });
+async function set_vcd_state(state, save) {
+  await invoke("set_vcd_state", { on: state, save: save });
   await message("Success!");
 }
 
-async function erase_device() {
-  await invoke("erase_device");
+async function erase_device(force, zero_fill) {
+  await invoke("erase_device", { force: force, zeroFill: zero_fill });
+}
+
+async function disk_in_use() {
+  return await invoke("disk_in_use");
+}
+
+// false if nothing else uses the drive, true if the user wants to go on although it may be in use,
+// null if they don't
+async function ask_force() {
+  let reason = await disk_in_use();
+  if (reason == null) {
+    return false;
+  }
+
+  let proceed = await ask(
+    reason + " Programs using the drive may lose data or crash. Continue anyway?",
+    { title: "The drive may be in use", type: "warning" }
+  );
+  return proceed ? true : null;
 }
 
 function toggle_block(visible, block) {
@@ -271,7 +307,11 @@ async function main() {
     }
 
     try {
-      await remove_password(current_password);
+      let force = await ask_force();
+      if (force == null) {
+        return;
+      }
+      await remove_password(current_password, force);
       location.reload();
     } catch (error) {
       await message(error);
@@ -311,6 +351,112 @@ async function main() {
     .querySelector("#run-self-test")
     .addEventListener("click", () => basic_diagnose());
 
+  let smart_health_text = document.querySelector("#smart-health");
+  let smart_table = document.querySelector("#smart-table");
+  let smart_attributes = document.querySelector("#smart-attributes");
+
+  document
+    .querySelector("#read-smart")
+    .addEventListener("click", async () => {
+      let smart;
+      try {
+        smart = await get_smart();
+      } catch (error) {
+        smart_health_text.textContent = error;
+        smart_table.hidden = true;
+        return;
+      }
+
+      smart_health_text.textContent = "SMART overall-health: " + (smart.passed ? "PASSED" : "FAILING");
+      smart_attributes.replaceChildren();
+      for (const attribute of smart.attributes) {
+        let row = document.createElement("tr");
+        if (attribute.failing) {
+          row.className = "table-danger";
+        }
+        for (const value of [
+          attribute.id,
+          attribute.name,
+          attribute.current,
+          attribute.worst,
+          attribute.threshold,
+          attribute.prefailure ? "Pre-fail" : "Old_age",
+          attribute.raw_value,
+        ]) {
+          let cell = document.createElement("td");
+          cell.textContent = value;
+          row.appendChild(cell);
+        }
+        smart_attributes.appendChild(row);
+      }
+      smart_table.hidden = false;
+    });
+
+  let self_test_state_text = document.querySelector("#self-test-state");
+  let self_test_timer = null;
+
+  async function poll_self_test() {
+    try {
+      let state = await get_self_test_state();
+      if (state.running) {
+        self_test_state_text.textContent = state.progress == null
+          ? "Self-test in progress"
+          : "Self-test in progress: " + state.progress + "%";
+        return;
+      }
+
+      if (state.result == null) {
+        self_test_state_text.textContent = "No self-test has been run yet.";
+      } else if (state.failure_lba == null) {
+        self_test_state_text.textContent = "Last self-test: " + state.result;
+      } else {
+        self_test_state_text.textContent = "Last self-test: " + state.result + ", first failure at LBA " + state.failure_lba;
+      }
+    } catch (error) {
+      self_test_state_text.textContent = error;
+    }
+
+    clearInterval(self_test_timer);
+    self_test_timer = null;
+  }
+
+  async function run_background_self_test(extended) {
+    try {
+      await start_self_test(extended);
+    } catch (error) {
+      await message(error);
+      return;
+    }
+
+    if (self_test_timer == null) {
+      self_test_timer = setInterval(poll_self_test, 2000);
+    }
+    await poll_self_test();
+  }
+
+  document
+    .querySelector("#run-short-self-test")
+    .addEventListener("click", () => run_background_self_test(false));
+  document
+    .querySelector("#run-extended-self-test")
+    .addEventListener("click", () => run_background_self_test(true));
+  document
+    .querySelector("#abort-self-test")
+    .addEventListener("click", async () => {
+      try {
+        await abort_self_test();
+      } catch (error) {
+        await message(error);
+      }
+      await poll_self_test();
+    });
+
+  if (!diagnose_tab.disabled) {
+    self_test_timer = setInterval(poll_self_test, 2000);
+    await poll_self_test();
+  }
+
+  let save_settings_check = document.querySelector("#save-settings-switch");
   let sleep_timer_check = document.querySelector("#sleep-timer-switch");
   let sleep_timer_block = document.querySelector("#sleep-timer-value-block");
   let sleep_timer_input = document.querySelector("#input-timer");
@@ -324,7 +470,7 @@ async function main() {
 
   sleep_timer_check.addEventListener("change", async (e) => {
     if (!e.target.checked && timer_changed) {
-      await set_sleep_timer(0);
+      await set_sleep_timer(0, save_settings_check.checked);
       sleep_timer_input.value = 0;
       timer_changed = false;
     }
@@ -332,31 +478,78 @@ async function main() {
     toggle_block(e.target.checked, sleep_timer_block);
   });
   sleep_timer_button.addEventListener("click", async () => {
-    set_sleep_timer(sleep_timer_input.value);
+    try {
+      await set_sleep_timer(sleep_timer_input.value, save_settings_check.checked);
+    } catch (error) {
+      await message(error);
+    }
     sleep_timer_input.value = await get_sleep_timer();
     timer_changed = true;
   })
 
   let led_switch = document.querySelector("#led-switch");
   led_switch.checked = await get_led_state();
-  led_switch.addEventListener("change", async (e) => { await set_led_state(e.target.checked) });
+  led_switch.addEventListener("change", async (e) => {
+    try {
+      await set_led_state(e.target.checked, save_settings_check.checked);
+    } catch (error) {
+      await message(error);
+      e.target.checked = await get_led_state();
+    }
+  });
 
   let vcd_switch = document.querySelector("#virtualcd-switch");
   vcd_switch.checked = await get_vcd_state();
-  vcd_switch.addEventListener("change", async (e) => { await set_vcd_state(e.target.checked) });
+  vcd_switch.addEventListener("change", async (e) => {
+    try {
+      await set_vcd_state(e.target.checked, save_settings_check.checked);
+    } catch (error) {
+      await message(error);
+      e.target.checked = await get_vcd_state();
+    }
+  });
 
   let erase_button = document.querySelector("#erase-button");
   let erase_check = document.querySelector("#agree-erase");
+  let zero_fill_check = document.querySelector("#zero-fill-erase");
+  let erase_progress_block = document.querySelector("#erase-progress-div");
+  let erase_progress = document.querySelector("#erase-progress");
+
+  await listen("erase-progress", (event) => {
+    erase_progress.style.width = event.payload + "%";
+    erase_progress.setAttribute("aria-valuenow", event.payload);
+    erase_progress.textContent = event.payload + "%";
+  });
 
   erase_check.addEventListener("change", (e) => { (erase_button.disabled = !e.target.checked) });
   erase_button.addEventListener("click", async () => {
+    let force;
+    try {
+      force = await ask_force();
+    } catch (error) {
+      await message(error);
+      return;
+    }
+    if (force == null) {
+      return;
+    }
+
+    erase_check.disabled = true;
+    zero_fill_check.disabled = true;
+    erase_button.disabled = true;
+    erase_progress.style.width = "0%";
+    erase_progress.textContent = "0%";
+    toggle_block(true, erase_progress_block);
     try {
-      await erase_device();
+      await erase_device(force, zero_fill_check.checked);
       await message("Successfully erased!");
     } catch (error) {
       await message(error)
     }
+    toggle_block(false, erase_progress_block);
+    erase_check.disabled = false;
     erase_check.checked = false;
+    zero_fill_check.disabled = false;
     erase_button.disabled = true;
   });
 
@@ -7,6 +7,9 @@ pub mod password_utility;
 
 pub mod security_block;
 
+#[cfg(any(test, feature = "simulator"))]
+pub mod simulator;
+
 pub use cipher::Cipher;
 pub use native::mode::*;
 pub use native::*;
@@ -7,7 +7,7 @@ use libscsi::{
         sense::{FixedSenseBuffer, Sense},
         *,
     },
-    DataDirection, MaskedStatus, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, SenseKey, Transport,
 };
 
 use crate::{Cipher, Error, DATA_SIGNATURE};
@@ -28,6 +28,7 @@ struct ChangeEncryptionPassphraseCommand {
     control: B8,
 }
 
+#[repr(C)]
 struct ChangeEncryptionPassphraseData {
     signature: u8,
     reserved_0: [u8; 2],
@@ -98,20 +99,17 @@ impl Command for ThisCommand {
     ) -> Self::ReturnType {
         result.check_ioctl_error()?;
 
-        if result.masked_status == MaskedStatus::CHECK_CONDITION {
-            let sens_buffer = result.sense_buffer.as_ref().unwrap();
-            // ILLEGAL REQUEST
-            if sens_buffer.sense_key() == 0x05 {
-                if sens_buffer.additional_sense_code() == 0x74 {
-                    if sens_buffer.additional_sense_code_qualifier() == 0x40 {
-                        return Err(Error::PasswordIncorrect);
-                    }
-                } else if (sens_buffer.additional_sense_code() == 0x24
-                    || sens_buffer.additional_sense_code() == 0x26)
-                    && sens_buffer.additional_sense_code_qualifier() == 0x00
-                {
-                    return Err(Error::PasswordBlobSizeIncorrect);
+        if let Some(sense) = result.sense_data() {
+            match (
+                sense.sense_key,
+                sense.additional_sense_code,
+                sense.additional_sense_code_qualifier,
+            ) {
+                (SenseKey::IllegalRequest, 0x74, 0x40) => return Err(Error::PasswordIncorrect),
+                (SenseKey::IllegalRequest, 0x24 | 0x26, 0x00) => {
+                    return Err(Error::PasswordBlobSizeIncorrect)
                 }
+                _ => {}
             }
         }
 
@@ -126,8 +124,8 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn change_encryption_passphrase(
-        scsi: &Scsi,
+    pub(super) fn change_encryption_passphrase<T: Transport>(
+        scsi: &Scsi<T>,
         cipher: Cipher,
         new_password: Option<Vec<u8>>,
         old_password: Option<Vec<u8>>,
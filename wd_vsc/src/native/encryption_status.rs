@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::mem::size_of;
+use std::{mem::size_of, time::Duration};
 
 use modular_bitfield_msb::prelude::*;
 
@@ -9,7 +9,7 @@ use libscsi::{
         sense::{BytesSenseBuffer, Sense},
         *,
     },
-    DataDirection, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, Transport,
 };
 
 use crate::{Cipher, SecurityStatus, DATA_SIGNATURE};
@@ -93,6 +93,10 @@ impl Command for ThisCommand {
         Self::SenseBuffer::default()
     }
 
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(super::STATUS_COMMAND_TIMEOUT)
+    }
+
     fn process_result(
         &self,
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
@@ -137,7 +141,9 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn encryption_status(scsi: &Scsi) -> crate::Result<EncryptionStatus> {
+    pub(super) fn encryption_status<T: Transport>(
+        scsi: &Scsi<T>,
+    ) -> crate::Result<EncryptionStatus> {
         scsi.execute_command(&ThisCommand {})
     }
 }
@@ -163,4 +169,48 @@ mod tests {
             concat!("Size of: ", stringify!(EncryptionStatusDataWithCiphers))
         );
     }
+
+    #[test]
+    fn retried_after_host_timeout() {
+        use libscsi::{
+            DriverStatus, HostStatus, MaskedStatus, ReplayTransport, RetryPolicy, TraceRecord,
+        };
+
+        let command = [OPERATION_CODE, OPERATION_SUBCODE, 0, 0, 0, 0, 0, 0, 0x30, 0];
+        let mut response = vec![0; TOTAL_DATA_SIZE];
+        response[0] = DATA_SIGNATURE;
+        response[3] = 0x02;
+        response[4] = 0x28;
+        response[15] = 1;
+        response[16] = 0x28;
+        let record = |host_status, data_in| TraceRecord {
+            command: command.to_vec(),
+            direction: DataDirection::FromDevice,
+            data_out: vec![],
+            data_in,
+            sense: vec![],
+            masked_status: MaskedStatus::empty(),
+            host_status,
+            driver_status: DriverStatus::empty(),
+            duration: Duration::ZERO,
+            ioctl_result: 0,
+        };
+        let mut device = Scsi::with_transport(
+            "replay",
+            ReplayTransport::from_records(vec![
+                record(HostStatus::TimeOut, vec![]),
+                record(HostStatus::Ok, response),
+            ]),
+        );
+        device.set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+
+        let status = super::super::WdVscWrapper::encryption_status(&device).unwrap();
+        assert!(matches!(status.security_status, SecurityStatus::Unlocked));
+        assert_eq!(status.supported_ciphers, [Cipher::Aes256Xts]);
+        assert_eq!(device.transport().remaining(), 0);
+    }
 }
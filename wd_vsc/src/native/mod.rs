@@ -8,7 +8,9 @@ mod write_handy_store;
 
 pub mod mode;
 
-use libscsi::Scsi;
+use std::time::Duration;
+
+use libscsi::{Scsi, Transport};
 use read_handy_capacity::HandyCapacity;
 
 pub use encryption_status::*;
@@ -24,6 +26,9 @@ pub const DEFAULT_SALT: [u8; SALT_SIZE_FOR_U8] = [87, 0, 68, 0, 67, 0, 46, 0];
 pub const DEFAULT_ITERATION_COUNT: u32 = 1000;
 pub const MAX_HINT_SIZE_FOR_U16: usize = 101;
 
+/// Answered by the bridge right away, no need to wait for the default timeout if it hangs
+const STATUS_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub trait WdVsc {
     fn encryption_status(&self) -> crate::Result<EncryptionStatus>;
     fn read_handy_capacity(&self) -> crate::Result<HandyCapacity>;
@@ -41,6 +46,7 @@ pub trait WdVsc {
         old_password: Option<Vec<u8>>,
     ) -> crate::Result<()>;
 
+    /// Fails unless the disk can be claimed, see [`Scsi::claim_exclusive`].
     fn reset_data_encryption_key(
         &self,
         cipher: Cipher,
@@ -48,7 +54,7 @@ pub trait WdVsc {
     ) -> crate::Result<()>;
 }
 
-impl WdVsc for Scsi {
+impl<T: Transport> WdVsc for Scsi<T> {
     fn encryption_status(&self) -> crate::Result<EncryptionStatus> {
         WdVscWrapper::encryption_status(self)
     }
@@ -87,6 +93,8 @@ impl WdVsc for Scsi {
         cipher: Cipher,
         key_reset_enabler: u32,
     ) -> crate::Result<()> {
+        // every file system on it turns into noise
+        let _claim = self.claim_exclusive()?;
         WdVscWrapper::reset_data_encryption_key(self, cipher, key_reset_enabler)
     }
 }
@@ -2,16 +2,39 @@
 
 use modular_bitfield_msb::prelude::*;
 
-use libscsi::Scsi;
+use libscsi::{
+    command::{ModePageField, PageControl},
+    Scsi, Transport,
+};
+
+use super::{read_page, ModeSetting};
 
 const PAGE_CODE: u8 = 0x20;
 const PAGE_LENGTH: u8 = 0x06;
+const PAGE_SIZE: usize = PAGE_LENGTH as usize + 2;
 const SIGNATURE: u8 = 0x30;
 
+const FIELDS: &[ModePageField] = &[
+    ModePageField {
+        name: "Virtual CD",
+        bytes: 4..5,
+        bits: 0x02,
+    },
+    ModePageField {
+        name: "SES",
+        bytes: 4..5,
+        bits: 0x01,
+    },
+    ModePageField {
+        name: "2TB limit",
+        bytes: 5..6,
+        bits: 0x02,
+    },
+];
+
 #[bitfield]
 #[derive(Debug, Clone, Copy, Default)]
 struct DeviceConfigurationPage {
-    header: B64,
     parameter_savable: B1,
     reserved_0: B1,
     page_code: B6,
@@ -37,21 +60,41 @@ impl DeviceConfigurationPage {
     }
 }
 
-fn read(device: &Scsi) -> crate::Result<DeviceConfigurationPage> {
-    Ok(device.mode_sense::<DeviceConfigurationPage>(PAGE_CODE)?)
+fn read<T: Transport>(
+    device: &Scsi<T>,
+    page_control: PageControl,
+) -> crate::Result<DeviceConfigurationPage> {
+    Ok(DeviceConfigurationPage::from_bytes(read_page(
+        device,
+        PAGE_CODE,
+        page_control,
+    )?))
 }
 
-pub fn get_virtual_cd_status(device: &Scsi) -> crate::Result<bool> {
-    Ok(read(device)?.is_virtual_cd_on())
+pub fn get_virtual_cd_status<T: Transport>(device: &Scsi<T>) -> crate::Result<bool> {
+    Ok(read(device, PageControl::Current)?.is_virtual_cd_on())
 }
 
-pub fn set_virtual_cd_status(device: &Scsi, enable_cd: bool) -> crate::Result<()> {
-    let data = read(device)?
-        .with_header(0)
+pub fn get_virtual_cd_setting<T: Transport>(device: &Scsi<T>) -> crate::Result<ModeSetting<bool>> {
+    Ok(ModeSetting {
+        current: read(device, PageControl::Current)?.is_virtual_cd_on(),
+        default: read(device, PageControl::Default)?.is_virtual_cd_on(),
+        changeable: read(device, PageControl::Changeable)?.disable_cdrom() != 0,
+    })
+}
+
+/// With `save` the device keeps it across power cycles, or it fails if it can't.
+pub fn set_virtual_cd_status<T: Transport>(
+    device: &Scsi<T>,
+    enable_cd: bool,
+    save: bool,
+) -> crate::Result<()> {
+    let current = read(device, PageControl::Current)?;
+    let data = current
         .with_parameter_savable(0)
         .with_disable_cdrom(!enable_cd as u8);
 
-    Ok(device.mode_select(data)?)
+    Ok(device.mode_select_page_checked(&current.into_bytes(), &data.into_bytes(), FIELDS, save)?)
 }
 
 #[cfg(test)]
@@ -59,13 +102,11 @@ mod tests {
     use super::*;
     use std::mem::size_of;
 
-    const DEVICE_CONFIGURATION_PAGE_SIZE: usize = 8 + 8;
-
     #[test]
     fn layout_test() {
         assert_eq!(
             size_of::<DeviceConfigurationPage>(),
-            DEVICE_CONFIGURATION_PAGE_SIZE,
+            PAGE_SIZE,
             concat!("Size of: ", stringify!(DeviceConfigurationPage))
         );
     }
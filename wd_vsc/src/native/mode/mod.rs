@@ -1,3 +1,35 @@
 pub mod device_configuration_page;
 pub mod operations_page;
 pub mod power_condition_mode_page;
+
+use libscsi::{command::PageControl, Scsi, Transport};
+
+use crate::Error;
+
+/// A mode page setting with its factory default, and whether the device lets MODE SELECT change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSetting<T> {
+    pub current: T,
+    pub default: T,
+    pub changeable: bool,
+}
+
+/// Reads a page without its mode parameter header and block descriptors.
+fn read_page<T: Transport, const N: usize>(
+    device: &Scsi<T>,
+    page_code: u8,
+    page_control: PageControl,
+) -> crate::Result<[u8; N]> {
+    let data = device.mode_sense_page(page_code, 0, page_control)?;
+
+    data.pages
+        .get(..N)
+        .and_then(|page| page.try_into().ok())
+        .ok_or_else(|| {
+            Error::Other(format!(
+                "Mode page {:02X}h too short: {} bytes",
+                page_code,
+                data.pages.len()
+            ))
+        })
+}
@@ -2,16 +2,39 @@
 
 use modular_bitfield_msb::prelude::*;
 
-use libscsi::Scsi;
+use libscsi::{
+    command::{ModePageField, PageControl},
+    Scsi, Transport,
+};
+
+use super::{read_page, ModeSetting};
 
 const PAGE_CODE: u8 = 0x21;
 const PAGE_LENGTH: u8 = 0x0a;
+const PAGE_SIZE: usize = PAGE_LENGTH as usize + 2;
 const SIGNATURE: u8 = 0x30;
 
+const FIELDS: &[ModePageField] = &[
+    ModePageField {
+        name: "Power LED brightness",
+        bytes: 8..9,
+        bits: 0xff,
+    },
+    ModePageField {
+        name: "Backlight brightness",
+        bytes: 9..10,
+        bits: 0xff,
+    },
+    ModePageField {
+        name: "Inverted LCD",
+        bytes: 10..11,
+        bits: 0x01,
+    },
+];
+
 #[bitfield]
 #[derive(Debug, Clone, Copy, Default)]
 struct OperationsPage {
-    header: B64,
     parameter_savable: B1,
     reserved_0: B1,
     page_code: B6,
@@ -42,21 +65,43 @@ impl OperationsPage {
     }
 }
 
-fn read(device: &Scsi) -> crate::Result<OperationsPage> {
-    Ok(device.mode_sense::<OperationsPage>(PAGE_CODE)?)
+fn read<T: Transport>(
+    device: &Scsi<T>,
+    page_control: PageControl,
+) -> crate::Result<OperationsPage> {
+    Ok(OperationsPage::from_bytes(read_page(
+        device,
+        PAGE_CODE,
+        page_control,
+    )?))
 }
 
-pub fn get_led_brightness(device: &Scsi) -> crate::Result<u8> {
-    Ok(read(device)?.get_led_brightness())
+pub fn get_led_brightness<T: Transport>(device: &Scsi<T>) -> crate::Result<u8> {
+    Ok(read(device, PageControl::Current)?.get_led_brightness())
 }
 
-pub fn set_led_brightness(device: &Scsi, led_brightness: u8) -> crate::Result<()> {
-    let data = read(device)?
-        .with_header(0)
+pub fn get_led_brightness_setting<T: Transport>(
+    device: &Scsi<T>,
+) -> crate::Result<ModeSetting<u8>> {
+    Ok(ModeSetting {
+        current: read(device, PageControl::Current)?.get_led_brightness(),
+        default: read(device, PageControl::Default)?.get_led_brightness(),
+        changeable: read(device, PageControl::Changeable)?.get_led_brightness() != 0,
+    })
+}
+
+/// With `save` the device keeps it across power cycles, or it fails if it can't.
+pub fn set_led_brightness<T: Transport>(
+    device: &Scsi<T>,
+    led_brightness: u8,
+    save: bool,
+) -> crate::Result<()> {
+    let current = read(device, PageControl::Current)?;
+    let data = current
         .with_parameter_savable(0)
         .with_power_led_brite(led_brightness);
 
-    Ok(device.mode_select(data)?)
+    Ok(device.mode_select_page_checked(&current.into_bytes(), &data.into_bytes(), FIELDS, save)?)
 }
 
 #[cfg(test)]
@@ -64,13 +109,11 @@ mod tests {
     use super::*;
     use std::mem::size_of;
 
-    const OPERATIONS_PAGE_SIZE: usize = 8 + 12;
-
     #[test]
     fn layout_test() {
         assert_eq!(
             size_of::<OperationsPage>(),
-            OPERATIONS_PAGE_SIZE,
+            PAGE_SIZE,
             concat!("Size of: ", stringify!(OperationsPage))
         );
     }
@@ -2,34 +2,51 @@
 
 use modular_bitfield_msb::prelude::*;
 
-use libscsi::Scsi;
+use libscsi::{
+    command::{ModePageField, PageControl},
+    Scsi, Transport,
+};
+
+use super::{read_page, ModeSetting};
 
 const PAGE_CODE: u8 = 0x1a;
 const PAGE_LENGTH: u8 = 0x26;
+const PAGE_SIZE: usize = PAGE_LENGTH as usize + 2;
+
+const FIELDS: &[ModePageField] = &[
+    ModePageField {
+        name: "Standby Z timer enable",
+        bytes: 3..4,
+        bits: 0x01,
+    },
+    ModePageField {
+        name: "Standby Z timer",
+        bytes: 8..12,
+        bits: 0xff,
+    },
+    ModePageField {
+        name: "Idle A timer enable",
+        bytes: 3..4,
+        bits: 0x02,
+    },
+    ModePageField {
+        name: "Idle A timer",
+        bytes: 4..8,
+        bits: 0xff,
+    },
+];
 
 #[bitfield]
 #[derive(Debug, Clone, Copy)]
 struct PowerConditionModePage {
-    /// header (start)
-    mode_data_length: B16,
-    medium_type: B8,
-    wp: B1,
-    reserved_0: B2,
-    dpofua: B1,
-    reserved_1: B4,
-    reserved: B7,
-    longlba: B1,
-    reserved_2: B8,
-    block_descriptor_length: B16,
-    /// header (end)
     parameter_savable: B1,
     spf: B1,
     page_code: B6,
     page_length: B8,
     pm_bg_precedence: B2,
-    reserved_3: B5,
+    reserved_0: B5,
     standby_y: B1,
-    reserved_4: B4,
+    reserved_1: B4,
     idle_c: B1,
     idle_b: B1,
     idle_a: B1,
@@ -39,11 +56,11 @@ struct PowerConditionModePage {
     idle_b_condition_timer: B32,
     idle_c_condition_timer: B32,
     standby_y_condition_timer: B32,
-    reserved_5: B120,
+    reserved_2: B120,
     ccf_idle: B2,
     ccf_standby: B2,
     ccf_stopped: B2,
-    reserved_6: B2,
+    reserved_3: B2,
 }
 
 impl Default for PowerConditionModePage {
@@ -58,15 +75,37 @@ impl PowerConditionModePage {
     }
 }
 
-fn read(device: &Scsi) -> crate::Result<PowerConditionModePage> {
-    Ok(device.mode_sense::<PowerConditionModePage>(PAGE_CODE)?)
+fn read<T: Transport>(
+    device: &Scsi<T>,
+    page_control: PageControl,
+) -> crate::Result<PowerConditionModePage> {
+    Ok(PowerConditionModePage::from_bytes(read_page(
+        device,
+        PAGE_CODE,
+        page_control,
+    )?))
 }
 
-pub fn get_sleep_timer(device: &Scsi) -> crate::Result<u32> {
-    Ok(read(device)?.get_sleep_timer())
+pub fn get_sleep_timer<T: Transport>(device: &Scsi<T>) -> crate::Result<u32> {
+    Ok(read(device, PageControl::Current)?.get_sleep_timer())
 }
 
-pub fn set_sleep_timer(device: &Scsi, sleep_timer: u32) -> crate::Result<()> {
+pub fn get_sleep_timer_setting<T: Transport>(device: &Scsi<T>) -> crate::Result<ModeSetting<u32>> {
+    let changeable = read(device, PageControl::Changeable)?;
+
+    Ok(ModeSetting {
+        current: read(device, PageControl::Current)?.get_sleep_timer(),
+        default: read(device, PageControl::Default)?.get_sleep_timer(),
+        changeable: changeable.standby_z() != 0 && changeable.standby_z_condition_timer() != 0,
+    })
+}
+
+/// With `save` the device keeps it across power cycles, or it fails if it can't.
+pub fn set_sleep_timer<T: Transport>(
+    device: &Scsi<T>,
+    sleep_timer: u32,
+    save: bool,
+) -> crate::Result<()> {
     let (enable_timer, sleep_timer) = if sleep_timer == 0 {
         (false, 0)
     } else {
@@ -74,13 +113,13 @@ pub fn set_sleep_timer(device: &Scsi, sleep_timer: u32) -> crate::Result<()> {
         (true, sleep_timer.clamp(60, 28800))
     };
 
-    let data = PowerConditionModePage::new()
-        .with_page_code(PAGE_CODE)
-        .with_page_length(PAGE_LENGTH)
+    let current = read(device, PageControl::Current)?;
+    let data = current
+        .with_parameter_savable(0)
         .with_standby_z(enable_timer as u8)
         .with_standby_z_condition_timer(sleep_timer.saturating_mul(10));
 
-    Ok(device.mode_select(data)?)
+    Ok(device.mode_select_page_checked(&current.into_bytes(), &data.into_bytes(), FIELDS, save)?)
 }
 
 #[cfg(test)]
@@ -88,13 +127,11 @@ mod tests {
     use super::*;
     use std::mem::size_of;
 
-    const POWER_CONDITION_MODE_PAGE_SIZE: usize = 8 + 40;
-
     #[test]
     fn layout_test() {
         assert_eq!(
             size_of::<PowerConditionModePage>(),
-            POWER_CONDITION_MODE_PAGE_SIZE,
+            PAGE_SIZE,
             concat!("Size of: ", stringify!(PowerConditionModePage))
         );
     }
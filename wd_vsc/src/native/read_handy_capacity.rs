@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 use modular_bitfield_msb::prelude::*;
 
 use libscsi::{
@@ -7,7 +9,7 @@ use libscsi::{
         sense::{BytesSenseBuffer, Sense},
         *,
     },
-    DataDirection, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, Transport,
 };
 
 const OPERATION_CODE: u8 = 0xd5;
@@ -64,6 +66,10 @@ impl Command for ThisCommand {
         Self::SenseBuffer::default()
     }
 
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(super::STATUS_COMMAND_TIMEOUT)
+    }
+
     fn process_result(
         &self,
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
@@ -82,7 +88,9 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn read_handy_capacity(scsi: &Scsi) -> crate::Result<HandyCapacity> {
+    pub(super) fn read_handy_capacity<T: Transport>(
+        scsi: &Scsi<T>,
+    ) -> crate::Result<HandyCapacity> {
         scsi.execute_command(&ThisCommand {})
     }
 }
@@ -8,7 +8,7 @@ use libscsi::{
         sense::{BytesSenseBuffer, Sense},
         *,
     },
-    DataDirection, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, Transport,
 };
 
 use crate::{Cipher, DATA_SIGNATURE};
@@ -28,6 +28,7 @@ struct ResetDataEncryptionKeyCommand {
     control: B8,
 }
 
+#[repr(C)]
 struct ResetDataEncryptionKeyData {
     signature: u8,
     reserved_0: [u8; 2],
@@ -106,8 +107,8 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn reset_data_encryption_key(
-        scsi: &Scsi,
+    pub(super) fn reset_data_encryption_key<T: Transport>(
+        scsi: &Scsi<T>,
         cipher: Cipher,
         key_reset_enabler: u32,
     ) -> crate::Result<()> {
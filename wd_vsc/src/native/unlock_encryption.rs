@@ -7,7 +7,7 @@ use libscsi::{
         sense::{FixedSenseBuffer, Sense},
         *,
     },
-    DataDirection, MaskedStatus, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, SenseKey, Transport,
 };
 
 use crate::{Error, Result, DATA_SIGNATURE};
@@ -26,6 +26,7 @@ struct UnlockEncryptionCommand {
     control: B8,
 }
 
+#[repr(C)]
 struct UnlockEncryptionData {
     signature: u8,
     reserved_0: [u8; 5],
@@ -81,24 +82,21 @@ impl Command for ThisCommand {
     ) -> Self::ReturnType {
         result.check_ioctl_error()?;
 
-        if result.masked_status == MaskedStatus::CHECK_CONDITION {
-            let sens_buffer = result.sense_buffer.as_ref().unwrap();
-            // ILLEGAL REQUEST
-            if sens_buffer.sense_key() == 0x05 {
-                if sens_buffer.additional_sense_code() == 0x74 {
-                    if sens_buffer.additional_sense_code_qualifier() == 0x81 {
-                        return Err(Error::NotLocked("Device is unlocked already.".to_owned()));
-                    } else if sens_buffer.additional_sense_code_qualifier() == 0x80 {
-                        return Err(Error::ExceedUnlockAttempts);
-                    } else if sens_buffer.additional_sense_code_qualifier() == 0x40 {
-                        return Err(Error::PasswordIncorrect);
-                    }
-                } else if (sens_buffer.additional_sense_code() == 0x24
-                    || sens_buffer.additional_sense_code() == 0x26)
-                    && sens_buffer.additional_sense_code_qualifier() == 0x00
-                {
-                    return Err(Error::PasswordBlobSizeIncorrect);
+        if let Some(sense) = result.sense_data() {
+            match (
+                sense.sense_key,
+                sense.additional_sense_code,
+                sense.additional_sense_code_qualifier,
+            ) {
+                (SenseKey::IllegalRequest, 0x74, 0x81) => {
+                    return Err(Error::NotLocked("Device is unlocked already.".to_owned()))
                 }
+                (SenseKey::IllegalRequest, 0x74, 0x80) => return Err(Error::ExceedUnlockAttempts),
+                (SenseKey::IllegalRequest, 0x74, 0x40) => return Err(Error::PasswordIncorrect),
+                (SenseKey::IllegalRequest, 0x24 | 0x26, 0x00) => {
+                    return Err(Error::PasswordBlobSizeIncorrect)
+                }
+                _ => {}
             }
         }
 
@@ -113,7 +111,7 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn unlock_encryption(scsi: &Scsi, password: Vec<u8>) -> Result<()> {
+    pub(super) fn unlock_encryption<T: Transport>(scsi: &Scsi<T>, password: Vec<u8>) -> Result<()> {
         if password.len() > MAX_PASSWORD_LENGTH {
             return Err(Error::PasswordBlobSizeIncorrect);
         }
@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 use modular_bitfield_msb::prelude::*;
 
 use libscsi::{
@@ -7,7 +9,7 @@ use libscsi::{
         sense::{BytesSenseBuffer, Sense},
         *,
     },
-    DataDirection, ResultData, Scsi,
+    DataDirection, ResultData, Scsi, Transport,
 };
 
 use crate::HANDY_STORE_BLOCK_SIZE;
@@ -60,6 +62,10 @@ impl Command for ThisCommand {
         Self::SenseBuffer::default()
     }
 
+    fn get_timeout(&self) -> Option<Duration> {
+        Some(super::STATUS_COMMAND_TIMEOUT)
+    }
+
     fn process_result(
         &self,
         result: &ResultData<Self::DataBuffer, Self::SenseBuffer>,
@@ -72,8 +78,8 @@ impl Command for ThisCommand {
 }
 
 impl super::WdVscWrapper {
-    pub(super) fn write_handy_store(
-        scsi: &Scsi,
+    pub(super) fn write_handy_store<T: Transport>(
+        scsi: &Scsi<T>,
         index: u32,
         data: [u8; HANDY_STORE_BLOCK_SIZE],
     ) -> crate::Result<()> {
@@ -1,4 +1,4 @@
-use libscsi::Scsi;
+use libscsi::{Scsi, Transport};
 use sha2::{Digest, Sha256};
 
 use crate::{
@@ -8,7 +8,7 @@ use crate::{
 };
 
 /// return: SecurityBlock, IsFromDisk
-pub fn read_security_block_or_default(device: &Scsi) -> (SecurityBlock, bool) {
+pub fn read_security_block_or_default<T: Transport>(device: &Scsi<T>) -> (SecurityBlock, bool) {
     match read_security_block(device) {
         Ok(b) => (b, true),
         Err(_) => (
@@ -79,8 +79,8 @@ pub fn create_password_blob(
 }
 
 #[allow(clippy::too_many_arguments)]
-pub fn change_password(
-    device: &Scsi,
+pub fn change_password<T: Transport>(
+    device: &Scsi<T>,
     status: &EncryptionStatus,
     new_password: Option<String>,
     old_password: Option<String>,
@@ -158,8 +158,8 @@ pub fn change_password(
     Ok(())
 }
 
-pub fn unwrap_salt_and_iteration_count(
-    device: Option<&Scsi>,
+pub fn unwrap_salt_and_iteration_count<T: Transport>(
+    device: Option<&Scsi<T>>,
     salt: Option<[u8; SALT_SIZE_FOR_U8]>,
     iteration_count: Option<u32>,
     default_salt_warning: Option<&str>,
@@ -1,6 +1,6 @@
 use std::{mem::size_of, slice};
 
-use libscsi::Scsi;
+use libscsi::{Scsi, Transport};
 
 use crate::{WdVsc, MAX_HINT_SIZE_FOR_U16};
 
@@ -27,7 +27,7 @@ struct SecurityBlockRaw {
     checksum: u8,
 }
 
-pub fn read_security_block(scsi: &Scsi) -> crate::Result<SecurityBlock> {
+pub fn read_security_block<T: Transport>(scsi: &Scsi<T>) -> crate::Result<SecurityBlock> {
     let raw_block = scsi.read_handy_store(SECURITY_BLOCK_INDEX)?;
     let (h, block, t) = unsafe { raw_block.as_slice().align_to::<SecurityBlockRaw>() };
     if !h.is_empty() || !t.is_empty() {
@@ -75,8 +75,8 @@ pub fn read_security_block(scsi: &Scsi) -> crate::Result<SecurityBlock> {
     })
 }
 
-pub fn write_security_block(
-    scsi: &Scsi,
+pub fn write_security_block<T: Transport>(
+    scsi: &Scsi<T>,
     iteration_count: u32,
     salt: [u8; 8],
     hint: String,
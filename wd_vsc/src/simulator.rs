@@ -0,0 +1,1564 @@
+//! A software model of a My Passport USB bridge, answering the commands wd_vsc sends.
+//!
+//! Use it with `Scsi::with_transport` to run the whole unlock / password / erase flow without a drive.
+//! Only built for tests and with the `simulator` feature.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use libscsi::{
+    command::sense::{FixedSenseBuffer, Sense},
+    DriverStatus, MaskedStatus, Transport, TransportRequest, TransportResult,
+};
+use rand::random;
+
+use crate::{Cipher, SecurityStatus, DATA_SIGNATURE, HANDY_STORE_BLOCK_SIZE};
+
+const TEST_UNIT_READY: u8 = 0x00;
+const REQUEST_SENSE: u8 = 0x03;
+const INQUIRY: u8 = 0x12;
+const START_STOP_UNIT: u8 = 0x1b;
+const READ_10: u8 = 0x28;
+const WRITE_10: u8 = 0x2a;
+const SEND_DIAGNOSTIC: u8 = 0x1d;
+const UNMAP: u8 = 0x42;
+const ATA_PASS_THROUGH_16: u8 = 0x85;
+const ATA_PASS_THROUGH_12: u8 = 0xa1;
+const LOG_SENSE: u8 = 0x4d;
+const READ_16: u8 = 0x88;
+const WRITE_16: u8 = 0x8a;
+const VERIFY_16: u8 = 0x8f;
+const WRITE_SAME_16: u8 = 0x93;
+const MODE_SELECT_10: u8 = 0x55;
+const MODE_SENSE_10: u8 = 0x5a;
+const SERVICE_ACTION_IN_16: u8 = 0x9e;
+const READ_CAPACITY_16_SERVICE_ACTION: u8 = 0x10;
+const ENCRYPTION_STATUS: u8 = 0xc0;
+const ENCRYPTION_STATUS_SUBCODE: u8 = 0x45;
+const SECURITY_COMMAND: u8 = 0xc1;
+const UNLOCK_ENCRYPTION_SUBCODE: u8 = 0xe1;
+const CHANGE_ENCRYPTION_PASSPHRASE_SUBCODE: u8 = 0xe2;
+const RESET_DATA_ENCRYPTION_KEY_SUBCODE: u8 = 0xe3;
+const READ_HANDY_CAPACITY: u8 = 0xd5;
+const READ_HANDY_STORE: u8 = 0xd8;
+const WRITE_HANDY_STORE: u8 = 0xda;
+
+const ENCRYPTION_STATUS_DATA_SIZE: usize = 0x30;
+const USE_VENDOR_OLD_PASSWORD: u8 = 0b00000001;
+const USE_VENDOR_NEW_PASSWORD: u8 = 0b00010000;
+const MAX_UNLOCK_ATTEMPTS: u8 = 5;
+/// TEST UNIT READY polls answered with BECOMING READY right after an unlock
+const BECOMING_READY_POLLS: u8 = 2;
+const LAST_HANDY_BLOCK_ADDRESS: u32 = 0xff;
+const MODE_PARAMETER_HEADER_LENGTH: usize = 8;
+const BLOCK_DESCRIPTOR_LENGTH: usize = 8;
+const LONG_BLOCK_DESCRIPTOR_LENGTH: usize = 16;
+
+const VENDOR_IDENTIFICATION: &[u8; 8] = b"WD      ";
+const PRODUCT_IDENTIFICATION: &[u8; 16] = b"My Passport 25E2";
+const PRODUCT_REVISION_LEVEL: &[u8; 4] = b"4004";
+const UNIT_SERIAL_NUMBER: &[u8; 12] = b"WX11A1234567";
+const WORLD_WIDE_NAME: u64 = 0x5001_4ee2_b123_4567;
+const MODE_PAGE_CODES: [u8; 3] = [0x1a, 0x20, 0x21];
+const SUPPORTED_VPD_PAGES: [u8; 5] = [0x00, 0x80, 0x83, 0xb0, 0xb2];
+/// SAM-5, SPC-4, SBC-3
+const VERSION_DESCRIPTORS: [u16; 3] = [0x00a0, 0x0460, 0x04c0];
+const LOGICAL_BLOCK_COUNT: u64 = 3_906_963_456;
+const LOGICAL_BLOCK_LENGTH: u32 = 512;
+const MAXIMUM_UNMAP_LBA_COUNT: u32 = 0x0040_0000;
+const MAXIMUM_UNMAP_BLOCK_DESCRIPTOR_COUNT: u32 = 8;
+const MAXIMUM_WRITE_SAME_LENGTH: u64 = 0x0100_0000;
+const MAXIMUM_TRANSFER_LENGTH: u32 = 0x0000_0800;
+const SUPPORTED_LOG_PAGES: [u8; 5] = [0x00, 0x0d, 0x0e, 0x10, 0x2f];
+const TEMPERATURE: u8 = 35;
+const REFERENCE_TEMPERATURE: u8 = 60;
+const SELF_TEST_LOG_LENGTH: usize = 20;
+const SELF_TEST_RESULT_PARAMETER_LENGTH: u8 = 0x10;
+/// REQUEST SENSE polls a background self-test takes to finish
+const SELF_TEST_POLLS: u8 = 4;
+const SELF_TEST_COMPLETED: u8 = 0x0;
+const SELF_TEST_ABORTED_BY_SEND_DIAGNOSTIC: u8 = 0x1;
+const SELF_TEST_FAILED_OTHER_SEGMENT: u8 = 0x7;
+const SELF_TEST_IN_PROGRESS: u8 = 0xf;
+const ATA_SMART: u8 = 0xb0;
+const ATA_CHECK_POWER_MODE: u8 = 0xe5;
+const ATA_IDENTIFY_DEVICE: u8 = 0xec;
+const SMART_READ_DATA: u16 = 0xd0;
+const SMART_READ_THRESHOLDS: u16 = 0xd1;
+const SMART_RETURN_STATUS: u16 = 0xda;
+const SMART_SIGNATURE: u64 = 0xc2_4f00;
+const SMART_THRESHOLD_EXCEEDED_SIGNATURE: u64 = 0x2c_f400;
+const SMART_DATA_REVISION: u16 = 0x0010;
+const REALLOCATED_SECTOR_COUNT_THRESHOLD: u8 = 140;
+/// Id, flags, threshold
+const SMART_ATTRIBUTES: [(u8, u16, u8); 5] = [
+    (5, 0x0033, REALLOCATED_SECTOR_COUNT_THRESHOLD),
+    (9, 0x0032, 0),
+    (12, 0x0032, 0),
+    (194, 0x0022, 0),
+    (197, 0x0032, 0),
+];
+const ATA_STATUS_DRDY: u8 = 0x40;
+const ATA_STATUS_ERR: u8 = 0x01;
+const ATA_ERROR_ABORT: u8 = 0x04;
+const ATA_STATUS_RETURN_DESCRIPTOR_LENGTH: usize = 14;
+const ATA_SECTOR_SIZE: usize = 512;
+const DISK_MODEL_NUMBER: &str = "WDC WD20SDRW-11VUUS0";
+const DISK_SERIAL_NUMBER: &str = "WD-WX11A1234567";
+const DISK_FIRMWARE_REVISION: &str = "01.01A01";
+/// A little more than the bridge reports, it keeps some sectors for itself
+const DISK_SECTOR_COUNT: u64 = 3_907_029_168;
+const DISK_ROTATION_RATE: u16 = 5400;
+
+/// Sense key, additional sense code, additional sense code qualifier
+type SenseTriple = (u8, u8, u8);
+
+const INVALID_COMMAND_OPERATION_CODE: SenseTriple = (0x05, 0x20, 0x00);
+const LBA_OUT_OF_RANGE: SenseTriple = (0x05, 0x21, 0x00);
+const INVALID_FIELD_IN_CDB: SenseTriple = (0x05, 0x24, 0x00);
+const INVALID_FIELD_IN_PARAMETER_LIST: SenseTriple = (0x05, 0x26, 0x00);
+const SAVING_PARAMETERS_NOT_SUPPORTED: SenseTriple = (0x05, 0x39, 0x00);
+const COMMAND_SEQUENCE_ERROR: SenseTriple = (0x05, 0x2c, 0x00);
+const PASSWORD_INCORRECT: SenseTriple = (0x05, 0x74, 0x40);
+const UNLOCK_ATTEMPTS_EXCEEDED: SenseTriple = (0x05, 0x74, 0x80);
+const ALREADY_UNLOCKED: SenseTriple = (0x05, 0x74, 0x81);
+const BECOMING_READY: SenseTriple = (0x02, 0x04, 0x01);
+const POWER_ON_OCCURRED: SenseTriple = (0x06, 0x29, 0x01);
+const ACCESS_NOT_AUTHORIZED: SenseTriple = (0x07, 0x74, 0x71);
+const ATA_PASS_THROUGH_INFORMATION_AVAILABLE: SenseTriple = (0x01, 0x00, 0x1d);
+const ATA_COMMAND_ABORTED: SenseTriple = (0x0b, 0x00, 0x00);
+
+#[derive(Debug)]
+pub struct MyPassportSimulator {
+    state: Mutex<SimulatorState>,
+}
+
+#[derive(Debug)]
+struct SimulatorState {
+    password: Option<Vec<u8>>,
+    locked: bool,
+    failed_unlock_attempts: u8,
+    becoming_ready_polls: u8,
+    /// Reported once with the next command, INQUIRY excepted
+    unit_attention: Option<SenseTriple>,
+    power_state: PowerState,
+    start_stop_cycles: u32,
+    load_unload_cycles: u32,
+    /// Most recent first
+    self_test_log: Vec<SelfTestLogEntry>,
+    /// REQUEST SENSE polls left until the running background self-test finishes
+    self_test_polls: u8,
+    /// Make self-tests fail at this logical block address
+    self_test_failure: Option<u64>,
+    /// Let the reallocated sector count fall below its threshold
+    smart_failure: bool,
+    current_cipher: Cipher,
+    supported_ciphers: Vec<Cipher>,
+    key_reset_enabler: u32,
+    handy_store: HashMap<u32, [u8; HANDY_STORE_BLOCK_SIZE]>,
+    mode_pages: HashMap<u8, Vec<u8>>,
+    /// Restored on power cycles, written by MODE SELECT with SP set
+    saved_mode_pages: HashMap<u8, Vec<u8>>,
+    /// Logical blocks written so far, the others read as zeros
+    blocks: HashMap<u64, Vec<u8>>,
+    unmap_supported: bool,
+    write_same_supported: bool,
+    /// Sent as descriptor format sense data with the response to an ATA PASS-THROUGH command
+    ata_status_return: Option<[u8; ATA_STATUS_RETURN_DESCRIPTOR_LENGTH]>,
+    last_timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerState {
+    Active,
+    Idle,
+    Standby,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SelfTestLogEntry {
+    self_test_code: u8,
+    result: u8,
+    address_of_first_failure: Option<u64>,
+}
+
+type Response = Result<usize, SenseTriple>;
+
+/// Registers of the SATA disk behind the bridge
+#[derive(Debug, Clone, Copy, Default)]
+struct AtaRegisters {
+    extend: bool,
+    /// Command on input, status on output
+    command_status: u8,
+    /// Features on input, error on output
+    features_error: u16,
+    count: u16,
+    lba: u64,
+    device: u8,
+}
+
+type AtaResponse = Result<(usize, AtaRegisters), AtaRegisters>;
+
+impl Default for MyPassportSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MyPassportSimulator {
+    /// A drive fresh out of the box, encrypted with AES-256-XTS and no user password.
+    pub fn new() -> Self {
+        let mode_pages: HashMap<u8, Vec<u8>> = MODE_PAGE_CODES
+            .iter()
+            .map(|page_code| (*page_code, default_mode_page(*page_code)))
+            .collect();
+
+        MyPassportSimulator {
+            state: Mutex::new(SimulatorState {
+                password: None,
+                locked: false,
+                failed_unlock_attempts: 0,
+                becoming_ready_polls: 0,
+                unit_attention: None,
+                power_state: PowerState::Active,
+                start_stop_cycles: 0,
+                load_unload_cycles: 0,
+                self_test_log: vec![],
+                self_test_polls: 0,
+                self_test_failure: None,
+                smart_failure: false,
+                current_cipher: Cipher::Aes256Xts,
+                supported_ciphers: vec![Cipher::Aes256Xts, Cipher::FullDiscEncryption],
+                key_reset_enabler: random(),
+                handy_store: HashMap::new(),
+                saved_mode_pages: mode_pages.clone(),
+                mode_pages,
+                blocks: HashMap::new(),
+                unmap_supported: true,
+                write_same_supported: true,
+                ata_status_return: None,
+                last_timeout: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// A drive protected by `password_blob`, as it looks right after being plugged in.
+    pub fn with_password(password_blob: Vec<u8>) -> Self {
+        let simulator = Self::new();
+        {
+            let mut state = simulator.state.lock().unwrap();
+            state.password = Some(password_blob);
+            state.locked = true;
+        }
+
+        simulator
+    }
+
+    pub fn security_status(&self) -> SecurityStatus {
+        self.state.lock().unwrap().security_status()
+    }
+
+    pub fn handy_store_block(&self, index: u32) -> [u8; HANDY_STORE_BLOCK_SIZE] {
+        self.state
+            .lock()
+            .unwrap()
+            .handy_store
+            .get(&index)
+            .copied()
+            .unwrap_or([0; HANDY_STORE_BLOCK_SIZE])
+    }
+
+    pub fn power_state(&self) -> PowerState {
+        self.state.lock().unwrap().power_state
+    }
+
+    /// Let the following self-tests fail at `address`, or pass again with `None`.
+    pub fn set_self_test_failure(&self, address: Option<u64>) {
+        self.state.lock().unwrap().self_test_failure = address;
+    }
+
+    /// Let the SMART status and the reallocated sector count attribute report a failing drive.
+    pub fn set_smart_failure(&self, failing: bool) {
+        self.state.lock().unwrap().smart_failure = failing;
+    }
+
+    /// Let UNMAP fail, like bridges that don't pass it through.
+    pub fn set_unmap_support(&self, supported: bool) {
+        self.state.lock().unwrap().unmap_supported = supported;
+    }
+
+    /// Let WRITE SAME fail, like bridges that don't pass it through.
+    pub fn set_write_same_support(&self, supported: bool) {
+        self.state.lock().unwrap().write_same_supported = supported;
+    }
+
+    /// Number of logical blocks that hold data
+    pub fn mapped_block_count(&self) -> usize {
+        self.state.lock().unwrap().blocks.len()
+    }
+
+    /// Timeout the most recent command was sent with
+    pub fn last_timeout(&self) -> Duration {
+        self.state.lock().unwrap().last_timeout
+    }
+
+    /// The next command is answered with a power on unit attention, like right after plugging in.
+    pub fn report_power_on(&self) {
+        self.state.lock().unwrap().unit_attention = Some(POWER_ON_OCCURRED);
+    }
+
+    /// Unplug and replug the drive: it locks again and the unlock attempt counter is cleared.
+    pub fn power_cycle(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.locked = state.password.is_some();
+        state.failed_unlock_attempts = 0;
+        state.power_state = PowerState::Active;
+        state.mode_pages = state.saved_mode_pages.clone();
+    }
+}
+
+impl Transport for MyPassportSimulator {
+    fn transport(&self, request: TransportRequest) -> TransportResult {
+        let mut state = self.state.lock().unwrap();
+        state.last_timeout = request.timeout;
+
+        let response = state.execute(request.command, request.data);
+
+        if let Some(descriptor) = state.ata_status_return.take() {
+            let (transfered_data_length, sense) = match response {
+                Ok(length) => (length, ATA_PASS_THROUGH_INFORMATION_AVAILABLE),
+                Err(sense) => (0, sense),
+            };
+            let (sense_key, additional_sense_code, additional_sense_code_qualifier) = sense;
+            let header = [
+                0x72,
+                sense_key,
+                additional_sense_code,
+                additional_sense_code_qualifier,
+                0x00,
+                0x00,
+                0x00,
+                ATA_STATUS_RETURN_DESCRIPTOR_LENGTH as u8,
+            ];
+            let transfered_sense_length =
+                copy_response(&[&header, &descriptor[..]].concat(), request.sense);
+
+            return TransportResult {
+                transfered_sense_length,
+                masked_status: MaskedStatus::CHECK_CONDITION,
+                driver_status: DriverStatus::SENSE,
+                ..TransportResult::good(transfered_data_length)
+            };
+        }
+
+        match response {
+            Ok(transfered_data_length) => TransportResult::good(transfered_data_length),
+            Err((sense_key, additional_sense_code, additional_sense_code_qualifier)) => {
+                let sense = FixedSenseBuffer::new()
+                    .with_response_code(0x70)
+                    .with_sense_key(sense_key)
+                    .with_additional_sense_length(10)
+                    .with_additional_sense_code(additional_sense_code)
+                    .with_additional_sense_code_qualifier(additional_sense_code_qualifier);
+                // only the first 18 bytes are meaningful for fixed format sense data
+                let transfered_sense_length = usize::min(request.sense.len(), 18);
+                request.sense[..transfered_sense_length]
+                    .copy_from_slice(&sense.as_byte_slice()[..transfered_sense_length]);
+
+                TransportResult {
+                    transfered_sense_length,
+                    masked_status: MaskedStatus::CHECK_CONDITION,
+                    driver_status: DriverStatus::SENSE,
+                    ..TransportResult::good(0)
+                }
+            }
+        }
+    }
+}
+
+impl SimulatorState {
+    fn security_status(&self) -> SecurityStatus {
+        if self.current_cipher == Cipher::NoEncryption {
+            SecurityStatus::NoEncryption
+        } else if self.failed_unlock_attempts >= MAX_UNLOCK_ATTEMPTS {
+            SecurityStatus::UnlockAttemptExceeded
+        } else if self.password.is_none() {
+            SecurityStatus::NoUserPassword
+        } else if self.locked {
+            SecurityStatus::Locked
+        } else {
+            SecurityStatus::Unlocked
+        }
+    }
+
+    fn execute(&mut self, command: &[u8], data: &mut [u8]) -> Response {
+        if command.first() != Some(&INQUIRY) {
+            if let Some(sense) = self.unit_attention.take() {
+                return Err(sense);
+            }
+        }
+
+        match (command.first(), command.get(1)) {
+            (Some(&TEST_UNIT_READY), _) => self.test_unit_ready(),
+            (Some(&REQUEST_SENSE), _) => self.request_sense(command, data),
+            (Some(&INQUIRY), _) => self.inquiry(command, data),
+            (Some(&START_STOP_UNIT), _) => self.start_stop_unit(command),
+            (Some(&SEND_DIAGNOSTIC), _) => self.send_diagnostic(command),
+            (Some(&UNMAP), _) => self.unmap(data),
+            (Some(&WRITE_SAME_16), _) => self.write_same(command, data),
+            (Some(&READ_10 | &READ_16), _) => self.read(command, data),
+            (Some(&WRITE_10 | &WRITE_16), _) => self.write(command, data),
+            (Some(&VERIFY_16), _) => self.verify(command),
+            (Some(&ATA_PASS_THROUGH_12 | &ATA_PASS_THROUGH_16), _) => {
+                self.ata_pass_through(command, data)
+            }
+            (Some(&LOG_SENSE), _) => self.log_sense(command, data),
+            (Some(&MODE_SENSE_10), _) => self.mode_sense(command, data),
+            (Some(&MODE_SELECT_10), _) => self.mode_select(command, data),
+            (Some(&SERVICE_ACTION_IN_16), Some(s))
+                if s & 0x1f == READ_CAPACITY_16_SERVICE_ACTION =>
+            {
+                read_capacity(data)
+            }
+            (Some(&ENCRYPTION_STATUS), Some(&ENCRYPTION_STATUS_SUBCODE)) => {
+                self.encryption_status(data)
+            }
+            (Some(&SECURITY_COMMAND), Some(&UNLOCK_ENCRYPTION_SUBCODE)) => {
+                self.unlock_encryption(data)
+            }
+            (Some(&SECURITY_COMMAND), Some(&CHANGE_ENCRYPTION_PASSPHRASE_SUBCODE)) => {
+                self.change_encryption_passphrase(data)
+            }
+            (Some(&SECURITY_COMMAND), Some(&RESET_DATA_ENCRYPTION_KEY_SUBCODE)) => {
+                self.reset_data_encryption_key(command, data)
+            }
+            (Some(&READ_HANDY_CAPACITY), _) => read_handy_capacity(data),
+            (Some(&READ_HANDY_STORE), _) => self.read_handy_store(command, data),
+            (Some(&WRITE_HANDY_STORE), _) => self.write_handy_store(command, data),
+            _ => Err(INVALID_COMMAND_OPERATION_CODE),
+        }
+    }
+
+    fn test_unit_ready(&mut self) -> Response {
+        if self.locked {
+            return Err(ACCESS_NOT_AUTHORIZED);
+        }
+
+        if self.becoming_ready_polls > 0 {
+            self.becoming_ready_polls -= 1;
+            return Err(BECOMING_READY);
+        }
+
+        Ok(0)
+    }
+
+    fn start_stop_unit(&mut self, command: &[u8]) -> Response {
+        let power_condition = command[4] >> 4;
+        let start = command[4] & 0x01 != 0;
+        let previous_power_state = self.power_state;
+        self.power_state = match power_condition {
+            // START VALID
+            0x0 if start => PowerState::Active,
+            0x0 => PowerState::Stopped,
+            0x1 | 0x7 => PowerState::Active,
+            0x2 | 0xa => PowerState::Idle,
+            0x3 | 0xb => PowerState::Standby,
+            _ => return Err(INVALID_FIELD_IN_CDB),
+        };
+
+        match (previous_power_state, self.power_state) {
+            (PowerState::Stopped, PowerState::Active | PowerState::Idle) => {
+                self.start_stop_cycles += 1
+            }
+            (PowerState::Active, PowerState::Idle | PowerState::Standby | PowerState::Stopped) => {
+                self.load_unload_cycles += 1
+            }
+            _ => {}
+        }
+
+        Ok(0)
+    }
+
+    fn log_sense(&self, command: &[u8], data: &mut [u8]) -> Response {
+        let page_code = command[2] & 0x3f;
+
+        let parameters = match page_code {
+            0x00 => SUPPORTED_LOG_PAGES.to_vec(),
+            // Temperature
+            0x0d => [
+                log_parameter(0x0000, &[0x00, TEMPERATURE]),
+                log_parameter(0x0001, &[0x00, REFERENCE_TEMPERATURE]),
+            ]
+            .concat(),
+            // Start-Stop Cycle Counter
+            0x0e => [
+                log_parameter(0x0001, b"202114"),
+                log_parameter(0x0003, &50_000u32.to_be_bytes()),
+                log_parameter(0x0004, &self.start_stop_cycles.to_be_bytes()),
+                log_parameter(0x0005, &300_000u32.to_be_bytes()),
+                log_parameter(0x0006, &self.load_unload_cycles.to_be_bytes()),
+            ]
+            .concat(),
+            // Self-Test Results
+            0x10 => (0..SELF_TEST_LOG_LENGTH)
+                .flat_map(|i| {
+                    let mut value = [0u8; SELF_TEST_RESULT_PARAMETER_LENGTH as usize];
+                    if let Some(entry) = self.self_test_log.get(i) {
+                        value[0] = entry.self_test_code << 5 | entry.result;
+                        value[1] = (self.self_test_log.len() - i) as u8;
+                        let address = entry.address_of_first_failure.unwrap_or(u64::MAX);
+                        value[4..12].copy_from_slice(&address.to_be_bytes());
+                        if entry.address_of_first_failure.is_some() {
+                            // MEDIUM ERROR, UNRECOVERED READ ERROR
+                            value[12..15].copy_from_slice(&[0x03, 0x11, 0x00]);
+                        }
+                    }
+                    log_parameter(i as u16 + 1, &value)
+                })
+                .collect(),
+            // Informational Exceptions
+            0x2f => log_parameter(0x0000, &[0x00, 0x00, TEMPERATURE]),
+            _ => return Err(INVALID_FIELD_IN_CDB),
+        };
+
+        let mut response = vec![page_code, 0x00];
+        response.extend_from_slice(&(parameters.len() as u16).to_be_bytes());
+        response.extend_from_slice(&parameters);
+
+        Ok(copy_response(&response, data))
+    }
+
+    fn send_diagnostic(&mut self, command: &[u8]) -> Response {
+        let self_test_code = command[1] >> 5;
+        let background_self_test_running = self.self_test_polls > 0;
+
+        match self_test_code {
+            // default self-test
+            0b000 => return Ok(0),
+            // abort background self-test
+            0b100 if background_self_test_running => {
+                self.self_test_polls = 0;
+                self.self_test_log[0].result = SELF_TEST_ABORTED_BY_SEND_DIAGNOSTIC;
+                return Ok(0);
+            }
+            0b100 => return Err(COMMAND_SEQUENCE_ERROR),
+            0b001 | 0b010 | 0b101 | 0b110 if !background_self_test_running => {}
+            0b001 | 0b010 | 0b101 | 0b110 => return Err(COMMAND_SEQUENCE_ERROR),
+            _ => return Err(INVALID_FIELD_IN_CDB),
+        }
+
+        self.self_test_log.insert(
+            0,
+            SelfTestLogEntry {
+                self_test_code,
+                result: SELF_TEST_IN_PROGRESS,
+                address_of_first_failure: None,
+            },
+        );
+        self.self_test_log.truncate(SELF_TEST_LOG_LENGTH);
+
+        match self_test_code {
+            // background
+            0b001 | 0b010 => self.self_test_polls = SELF_TEST_POLLS,
+            // foreground, finished once we return
+            _ => self.finish_self_test(),
+        }
+
+        Ok(0)
+    }
+
+    fn finish_self_test(&mut self) {
+        let entry = &mut self.self_test_log[0];
+        match self.self_test_failure {
+            Some(address) => {
+                entry.result = SELF_TEST_FAILED_OTHER_SEGMENT;
+                entry.address_of_first_failure = Some(address);
+            }
+            None => entry.result = SELF_TEST_COMPLETED,
+        }
+    }
+
+    /// Only reports the progress of a background self-test, errors are reported along with the
+    /// failing command.
+    fn request_sense(&mut self, command: &[u8], data: &mut [u8]) -> Response {
+        let descriptor_format = command[1] & 0x01 != 0;
+
+        let mut sense_key_specific = [0u8; 3];
+        if self.self_test_polls > 0 {
+            let done = (SELF_TEST_POLLS - self.self_test_polls) as u32;
+            let progress = (done * 0x10000 / SELF_TEST_POLLS as u32) as u16;
+            sense_key_specific[0] = 0x80;
+            sense_key_specific[1..].copy_from_slice(&progress.to_be_bytes());
+
+            self.self_test_polls -= 1;
+            if self.self_test_polls == 0 {
+                self.finish_self_test();
+            }
+        }
+
+        let mut response = vec![0u8; 18];
+        if descriptor_format {
+            response[0] = 0x72;
+            if sense_key_specific[0] != 0 {
+                response[7] = 8;
+                response[8..12].copy_from_slice(&[0x02, 0x06, 0x00, 0x00]);
+                response[12..15].copy_from_slice(&sense_key_specific);
+                response.truncate(16);
+            } else {
+                response.truncate(8);
+            }
+        } else {
+            response[0] = 0x70;
+            response[7] = 10;
+            response[15..18].copy_from_slice(&sense_key_specific);
+        }
+
+        Ok(copy_response(&response, data))
+    }
+
+    fn ata_pass_through(&mut self, command: &[u8], data: &mut [u8]) -> Response {
+        let check_condition = command[2] & 0x20 != 0;
+        let input = match command[0] {
+            ATA_PASS_THROUGH_12 => AtaRegisters {
+                extend: false,
+                command_status: command[9],
+                features_error: command[3] as u16,
+                count: command[4] as u16,
+                lba: u64::from_be_bytes([
+                    0,
+                    0,
+                    0,
+                    0,
+                    command[8] & 0x0f,
+                    command[7],
+                    command[6],
+                    command[5],
+                ]),
+                device: command[8],
+            },
+            _ => AtaRegisters {
+                extend: command[1] & 0x01 != 0,
+                command_status: command[14],
+                features_error: u16::from_be_bytes([command[3], command[4]]),
+                count: u16::from_be_bytes([command[5], command[6]]),
+                lba: u64::from_be_bytes([
+                    0,
+                    0,
+                    command[11],
+                    command[9],
+                    command[7],
+                    command[12],
+                    command[10],
+                    command[8],
+                ]),
+                device: command[13],
+            },
+        };
+
+        match self.execute_ata(&input, data) {
+            Ok((length, output)) => {
+                if check_condition {
+                    self.ata_status_return = Some(ata_status_return(&output));
+                }
+                Ok(length)
+            }
+            Err(output) => {
+                self.ata_status_return = Some(ata_status_return(&output));
+                Err(ATA_COMMAND_ABORTED)
+            }
+        }
+    }
+
+    fn execute_ata(&mut self, input: &AtaRegisters, data: &mut [u8]) -> AtaResponse {
+        let output = AtaRegisters {
+            command_status: ATA_STATUS_DRDY,
+            features_error: 0,
+            ..*input
+        };
+        let aborted = AtaRegisters {
+            command_status: ATA_STATUS_DRDY | ATA_STATUS_ERR,
+            features_error: ATA_ERROR_ABORT as u16,
+            ..output
+        };
+
+        match input.command_status {
+            ATA_SMART if input.lba & 0xff_ff00 != SMART_SIGNATURE => Err(aborted),
+            ATA_SMART => match input.features_error {
+                SMART_READ_DATA => Ok((copy_response(&self.smart_data(), data), output)),
+                SMART_READ_THRESHOLDS => Ok((copy_response(&smart_thresholds(), data), output)),
+                SMART_RETURN_STATUS => {
+                    let lba = match self.smart_failure {
+                        true => SMART_THRESHOLD_EXCEEDED_SIGNATURE,
+                        false => SMART_SIGNATURE,
+                    };
+                    Ok((0, AtaRegisters { lba, ..output }))
+                }
+                _ => Err(aborted),
+            },
+            ATA_IDENTIFY_DEVICE => Ok((copy_response(&identify_device(), data), output)),
+            ATA_CHECK_POWER_MODE => {
+                let count = match self.power_state {
+                    PowerState::Active => 0xff,
+                    PowerState::Idle => 0x80,
+                    PowerState::Standby | PowerState::Stopped => 0x00,
+                };
+                Ok((0, AtaRegisters { count, ..output }))
+            }
+            _ => Err(aborted),
+        }
+    }
+
+    fn smart_data(&self) -> [u8; ATA_SECTOR_SIZE] {
+        let mut sector = [0u8; ATA_SECTOR_SIZE];
+        sector[..2].copy_from_slice(&SMART_DATA_REVISION.to_le_bytes());
+
+        for (index, (id, flags, _)) in SMART_ATTRIBUTES.into_iter().enumerate() {
+            let (current, raw_value) = match id {
+                5 if self.smart_failure => (REALLOCATED_SECTOR_COUNT_THRESHOLD - 40, 2000),
+                9 => (98, 1234),
+                12 => (100, self.start_stop_cycles as u64 + 42),
+                194 => (100 - TEMPERATURE, TEMPERATURE as u64),
+                _ => (200, 0),
+            };
+
+            let entry = &mut sector[2 + index * 12..][..12];
+            entry[0] = id;
+            entry[1..3].copy_from_slice(&flags.to_le_bytes());
+            entry[3] = current;
+            entry[4] = current;
+            entry[5..11].copy_from_slice(&raw_value.to_le_bytes()[..6]);
+        }
+
+        with_checksum(sector)
+    }
+
+    fn inquiry(&self, command: &[u8], data: &mut [u8]) -> Response {
+        let enable_vpd = command[1] & 0x01 != 0;
+        let page_code = command[2];
+
+        let mut response = vec![0u8; 96];
+        match (enable_vpd, page_code) {
+            (false, _) => {
+                response[2] = 0x06;
+                response[3] = 0x02;
+                response[4] = 91;
+                response[8..16].copy_from_slice(VENDOR_IDENTIFICATION);
+                response[16..32].copy_from_slice(PRODUCT_IDENTIFICATION);
+                response[32..36].copy_from_slice(PRODUCT_REVISION_LEVEL);
+                for (i, descriptor) in VERSION_DESCRIPTORS.iter().enumerate() {
+                    response[58 + i * 2..60 + i * 2].copy_from_slice(&descriptor.to_be_bytes());
+                }
+            }
+            // Supported VPD Pages
+            (true, 0x00) => {
+                response[3] = SUPPORTED_VPD_PAGES.len() as u8;
+                response[4..4 + SUPPORTED_VPD_PAGES.len()].copy_from_slice(&SUPPORTED_VPD_PAGES);
+                response.truncate(4 + SUPPORTED_VPD_PAGES.len());
+            }
+            // Unit Serial Number
+            (true, 0x80) => {
+                response[1] = 0x80;
+                response[3] = UNIT_SERIAL_NUMBER.len() as u8;
+                response[4..4 + UNIT_SERIAL_NUMBER.len()].copy_from_slice(UNIT_SERIAL_NUMBER);
+                response.truncate(4 + UNIT_SERIAL_NUMBER.len());
+            }
+            // Device Identification, a single NAA designator
+            (true, 0x83) => {
+                response[1] = 0x83;
+                response[3] = 12;
+                response[4] = 0x01;
+                response[5] = 0x03;
+                response[7] = 8;
+                response[8..16].copy_from_slice(&WORLD_WIDE_NAME.to_be_bytes());
+                response.truncate(16);
+            }
+            // Block Limits
+            (true, 0xb0) => {
+                response[1] = 0xb0;
+                response[3] = 0x3c;
+                response[8..12].copy_from_slice(&MAXIMUM_TRANSFER_LENGTH.to_be_bytes());
+                response[20..24].copy_from_slice(&MAXIMUM_UNMAP_LBA_COUNT.to_be_bytes());
+                response[24..28]
+                    .copy_from_slice(&MAXIMUM_UNMAP_BLOCK_DESCRIPTOR_COUNT.to_be_bytes());
+                response[36..44].copy_from_slice(&MAXIMUM_WRITE_SAME_LENGTH.to_be_bytes());
+                response.truncate(64);
+            }
+            // Logical Block Provisioning
+            (true, 0xb2) => {
+                response[1] = 0xb2;
+                response[3] = 0x04;
+                // LBPU and LBPWS
+                response[5] =
+                    (self.unmap_supported as u8) << 7 | (self.write_same_supported as u8) << 6;
+                response.truncate(8);
+            }
+            _ => return Err(INVALID_FIELD_IN_CDB),
+        }
+
+        Ok(copy_response(&response, data))
+    }
+
+    fn mode_sense(&self, command: &[u8], data: &mut [u8]) -> Response {
+        let long_lba_accepted = command[1] & 0x10 != 0;
+        let disable_block_descriptors = command[1] & 0x08 != 0;
+        let page_control = command[2] >> 6;
+        let page_code = command[2] & 0x3f;
+        let subpage_code = command[3];
+        if subpage_code != 0 {
+            return Err(INVALID_FIELD_IN_CDB);
+        }
+        let current_page = self
+            .mode_pages
+            .get(&page_code)
+            .ok_or(INVALID_FIELD_IN_CDB)?;
+        let page = match page_control {
+            0b00 => current_page.clone(),
+            0b01 => changeable_mask(page_code),
+            0b11 => self.saved_mode_pages[&page_code].clone(),
+            _ => default_mode_page(page_code),
+        };
+
+        let mut response = vec![0u8; MODE_PARAMETER_HEADER_LENGTH];
+        if !disable_block_descriptors && long_lba_accepted {
+            response[4] = 0x01;
+            response[7] = LONG_BLOCK_DESCRIPTOR_LENGTH as u8;
+            let mut block_descriptor = [0u8; LONG_BLOCK_DESCRIPTOR_LENGTH];
+            block_descriptor[..8].copy_from_slice(&LOGICAL_BLOCK_COUNT.to_be_bytes());
+            block_descriptor[12..].copy_from_slice(&LOGICAL_BLOCK_LENGTH.to_be_bytes());
+            response.extend_from_slice(&block_descriptor);
+        } else if !disable_block_descriptors {
+            response[7] = BLOCK_DESCRIPTOR_LENGTH as u8;
+            let mut block_descriptor = [0u8; BLOCK_DESCRIPTOR_LENGTH];
+            block_descriptor[..4].copy_from_slice(
+                &(u32::try_from(LOGICAL_BLOCK_COUNT).unwrap_or(u32::MAX)).to_be_bytes(),
+            );
+            block_descriptor[5..].copy_from_slice(&LOGICAL_BLOCK_LENGTH.to_be_bytes()[1..]);
+            response.extend_from_slice(&block_descriptor);
+        }
+        response.extend_from_slice(&page);
+
+        let mode_data_length = (response.len() - 2) as u16;
+        response[..2].copy_from_slice(&mode_data_length.to_be_bytes());
+
+        Ok(copy_response(&response, data))
+    }
+
+    fn mode_select(&mut self, command: &[u8], data: &[u8]) -> Response {
+        let save_pages = command[1] & 0x01 != 0;
+        let parameter_list_length = u16::from_be_bytes([command[7], command[8]]) as usize;
+        let data = &data[..usize::min(parameter_list_length, data.len())];
+        if data.len() < MODE_PARAMETER_HEADER_LENGTH {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        let block_descriptor_length = u16::from_be_bytes([data[6], data[7]]) as usize;
+        let page = data
+            .get(MODE_PARAMETER_HEADER_LENGTH + block_descriptor_length..)
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+        if page.len() < 2 {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        let page_code = page[0] & 0x3f;
+        let page_length = page[1] as usize;
+        let current_page = self
+            .mode_pages
+            .get_mut(&page_code)
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+        if page.len() < page_length + 2 || current_page.len() != page_length + 2 {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        let mask = changeable_mask(page_code);
+        let unchangeable =
+            (2..current_page.len()).any(|i| (current_page[i] ^ page[i]) & !mask[i] != 0);
+        if unchangeable {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        // keep the PS bit, it's reserved in MODE SELECT
+        let parameter_savable = current_page[0] & 0x80;
+        if save_pages && parameter_savable == 0 {
+            return Err(SAVING_PARAMETERS_NOT_SUPPORTED);
+        }
+        current_page.copy_from_slice(&page[..page_length + 2]);
+        current_page[0] = parameter_savable | page_code;
+        if save_pages {
+            let current_page = current_page.clone();
+            self.saved_mode_pages.insert(page_code, current_page);
+        }
+
+        Ok(0)
+    }
+
+    fn encryption_status(&self, data: &mut [u8]) -> Response {
+        let mut response = [0u8; ENCRYPTION_STATUS_DATA_SIZE];
+        response[0] = DATA_SIGNATURE;
+        response[3] = match self.security_status() {
+            SecurityStatus::NoUserPassword => 0,
+            SecurityStatus::Locked => 1,
+            SecurityStatus::Unlocked => 2,
+            SecurityStatus::UnlockAttemptExceeded => 6,
+            SecurityStatus::NoEncryption => 7,
+        };
+        response[4] = self.current_cipher.into();
+        let password_length = self.current_cipher.get_password_blob_size().unwrap_or(0);
+        response[6..8].copy_from_slice(&(password_length as u16).to_be_bytes());
+        response[8..12].copy_from_slice(&self.key_reset_enabler.to_be_bytes());
+        response[15] = self.supported_ciphers.len() as u8;
+        for (slot, cipher) in response[16..].iter_mut().zip(&self.supported_ciphers) {
+            *slot = (*cipher).into();
+        }
+
+        Ok(copy_response(&response, data))
+    }
+
+    fn unlock_encryption(&mut self, data: &[u8]) -> Response {
+        match self.security_status() {
+            SecurityStatus::Locked => {}
+            SecurityStatus::UnlockAttemptExceeded => return Err(UNLOCK_ATTEMPTS_EXCEEDED),
+            _ => return Err(ALREADY_UNLOCKED),
+        }
+
+        let password = parse_password_data(data, 1)?;
+        self.check_password_length(password)?;
+
+        if self.password.as_deref() != Some(password) {
+            self.failed_unlock_attempts += 1;
+            return Err(PASSWORD_INCORRECT);
+        }
+
+        self.locked = false;
+        self.failed_unlock_attempts = 0;
+        self.becoming_ready_polls = BECOMING_READY_POLLS;
+
+        Ok(0)
+    }
+
+    fn change_encryption_passphrase(&mut self, data: &[u8]) -> Response {
+        match self.security_status() {
+            SecurityStatus::NoUserPassword | SecurityStatus::Unlocked => {}
+            _ => return Err(COMMAND_SEQUENCE_ERROR),
+        }
+
+        let use_vendor_password = data.get(3).copied().unwrap_or_default();
+        let passwords = parse_password_data(data, 2)?;
+        let (old_password, new_password) = passwords.split_at(passwords.len() / 2);
+
+        let old_password = match use_vendor_password & USE_VENDOR_OLD_PASSWORD {
+            0 => Some(old_password),
+            _ => None,
+        };
+        let new_password = match use_vendor_password & USE_VENDOR_NEW_PASSWORD {
+            0 => Some(new_password),
+            _ => None,
+        };
+
+        for password in [old_password, new_password].into_iter().flatten() {
+            self.check_password_length(password)?;
+        }
+
+        if old_password != self.password.as_deref() {
+            return Err(PASSWORD_INCORRECT);
+        }
+
+        self.password = new_password.map(|p| p.to_vec());
+
+        Ok(0)
+    }
+
+    fn reset_data_encryption_key(&mut self, command: &[u8], data: &[u8]) -> Response {
+        let key_reset_enabler = u32::from_be_bytes(command[2..6].try_into().unwrap());
+        if key_reset_enabler != self.key_reset_enabler {
+            return Err(INVALID_FIELD_IN_CDB);
+        }
+
+        if data.len() < 8 || data[0] != DATA_SIGNATURE {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        let cipher = Cipher::from(data[4]);
+        if !self.supported_ciphers.contains(&cipher) {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        self.current_cipher = cipher;
+        self.password = None;
+        self.locked = false;
+        // the old key is gone, so is everything encrypted with it
+        self.blocks.clear();
+        self.failed_unlock_attempts = 0;
+        self.key_reset_enabler = random();
+
+        Ok(0)
+    }
+
+    fn read(&self, command: &[u8], data: &mut [u8]) -> Response {
+        let (lba, count) = self.block_range(command)?;
+        let length = count as usize * LOGICAL_BLOCK_LENGTH as usize;
+        let data = data.get_mut(..length).ok_or(INVALID_FIELD_IN_CDB)?;
+
+        for (block, lba) in data
+            .chunks_exact_mut(LOGICAL_BLOCK_LENGTH as usize)
+            .zip(lba..)
+        {
+            match self.blocks.get(&lba) {
+                Some(stored) => block.copy_from_slice(stored),
+                None => block.fill(0),
+            }
+        }
+
+        Ok(length)
+    }
+
+    fn write(&mut self, command: &[u8], data: &[u8]) -> Response {
+        let (lba, count) = self.block_range(command)?;
+        let length = count as usize * LOGICAL_BLOCK_LENGTH as usize;
+        let data = data.get(..length).ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+
+        for (block, lba) in data.chunks_exact(LOGICAL_BLOCK_LENGTH as usize).zip(lba..) {
+            self.blocks.insert(lba, block.to_vec());
+        }
+
+        Ok(0)
+    }
+
+    fn write_same(&mut self, command: &[u8], data: &[u8]) -> Response {
+        let unmap = command[1] & 0x08 != 0;
+        if !self.write_same_supported {
+            return Err(INVALID_COMMAND_OPERATION_CODE);
+        }
+        if self.locked {
+            return Err(ACCESS_NOT_AUTHORIZED);
+        }
+
+        let lba = u64::from_be_bytes(command[2..10].try_into().unwrap());
+        let count = u32::from_be_bytes(command[10..14].try_into().unwrap()) as u64;
+        if count > MAXIMUM_WRITE_SAME_LENGTH {
+            return Err(INVALID_FIELD_IN_CDB);
+        }
+        if lba.saturating_add(count) > LOGICAL_BLOCK_COUNT {
+            return Err(LBA_OUT_OF_RANGE);
+        }
+        let block = data
+            .get(..LOGICAL_BLOCK_LENGTH as usize)
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+
+        // zeros read back the same whether they're stored or not
+        if unmap || block.iter().all(|b| *b == 0) {
+            self.blocks.retain(|l, _| !(lba..lba + count).contains(l));
+        } else {
+            for lba in lba..lba + count {
+                self.blocks.insert(lba, block.to_vec());
+            }
+        }
+
+        Ok(0)
+    }
+
+    fn unmap(&mut self, data: &[u8]) -> Response {
+        if !self.unmap_supported {
+            return Err(INVALID_COMMAND_OPERATION_CODE);
+        }
+        if self.locked {
+            return Err(ACCESS_NOT_AUTHORIZED);
+        }
+
+        let descriptor_data_length = data
+            .get(2..4)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+        let descriptors = data
+            .get(8..8 + descriptor_data_length)
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+        if descriptors.len() / 16 > MAXIMUM_UNMAP_BLOCK_DESCRIPTOR_COUNT as usize {
+            return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+        }
+
+        let mut ranges = vec![];
+        for descriptor in descriptors.chunks_exact(16) {
+            let lba = u64::from_be_bytes(descriptor[..8].try_into().unwrap());
+            let count = u32::from_be_bytes(descriptor[8..12].try_into().unwrap());
+            if count > MAXIMUM_UNMAP_LBA_COUNT {
+                return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+            }
+            if lba.saturating_add(count as u64) > LOGICAL_BLOCK_COUNT {
+                return Err(LBA_OUT_OF_RANGE);
+            }
+            ranges.push(lba..lba + count as u64);
+        }
+        self.blocks
+            .retain(|lba, _| !ranges.iter().any(|range| range.contains(lba)));
+
+        Ok(0)
+    }
+
+    fn verify(&self, command: &[u8]) -> Response {
+        self.block_range(command)?;
+
+        Ok(0)
+    }
+
+    /// Logical block address and transfer length of a READ, WRITE or VERIFY command.
+    fn block_range(&self, command: &[u8]) -> Result<(u64, u32), SenseTriple> {
+        if self.locked {
+            return Err(ACCESS_NOT_AUTHORIZED);
+        }
+
+        let (lba, count) = match command[0] {
+            READ_10 | WRITE_10 => (
+                u32::from_be_bytes(command[2..6].try_into().unwrap()) as u64,
+                u16::from_be_bytes([command[7], command[8]]) as u32,
+            ),
+            _ => (
+                u64::from_be_bytes(command[2..10].try_into().unwrap()),
+                u32::from_be_bytes(command[10..14].try_into().unwrap()),
+            ),
+        };
+
+        if count > MAXIMUM_TRANSFER_LENGTH {
+            return Err(INVALID_FIELD_IN_CDB);
+        }
+        if lba.saturating_add(count as u64) > LOGICAL_BLOCK_COUNT {
+            return Err(LBA_OUT_OF_RANGE);
+        }
+
+        Ok((lba, count))
+    }
+
+    fn read_handy_store(&self, command: &[u8], data: &mut [u8]) -> Response {
+        let index = handy_store_index(command)?;
+        let block = self
+            .handy_store
+            .get(&index)
+            .copied()
+            .unwrap_or([0; HANDY_STORE_BLOCK_SIZE]);
+
+        Ok(copy_response(&block, data))
+    }
+
+    fn write_handy_store(&mut self, command: &[u8], data: &[u8]) -> Response {
+        let index = handy_store_index(command)?;
+        let block: [u8; HANDY_STORE_BLOCK_SIZE] = data
+            .get(..HANDY_STORE_BLOCK_SIZE)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)?;
+        self.handy_store.insert(index, block);
+
+        Ok(0)
+    }
+
+    fn check_password_length(&self, password: &[u8]) -> Result<(), SenseTriple> {
+        match self.current_cipher.get_password_blob_size() {
+            Ok(length) if length == password.len() => Ok(()),
+            _ => Err(INVALID_FIELD_IN_PARAMETER_LIST),
+        }
+    }
+}
+
+/// Validates the 8 byte header shared by the security commands and returns the password bytes after it.
+fn parse_password_data(data: &[u8], password_count: usize) -> Result<&[u8], SenseTriple> {
+    if data.len() < 8 || data[0] != DATA_SIGNATURE {
+        return Err(INVALID_FIELD_IN_PARAMETER_LIST);
+    }
+
+    let password_length = u16::from_be_bytes([data[6], data[7]]) as usize;
+    data.get(8..8 + password_length * password_count)
+        .ok_or(INVALID_FIELD_IN_PARAMETER_LIST)
+}
+
+fn handy_store_index(command: &[u8]) -> Result<u32, SenseTriple> {
+    let index = u32::from_be_bytes(command[2..6].try_into().unwrap());
+    let transfer_length = u16::from_be_bytes([command[7], command[8]]);
+    if index > LAST_HANDY_BLOCK_ADDRESS || transfer_length != 1 {
+        return Err(LBA_OUT_OF_RANGE);
+    }
+
+    Ok(index)
+}
+
+fn ata_status_return(registers: &AtaRegisters) -> [u8; ATA_STATUS_RETURN_DESCRIPTOR_LENGTH] {
+    let count = registers.count.to_be_bytes();
+    let lba = registers.lba.to_be_bytes();
+
+    [
+        0x09,
+        ATA_STATUS_RETURN_DESCRIPTOR_LENGTH as u8 - 2,
+        registers.extend as u8,
+        registers.features_error as u8,
+        count[0],
+        count[1],
+        lba[4],
+        lba[7],
+        lba[3],
+        lba[6],
+        lba[2],
+        lba[5],
+        registers.device,
+        registers.command_status,
+    ]
+}
+
+fn identify_device() -> [u8; ATA_SECTOR_SIZE] {
+    let mut sector = [0u8; ATA_SECTOR_SIZE];
+    let mut set_word = |index: usize, value: u16| {
+        sector[index * 2..index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+    };
+
+    for (start, length, value) in [
+        (10, 20, DISK_SERIAL_NUMBER),
+        (23, 8, DISK_FIRMWARE_REVISION),
+        (27, 40, DISK_MODEL_NUMBER),
+    ] {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(length, b' ');
+        for (i, pair) in bytes.chunks(2).enumerate() {
+            set_word(start + i, u16::from_be_bytes([pair[0], pair[1]]));
+        }
+    }
+    // SMART, security, APM and 48 bit addressing supported, APM enabled
+    set_word(82, 0x0003);
+    set_word(83, 0x0408);
+    set_word(86, 0x0408);
+    set_word(91, 0x0080);
+    for i in 0..4 {
+        set_word(100 + i, (DISK_SECTOR_COUNT >> (i * 16)) as u16);
+    }
+    // 8 logical sectors per physical sector
+    set_word(106, 0x6003);
+    // security supported and frozen
+    set_word(128, 0x0009);
+    set_word(206, 0x0001);
+    set_word(217, DISK_ROTATION_RATE);
+    sector[510] = 0xa5;
+
+    with_checksum(sector)
+}
+
+fn smart_thresholds() -> [u8; ATA_SECTOR_SIZE] {
+    let mut sector = [0u8; ATA_SECTOR_SIZE];
+    sector[..2].copy_from_slice(&SMART_DATA_REVISION.to_le_bytes());
+
+    for (index, (id, _, threshold)) in SMART_ATTRIBUTES.into_iter().enumerate() {
+        sector[2 + index * 12] = id;
+        sector[3 + index * 12] = threshold;
+    }
+
+    with_checksum(sector)
+}
+
+fn with_checksum(mut sector: [u8; ATA_SECTOR_SIZE]) -> [u8; ATA_SECTOR_SIZE] {
+    let sum = sector[..ATA_SECTOR_SIZE - 1]
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b));
+    sector[ATA_SECTOR_SIZE - 1] = sum.wrapping_neg();
+
+    sector
+}
+
+fn log_parameter(parameter_code: u16, value: &[u8]) -> Vec<u8> {
+    let mut parameter = parameter_code.to_be_bytes().to_vec();
+    // binary format list
+    parameter.push(0x03);
+    parameter.push(value.len() as u8);
+    parameter.extend_from_slice(value);
+
+    parameter
+}
+
+fn read_capacity(data: &mut [u8]) -> Response {
+    let mut response = [0u8; 32];
+    response[..8].copy_from_slice(&(LOGICAL_BLOCK_COUNT - 1).to_be_bytes());
+    response[8..12].copy_from_slice(&LOGICAL_BLOCK_LENGTH.to_be_bytes());
+    // LBPME
+    response[14] = 0x80;
+
+    Ok(copy_response(&response, data))
+}
+
+fn read_handy_capacity(data: &mut [u8]) -> Response {
+    let mut response = [0u8; 12];
+    response[..4].copy_from_slice(&LAST_HANDY_BLOCK_ADDRESS.to_be_bytes());
+    response[4..8].copy_from_slice(&(HANDY_STORE_BLOCK_SIZE as u32).to_be_bytes());
+    response[10..12].copy_from_slice(&1u16.to_be_bytes());
+
+    Ok(copy_response(&response, data))
+}
+
+fn copy_response(response: &[u8], data: &mut [u8]) -> usize {
+    let length = usize::min(response.len(), data.len());
+    data[..length].copy_from_slice(&response[..length]);
+
+    length
+}
+
+fn default_mode_page(page_code: u8) -> Vec<u8> {
+    match page_code {
+        0x1a => default_power_condition_page(),
+        0x20 => default_device_configuration_page(),
+        _ => default_operations_page(),
+    }
+}
+
+/// Set bits can be changed by MODE SELECT
+fn changeable_mask(page_code: u8) -> Vec<u8> {
+    let mut mask = vec![0u8; default_mode_page(page_code).len()];
+    mask[0] = 0x80 | page_code;
+    mask[1] = (mask.len() - 2) as u8;
+    match page_code {
+        // STANDBY_Z and its timer
+        0x1a => {
+            mask[3] = 0x01;
+            mask[8..12].fill(0xff);
+        }
+        // DISABLE_CDROM
+        0x20 => mask[4] = 0x02,
+        // POWER_LED_BRITE
+        _ => mask[8] = 0xff,
+    }
+
+    mask
+}
+
+fn default_power_condition_page() -> Vec<u8> {
+    let mut page = vec![0u8; 0x26 + 2];
+    page[0] = 0x80 | 0x1a;
+    page[1] = 0x26;
+    // STANDBY_Z enabled, 30 minutes in 100 milliseconds
+    page[3] = 0x01;
+    page[8..12].copy_from_slice(&18_000u32.to_be_bytes());
+
+    page
+}
+
+fn default_device_configuration_page() -> Vec<u8> {
+    let mut page = vec![0u8; 0x06 + 2];
+    page[0] = 0x80 | 0x20;
+    page[1] = 0x06;
+    page[2] = 0x30;
+    page[4] = 0b0011_0000;
+
+    page
+}
+
+fn default_operations_page() -> Vec<u8> {
+    let mut page = vec![0u8; 0x0a + 2];
+    page[0] = 0x80 | 0x21;
+    page[1] = 0x0a;
+    page[2] = 0x30;
+    page[8] = 0xff;
+
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::Duration,
+    };
+
+    use libscsi::{ReplayTransport, Scsi};
+
+    use super::*;
+    use crate::{
+        device_configuration_page, operations_page,
+        password_utility::{change_password, create_password_blob},
+        power_condition_mode_page,
+        security_block::{read_security_block, write_security_block},
+        Error, ModeSetting, WdVsc, DEFAULT_ITERATION_COUNT, DEFAULT_SALT,
+    };
+
+    fn password_blob(password: &str) -> Vec<u8> {
+        create_password_blob(
+            Cipher::Aes256Xts,
+            &DEFAULT_SALT,
+            DEFAULT_ITERATION_COUNT,
+            password,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn password_flow() {
+        let device = Scsi::with_transport("simulator", MyPassportSimulator::new());
+        assert_eq!(
+            device.inquiry_product_identification().unwrap(),
+            "My Passport 25E2"
+        );
+        let inquiry = device.inquiry_standard().unwrap();
+        assert_eq!(inquiry.vendor_identification, "WD");
+        assert_eq!(inquiry.product_revision_level, "4004");
+        assert_eq!(inquiry.version_descriptors, VERSION_DESCRIPTORS);
+        assert_eq!(
+            device.inquiry_supported_vpd_pages().unwrap(),
+            SUPPORTED_VPD_PAGES
+        );
+        assert_eq!(device.inquiry_unit_serial_number().unwrap(), "WX11A1234567");
+        assert_eq!(
+            device
+                .inquiry_world_wide_name()
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "0x50014ee2b1234567"
+        );
+
+        let status = device.encryption_status().unwrap();
+        assert!(matches!(
+            status.security_status,
+            SecurityStatus::NoUserPassword
+        ));
+        change_password(
+            &device,
+            &status,
+            Some("pass".to_owned()),
+            None,
+            Some("hint".to_owned()),
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(read_security_block(&device).unwrap().hint, "hint");
+
+        device.transport().power_cycle();
+        assert!(matches!(
+            device.encryption_status().unwrap().security_status,
+            SecurityStatus::Locked
+        ));
+        assert!(matches!(
+            device.unlock_encryption(password_blob("wrong")),
+            Err(Error::PasswordIncorrect)
+        ));
+        device.unlock_encryption(password_blob("pass")).unwrap();
+        assert!(matches!(
+            device.unlock_encryption(password_blob("pass")),
+            Err(Error::NotLocked(_))
+        ));
+    }
+
+    #[test]
+    fn unlock_attempts_exceeded() {
+        let device = Scsi::with_transport(
+            "simulator",
+            MyPassportSimulator::with_password(password_blob("pass")),
+        );
+
+        for _ in 0..MAX_UNLOCK_ATTEMPTS {
+            assert!(matches!(
+                device.unlock_encryption(password_blob("wrong")),
+                Err(Error::PasswordIncorrect)
+            ));
+        }
+        assert!(matches!(
+            device.unlock_encryption(password_blob("pass")),
+            Err(Error::ExceedUnlockAttempts)
+        ));
+
+        device.transport().power_cycle();
+        device.unlock_encryption(password_blob("pass")).unwrap();
+    }
+
+    #[test]
+    fn erase() {
+        let device = Scsi::with_transport(
+            "simulator",
+            MyPassportSimulator::with_password(password_blob("pass")),
+        );
+        let status = device.encryption_status().unwrap();
+        device
+            .reset_data_encryption_key(Cipher::FullDiscEncryption, status.key_reset_enabler)
+            .unwrap();
+
+        let status = device.encryption_status().unwrap();
+        assert!(matches!(
+            status.security_status,
+            SecurityStatus::NoUserPassword
+        ));
+        assert_eq!(status.current_cipher, Cipher::FullDiscEncryption);
+    }
+
+    #[test]
+    fn mode_pages() {
+        let device = Scsi::with_transport("simulator", MyPassportSimulator::new());
+
+        assert_eq!(
+            power_condition_mode_page::get_sleep_timer(&device).unwrap(),
+            1800
+        );
+        power_condition_mode_page::set_sleep_timer(&device, 600, true).unwrap();
+        assert_eq!(
+            power_condition_mode_page::get_sleep_timer(&device).unwrap(),
+            600
+        );
+
+        assert_eq!(operations_page::get_led_brightness(&device).unwrap(), 0xff);
+        operations_page::set_led_brightness(&device, 0, true).unwrap();
+        assert_eq!(operations_page::get_led_brightness(&device).unwrap(), 0);
+
+        assert!(device_configuration_page::get_virtual_cd_status(&device).unwrap());
+        device_configuration_page::set_virtual_cd_status(&device, false, true).unwrap();
+        assert!(!device_configuration_page::get_virtual_cd_status(&device).unwrap());
+
+        assert_eq!(
+            power_condition_mode_page::get_sleep_timer_setting(&device).unwrap(),
+            ModeSetting {
+                current: 600,
+                default: 1800,
+                changeable: true,
+            }
+        );
+        let led_brightness = operations_page::get_led_brightness_setting(&device).unwrap();
+        assert_eq!((led_brightness.current, led_brightness.default), (0, 0xff));
+        assert!(led_brightness.changeable);
+        let virtual_cd = device_configuration_page::get_virtual_cd_setting(&device).unwrap();
+        assert!(!virtual_cd.current && virtual_cd.default && virtual_cd.changeable);
+
+        // saved pages survive a power cycle, the others don't
+        power_condition_mode_page::set_sleep_timer(&device, 900, false).unwrap();
+        assert_eq!(
+            power_condition_mode_page::get_sleep_timer(&device).unwrap(),
+            900
+        );
+        device.transport().power_cycle();
+        assert_eq!(operations_page::get_led_brightness(&device).unwrap(), 0);
+        assert_eq!(
+            power_condition_mode_page::get_sleep_timer(&device).unwrap(),
+            600
+        );
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("wd_vsc_trace_{}.txt", std::process::id()));
+
+        let mut device = Scsi::with_transport("simulator", MyPassportSimulator::new());
+        device.start_recording(File::create(&path).unwrap());
+        write_security_block(&device, 2000, DEFAULT_SALT, "hint".to_owned()).unwrap();
+        let status = device.encryption_status().unwrap();
+        let block = read_security_block(&device).unwrap();
+        device.stop_recording().unwrap();
+
+        let replay = Scsi::with_transport("replay", ReplayTransport::open(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        write_security_block(&replay, 2000, DEFAULT_SALT, "hint".to_owned()).unwrap();
+        let replayed_status = replay.encryption_status().unwrap();
+        let replayed_block = read_security_block(&replay).unwrap();
+
+        assert_eq!(replayed_status.current_cipher, status.current_cipher);
+        assert_eq!(replayed_status.key_reset_enabler, status.key_reset_enabler);
+        assert_eq!(replayed_status.supported_ciphers, status.supported_ciphers);
+        assert_eq!(replayed_block.iteration_count, block.iteration_count);
+        assert_eq!(replayed_block.hint, block.hint);
+        assert_eq!(replay.transport().remaining(), 0);
+    }
+
+    #[test]
+    fn encryption_status_timeout() {
+        let device = Scsi::with_transport("simulator", MyPassportSimulator::new());
+        device.encryption_status().unwrap();
+        assert_eq!(device.transport().last_timeout(), Duration::from_secs(10));
+    }
+}